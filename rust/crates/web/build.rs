@@ -0,0 +1,31 @@
+use std::process::Command;
+
+/// Sets `GIT_SHA` for `/version`, from the environment (e.g. a container
+/// build without `.git`) or from `git rev-parse HEAD`.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    if let (Some(git_dir), Some(head_ref)) = (
+        git(&["rev-parse", "--git-dir"]),
+        git(&["rev-parse", "--symbolic-full-name", "HEAD"]),
+    ) {
+        // a commit or a checkout changes one of these
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/{}", git_dir, head_ref);
+    }
+    let git_sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .or_else(|| git(&["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GIT_SHA={}", git_sha);
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|s| s.trim().to_owned())
+}
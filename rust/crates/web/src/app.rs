@@ -1,24 +1,57 @@
 use std::sync::Arc;
 
 use crate::{
-    handler::{graphql::GraphQLSchema, HasGraphQLSchema},
-    infra::store::InMemoryStore,
+    handler::{
+        graphql::{GraphQLLimits, GraphQLSchema, IdempotencyKeys, PersistedQueries},
+        HasGraphQLSchema,
+    },
+    infra::{instrumented_store::InstrumentedStore, store::InMemoryStore},
+    shutdown::{HasShutdown, Shutdown},
     use_case::{HasStore, Store},
 };
 
 #[derive(Clone)]
 pub struct App {
     graphql_schema: GraphQLSchema,
-    store: Arc<InMemoryStore>,
+    shutdown: Shutdown,
+    store: Arc<dyn Store + Send + Sync>,
 }
 
 impl App {
-    pub fn example() -> Self {
+    pub fn new(store: Arc<dyn Store + Send + Sync>) -> Self {
         Self {
             graphql_schema: GraphQLSchema::new(),
-            store: Arc::new(InMemoryStore::example()),
+            shutdown: Shutdown::new(),
+            store: Arc::new(InstrumentedStore(store)),
+        }
+    }
+
+    pub fn with_graphql_limits(self, limits: GraphQLLimits) -> Self {
+        Self {
+            graphql_schema: self.graphql_schema.with_limits(limits),
+            ..self
         }
     }
+
+    pub fn with_persisted_queries(self, persisted_queries: PersistedQueries) -> Self {
+        Self {
+            graphql_schema: self
+                .graphql_schema
+                .with_persisted_queries(persisted_queries),
+            ..self
+        }
+    }
+
+    pub fn with_idempotency_keys(self, idempotency_keys: IdempotencyKeys) -> Self {
+        Self {
+            graphql_schema: self.graphql_schema.with_idempotency_keys(idempotency_keys),
+            ..self
+        }
+    }
+
+    pub fn example() -> Self {
+        Self::new(Arc::new(InMemoryStore::example()))
+    }
 }
 
 impl HasGraphQLSchema for App {
@@ -27,6 +60,12 @@ impl HasGraphQLSchema for App {
     }
 }
 
+impl HasShutdown for App {
+    fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+}
+
 impl HasStore for App {
     fn store(&self) -> Arc<dyn Store + Send + Sync> {
         self.store.clone()
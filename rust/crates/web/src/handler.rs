@@ -1,13 +1,257 @@
+pub mod api;
+mod cors;
+mod export;
 pub mod graphql;
+mod health;
+mod http_options;
+mod import;
+mod metrics;
 mod root;
+mod sync;
+mod trace;
 
-use axum::Router;
+use axum::{
+    body::Body,
+    extract::DefaultBodyLimit,
+    http::{header, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use http_body::Limited;
+use tower_http::{
+    compression::{predicate::SizeAbove, CompressionLayer, DefaultPredicate, Predicate as _},
+    limit::RequestBodyLimitLayer,
+    set_header::SetResponseHeaderLayer,
+    timeout::TimeoutLayer,
+};
 
-pub use self::graphql::HasGraphQLSchema;
-use crate::use_case::HasStore;
+pub use self::{graphql::HasGraphQLSchema, http_options::HttpOptions};
+use crate::{
+    shutdown::HasShutdown,
+    use_case::{self, HasStore},
+};
 
-pub fn route<T: Clone + HasGraphQLSchema + HasStore + Send + Sync + 'static>() -> Router<T> {
+/// The request body seen by the routes, limited to `HttpOptions::max_body_bytes`.
+pub type LimitedBody = Limited<Body>;
+
+/// Smaller responses are sent as they are, as compressing them would not save
+/// a packet.
+const MIN_COMPRESSED_SIZE: u16 = 1_024;
+
+pub fn route<T: Clone + HasGraphQLSchema + HasShutdown + HasStore + Send + Sync + 'static>(
+) -> Router<T> {
+    route_with(HttpOptions::default())
+}
+
+/// The routes with the middleware stack, outermost first: tracing, security
+/// headers, CORS, compression (gzip or brotli, streamed), the timeout and the
+/// body limit.
+pub fn route_with<T: Clone + HasGraphQLSchema + HasShutdown + HasStore + Send + Sync + 'static>(
+    options: HttpOptions,
+) -> Router<T> {
     Router::new()
+        .merge(api::route::<T>())
+        .merge(export::route::<T>())
         .merge(graphql::route::<T>())
+        .merge(health::route::<T>())
+        .merge(import::route::<T>())
+        .merge(metrics::route::<T>())
         .merge(root::route::<T>())
+        .merge(sync::route::<T>())
+        // the body is limited by `RequestBodyLimitLayer` alone
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(options.max_body_bytes))
+        .layer(TimeoutLayer::new(options.request_timeout))
+        .layer(
+            CompressionLayer::new()
+                .compress_when(DefaultPredicate::new().and(SizeAbove::new(MIN_COMPRESSED_SIZE))),
+        )
+        .layer(cors::layer(&options.allowed_origins))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("DENY"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::REFERRER_POLICY,
+            HeaderValue::from_static("no-referrer"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_static("max-age=31536000; includeSubDomains"),
+        ))
+        .layer(middleware::from_fn(trace::middleware))
+}
+
+#[derive(serde::Serialize)]
+struct ErrorResponse {
+    code: &'static str,
+    message: String,
+}
+
+/// Maps the error to its HTTP status with a JSON `{ code, message }` body.
+fn error_response(e: use_case::Error) -> Response {
+    let status = match e {
+        use_case::Error::AlreadyExists(_)
+        | use_case::Error::Conflict(_)
+        | use_case::Error::VersionConflict { .. } => StatusCode::CONFLICT,
+        use_case::Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        use_case::Error::NotFound(_) => StatusCode::NOT_FOUND,
+        use_case::Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        use_case::Error::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        use_case::Error::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            code: e.code(),
+            message: e.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use axum::{
+        body::{Body, Bytes},
+        http::HeaderValue,
+    };
+
+    use super::*;
+    use crate::{
+        app::App,
+        test_utils::{request, send_request, StatusCode},
+    };
+
+    #[tokio::test]
+    async fn test_security_headers() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        for uri in ["/", "/not-found"] {
+            let response = send_request(app.clone(), request("GET", uri, "")?).await?;
+            assert_ne!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            let headers = response.headers();
+            assert_eq!(headers["x-content-type-options"], "nosniff");
+            assert_eq!(headers["x-frame-options"], "DENY");
+            assert_eq!(headers["referrer-policy"], "no-referrer");
+            assert_eq!(
+                headers["strict-transport-security"],
+                "max-age=31536000; includeSubDomains"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() -> anyhow::Result<()> {
+        let app = route_with(HttpOptions {
+            max_body_bytes: 64,
+            ..Default::default()
+        })
+        .with_state(App::example());
+        let small = r#"{"query":"{ hello }"}"#;
+        let large = format!(
+            r#"{{"query":"{{ hello }}","operationName":"{}"}}"#,
+            "a".repeat(64)
+        );
+
+        let response = send_request(app.clone(), request("POST", "/graphql", small)?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut with_length = request("POST", "/graphql", large.clone())?;
+        with_length
+            .headers_mut()
+            .insert("content-length", large.len().into());
+        let response = send_request(app.clone(), with_length).await?;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            for chunk in large.as_bytes().chunks(16) {
+                if sender
+                    .send_data(Bytes::copy_from_slice(chunk))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        // without a content-length the body is cut off while it is read
+        let response = send_request(app, request("POST", "/import", body)?).await?;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compression() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let get = |uri: &str, accept_encoding: Option<&'static str>| {
+            let mut request = request("GET", uri, "")?;
+            if let Some(accept_encoding) = accept_encoding {
+                request
+                    .headers_mut()
+                    .insert("accept-encoding", HeaderValue::from_static(accept_encoding));
+            }
+            anyhow::Ok(request)
+        };
+
+        for uri in ["/graphql", "/export?format=csv"] {
+            let response = send_request(app.clone(), get(uri, None)?).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(!response.headers().contains_key("content-encoding"));
+            let plain = hyper::body::to_bytes(response.into_body()).await?;
+
+            let response = send_request(app.clone(), get(uri, Some("gzip"))?).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-encoding"], "gzip");
+            assert!(!response.headers().contains_key("content-length"));
+            let compressed = hyper::body::to_bytes(response.into_body()).await?;
+            let mut decoded = vec![];
+            flate2::read::GzDecoder::new(compressed.as_ref()).read_to_end(&mut decoded)?;
+            assert_eq!(decoded, plain);
+
+            let response = send_request(app.clone(), get(uri, Some("br"))?).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-encoding"], "br");
+            let compressed = hyper::body::to_bytes(response.into_body()).await?;
+            let mut decoded = vec![];
+            brotli::Decompressor::new(compressed.as_ref(), 4_096).read_to_end(&mut decoded)?;
+            assert_eq!(decoded, plain);
+        }
+
+        let response = send_request(app.clone(), get("/graphql", Some("gzip;q=0, *"))?).await?;
+        assert_ne!(
+            response.headers().get("content-encoding"),
+            Some(&HeaderValue::from_static("gzip"))
+        );
+        let response = send_request(app.clone(), get("/graphql", Some("gzip;q=0"))?).await?;
+        assert!(!response.headers().contains_key("content-encoding"));
+
+        let response = send_request(app, get("/healthz", Some("gzip"))?).await?;
+        assert!(!response.headers().contains_key("content-encoding"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timeout() -> anyhow::Result<()> {
+        let app = Router::<(), Body>::new()
+            .route(
+                "/slow",
+                axum::routing::get(|| async {
+                    tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                    ""
+                }),
+            )
+            .layer(TimeoutLayer::new(std::time::Duration::from_millis(10)));
+        let response = send_request(app, request("GET", "/slow", "")?).await?;
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        Ok(())
+    }
 }
@@ -0,0 +1,731 @@
+//! A REST/JSON API over the store, for scripts that would rather not write
+//! GraphQL. The bodies are the `use_case::data` representations.
+//!
+//! Every entity response has a strong `ETag`. A write with `If-Match` fails
+//! with 412 unless the entity still has one of the tags, and a `PUT` with
+//! `If-None-Match: *` only creates. A `GET` with a matching `If-None-Match` is
+//! answered with 304. A conditional write is also conditional on the version
+//! it compared, so a writer that races it fails with 412 as well.
+
+mod openapi;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing, Json, Router,
+};
+use sha2::{Digest as _, Sha256};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    model,
+    use_case::{
+        self,
+        data::{CheckData, CheckListData, ItemData, PriceData},
+        HasStore,
+    },
+};
+
+pub use self::openapi::openapi;
+use super::{error_response, ErrorResponse, LimitedBody};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1_000;
+
+#[derive(serde::Deserialize)]
+struct PageQuery {
+    /// The last id of the previous page.
+    after: Option<String>,
+    limit: Option<usize>,
+}
+
+impl PageQuery {
+    fn limit(&self) -> Result<usize, use_case::Error> {
+        match self.limit {
+            None => Ok(DEFAULT_LIMIT),
+            Some(limit) if (1..=MAX_LIMIT).contains(&limit) => Ok(limit),
+            Some(limit) => Err(use_case::Error::InvalidInput(format!(
+                "limit {} is not in 1..={}",
+                limit, MAX_LIMIT
+            ))),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckListsResponse {
+    check_lists: Vec<CheckListData>,
+}
+
+#[derive(serde::Serialize)]
+struct ChecksResponse {
+    checks: Vec<CheckData>,
+}
+
+#[derive(serde::Serialize)]
+struct ItemsResponse {
+    items: Vec<ItemData>,
+}
+
+/// The item without its id, which is in the path.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct PutItemRequest {
+    name: String,
+    archived_at: Option<String>,
+    category: Option<String>,
+    emoji: Option<String>,
+    note: Option<String>,
+    sort_order: Option<i64>,
+    unit: Option<String>,
+}
+
+/// The check without its check list and item, which are in the path.
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct PutCheckRequest {
+    /// RFC 3339. Defaults to now.
+    checked_at: Option<String>,
+    note: Option<String>,
+    price: Option<PriceData>,
+    quantity: Option<f64>,
+    shop: Option<String>,
+}
+
+/// A strong tag of the JSON representation.
+fn etag<T: serde::Serialize>(value: &T) -> HeaderValue {
+    let json = serde_json::to_vec(value).expect("data is serializable");
+    let hash = format!("{:x}", Sha256::digest(json));
+    HeaderValue::from_str(&format!("\"{}\"", &hash[..32])).expect("hex is a valid header value")
+}
+
+/// Whether the `If-Match` or `If-None-Match` header lists the tag, or `*`
+/// while the entity exists. `If-Match` compares strongly, so a weak tag never
+/// matches; `If-None-Match` compares weakly, by the opaque part (RFC 9110).
+fn matches(headers: &HeaderMap, name: header::HeaderName, current: Option<&HeaderValue>) -> bool {
+    let weak = name != header::IF_MATCH;
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| match current {
+            Some(current) => {
+                let tag = match tag.strip_prefix("W/") {
+                    Some(opaque) if weak => opaque,
+                    _ => tag,
+                };
+                tag == "*" || tag.as_bytes() == current.as_bytes()
+            }
+            None => false,
+        })
+}
+
+/// Whether the preconditions of a write hold for the current tag (`None` if
+/// the entity does not exist).
+fn preconditions_hold(headers: &HeaderMap, current: Option<&HeaderValue>) -> bool {
+    (!headers.contains_key(header::IF_MATCH) || matches(headers, header::IF_MATCH, current))
+        && !matches(headers, header::IF_NONE_MATCH, current)
+}
+
+/// The version a conditional write expects, `None` for an unconditional one.
+fn expected_version(headers: &HeaderMap, current_version: u64) -> Option<u64> {
+    (headers.contains_key(header::IF_MATCH) || headers.contains_key(header::IF_NONE_MATCH))
+        .then_some(current_version)
+}
+
+/// Answers a write that lost a race with a conditional one as if the
+/// preconditions did not hold.
+fn write_error_response(e: use_case::Error) -> Response {
+    match e {
+        use_case::Error::VersionConflict { .. } => precondition_failed(),
+        e => error_response(e),
+    }
+}
+
+fn precondition_failed() -> Response {
+    (
+        StatusCode::PRECONDITION_FAILED,
+        Json(ErrorResponse {
+            code: "PRECONDITION_FAILED",
+            message: "the entity does not match If-Match or If-None-Match".to_owned(),
+        }),
+    )
+        .into_response()
+}
+
+/// Responds to a `GET` with the entity and its tag, or with 304 if the client
+/// has it.
+fn entity_response<T: serde::Serialize>(headers: &HeaderMap, value: T) -> Response {
+    let tag = etag(&value);
+    if matches(headers, header::IF_NONE_MATCH, Some(&tag)) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, tag)]).into_response();
+    }
+    ([(header::ETAG, tag)], Json(value)).into_response()
+}
+
+/// Responds to a `PUT` with the stored entity and its tag.
+fn written_response<T: serde::Serialize>(created: bool, value: T) -> Response {
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    (status, [(header::ETAG, etag(&value))], Json(value)).into_response()
+}
+
+fn now() -> Result<String, use_case::Error> {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .map_err(|e| use_case::Error::Unknown(e.to_string()))
+}
+
+async fn find_item(
+    store: &(dyn use_case::Store + Send + Sync),
+    id: &str,
+) -> Result<Option<model::Item>, use_case::Error> {
+    // TODO: Store::find_item
+    Ok(store
+        .find_all_items()
+        .await?
+        .into_iter()
+        .find(|item| item.id == id))
+}
+
+async fn find_check_list(
+    store: &(dyn use_case::Store + Send + Sync),
+    date: &str,
+) -> Result<Option<model::CheckList>, use_case::Error> {
+    model::validate_date(date)?;
+    Ok(store
+        .find_all_check_lists()
+        .await?
+        .into_iter()
+        .find(|check_list| check_list.date == date))
+}
+
+async fn find_check(
+    store: &(dyn use_case::Store + Send + Sync),
+    date: &str,
+    item_id: &str,
+) -> Result<Option<(model::CheckList, Option<model::Check>)>, use_case::Error> {
+    let Some(check_list) = find_check_list(store, date).await? else {
+        return Ok(None);
+    };
+    let check = store
+        .find_checks_by_check_list_id(check_list.id.clone())
+        .await?
+        .into_iter()
+        .find(|check| check.item_id == item_id);
+    Ok(Some((check_list, check)))
+}
+
+fn not_found(what: String) -> Response {
+    error_response(use_case::Error::NotFound(what))
+}
+
+async fn list_items<T: HasStore>(
+    State(state): State<T>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<ItemsResponse>, Response> {
+    let limit = query.limit().map_err(error_response)?;
+    let items = state
+        .store()
+        .find_items(query.after, limit)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(ItemsResponse {
+        items: items.into_iter().map(ItemData::from).collect(),
+    }))
+}
+
+async fn get_item<T: HasStore>(
+    State(state): State<T>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let item = find_item(state.store().as_ref(), &id)
+        .await
+        .map_err(error_response)?
+        .ok_or_else(|| not_found(format!("item {}", id)))?;
+    Ok(entity_response(&headers, ItemData::from(item)))
+}
+
+/// Creates or replaces the item. Responds with 201 if it is created.
+async fn put_item<T: HasStore>(
+    State(state): State<T>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<PutItemRequest>,
+) -> Result<Response, Response> {
+    let store = state.store();
+    let current = find_item(store.as_ref(), &id)
+        .await
+        .map_err(error_response)?;
+    let current_tag = current.clone().map(|item| etag(&ItemData::from(item)));
+    if !preconditions_hold(&headers, current_tag.as_ref()) {
+        return Err(precondition_failed());
+    }
+    let item = model::Item {
+        id,
+        name: request.name,
+        archived_at: request.archived_at,
+        category: request.category,
+        emoji: request.emoji,
+        note: request.note,
+        sort_order: request.sort_order,
+        unit: request.unit,
+        ..Default::default()
+    };
+    item.validate()
+        .map_err(|e| error_response(use_case::Error::from(e)))?;
+    let current_version = current.as_ref().map_or(0, |item| item.version);
+    let item = store
+        .compare_and_store_item(item, expected_version(&headers, current_version))
+        .await
+        .map_err(write_error_response)?;
+    Ok(written_response(current.is_none(), ItemData::from(item)))
+}
+
+/// Deletes the item and its checks permanently.
+async fn delete_item<T: HasStore>(
+    State(state): State<T>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Response> {
+    let store = state.store();
+    let item = find_item(store.as_ref(), &id)
+        .await
+        .map_err(error_response)?
+        .ok_or_else(|| not_found(format!("item {}", id)))?;
+    let version = item.version;
+    if !preconditions_hold(&headers, Some(&etag(&ItemData::from(item)))) {
+        return Err(precondition_failed());
+    }
+    store
+        .compare_and_purge_item(id, expected_version(&headers, version))
+        .await
+        .map_err(write_error_response)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_item_checks<T: HasStore>(
+    State(state): State<T>,
+    Path(id): Path<String>,
+) -> Result<Json<ChecksResponse>, Response> {
+    let checks = state
+        .store()
+        .find_checks_by_item_id(id)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(ChecksResponse {
+        checks: checks.into_iter().map(CheckData::from).collect(),
+    }))
+}
+
+async fn list_check_lists<T: HasStore>(
+    State(state): State<T>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<CheckListsResponse>, Response> {
+    let limit = query.limit().map_err(error_response)?;
+    let check_lists = state
+        .store()
+        .find_check_lists(query.after, limit)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(CheckListsResponse {
+        check_lists: check_lists.into_iter().map(CheckListData::from).collect(),
+    }))
+}
+
+async fn get_check_list<T: HasStore>(
+    State(state): State<T>,
+    Path(date): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let check_list = find_check_list(state.store().as_ref(), &date)
+        .await
+        .map_err(error_response)?
+        .ok_or_else(|| not_found(format!("check list {}", date)))?;
+    Ok(entity_response(&headers, CheckListData::from(check_list)))
+}
+
+async fn list_check_list_checks<T: HasStore>(
+    State(state): State<T>,
+    Path(date): Path<String>,
+) -> Result<Json<ChecksResponse>, Response> {
+    let store = state.store();
+    let check_list = find_check_list(store.as_ref(), &date)
+        .await
+        .map_err(error_response)?
+        .ok_or_else(|| not_found(format!("check list {}", date)))?;
+    let checks = store
+        .find_checks_by_check_list_id(check_list.id)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(ChecksResponse {
+        checks: checks.into_iter().map(CheckData::from).collect(),
+    }))
+}
+
+async fn get_check<T: HasStore>(
+    State(state): State<T>,
+    Path((date, item_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let check = find_check(state.store().as_ref(), &date, &item_id)
+        .await
+        .map_err(error_response)?
+        .and_then(|(_, check)| check)
+        .ok_or_else(|| not_found(format!("check {} {}", date, item_id)))?;
+    Ok(entity_response(&headers, CheckData::from(check)))
+}
+
+/// Checks the item, creating the check list of the date along with the check
+/// if there is none.
+/// Checking a checked item replaces its details, as `checkItem` does.
+async fn put_check<T: HasStore>(
+    State(state): State<T>,
+    Path((date, item_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(request): Json<PutCheckRequest>,
+) -> Result<Response, Response> {
+    let store = state.store();
+    let found = find_check(store.as_ref(), &date, &item_id)
+        .await
+        .map_err(error_response)?;
+    let current = found.as_ref().and_then(|(_, check)| check.clone());
+    let current_tag = current.clone().map(|check| etag(&CheckData::from(check)));
+    if !preconditions_hold(&headers, current_tag.as_ref()) {
+        return Err(precondition_failed());
+    }
+    let (check_list, new_check_list) = match found {
+        Some((check_list, _)) => (check_list, None),
+        None => {
+            let check_list = model::CheckList {
+                id: uuid::Uuid::new_v4().to_string(),
+                date: date.clone(),
+                ..Default::default()
+            };
+            (check_list.clone(), Some(check_list))
+        }
+    };
+    let check = model::Check {
+        check_list_id: check_list.id,
+        item_id,
+        checked_at: Some(match request.checked_at {
+            Some(checked_at) => checked_at,
+            None => now().map_err(error_response)?,
+        }),
+        note: request.note,
+        price: request.price.map(model::Price::from),
+        quantity: request.quantity,
+        shop: request.shop,
+        ..Default::default()
+    };
+    check
+        .validate()
+        .map_err(|e| error_response(use_case::Error::from(e)))?;
+    let check = match new_check_list {
+        Some(check_list) => {
+            let item_id = check.item_id.clone();
+            store
+                .store_batch(use_case::Batch {
+                    check_lists: vec![check_list],
+                    checks: vec![check],
+                    ..Default::default()
+                })
+                .await
+                .map_err(error_response)?;
+            find_check(store.as_ref(), &date, &item_id)
+                .await
+                .map_err(error_response)?
+                .and_then(|(_, check)| check)
+                .ok_or_else(|| not_found(format!("check {} {}", date, item_id)))?
+        }
+        None => {
+            let current_version = current.as_ref().map_or(0, |check| check.version);
+            store
+                .compare_and_store_check(check, expected_version(&headers, current_version))
+                .await
+                .map_err(write_error_response)?
+        }
+    };
+    Ok(written_response(current.is_none(), CheckData::from(check)))
+}
+
+/// Unchecks the item.
+async fn delete_check<T: HasStore>(
+    State(state): State<T>,
+    Path((date, item_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Response> {
+    let store = state.store();
+    let (check_list, check) = find_check(store.as_ref(), &date, &item_id)
+        .await
+        .map_err(error_response)?
+        .and_then(|(check_list, check)| check.map(|check| (check_list, check)))
+        .ok_or_else(|| not_found(format!("check {} {}", date, item_id)))?;
+    let version = check.version;
+    if !preconditions_hold(&headers, Some(&etag(&CheckData::from(check)))) {
+        return Err(precondition_failed());
+    }
+    store
+        .compare_and_delete_check(check_list.id, item_id, expected_version(&headers, version))
+        .await
+        .map_err(write_error_response)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_openapi() -> Json<serde_json::Value> {
+    Json(openapi())
+}
+
+pub fn route<T: Clone + HasStore + Send + Sync + 'static>() -> Router<T, LimitedBody> {
+    Router::new()
+        .route("/api/check-lists", routing::get(list_check_lists::<T>))
+        .route("/api/check-lists/:date", routing::get(get_check_list::<T>))
+        .route(
+            "/api/check-lists/:date/items",
+            routing::get(list_check_list_checks::<T>),
+        )
+        .route(
+            "/api/check-lists/:date/items/:item_id",
+            routing::get(get_check::<T>)
+                .put(put_check::<T>)
+                .delete(delete_check::<T>),
+        )
+        .route("/api/items", routing::get(list_items::<T>))
+        .route(
+            "/api/items/:id",
+            routing::get(get_item::<T>)
+                .put(put_item::<T>)
+                .delete(delete_item::<T>),
+        )
+        .route("/api/items/:id/checks", routing::get(list_item_checks::<T>))
+        .route("/api/openapi.json", routing::get(get_openapi))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderValue, Request};
+    use hyper::Body;
+
+    use super::*;
+    use crate::{
+        app::App,
+        test_utils::{request, send_request, ResponseExt},
+    };
+
+    fn with_header(mut request: Request<Body>, name: &'static str, value: &str) -> Request<Body> {
+        request
+            .headers_mut()
+            .insert(name, HeaderValue::from_str(value).unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_items() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+
+        let response = send_request(app.clone(), request("GET", "/api/items?limit=1", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"items":[{"id":"1","name":"item1","version":3}]}"#
+        );
+        let response = send_request(app.clone(), request("GET", "/api/items?limit=0", "")?).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = send_request(app.clone(), request("GET", "/api/items/1", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let tag = response.headers()[header::ETAG].to_str()?.to_owned();
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"id":"1","name":"item1","version":3}"#
+        );
+
+        let not_modified = with_header(request("GET", "/api/items/1", "")?, "if-none-match", &tag);
+        let response = send_request(app.clone(), not_modified).await?;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let weak_tag = format!("W/{}", tag);
+        let not_modified = with_header(
+            request("GET", "/api/items/1", "")?,
+            "if-none-match",
+            &weak_tag,
+        );
+        let response = send_request(app.clone(), not_modified).await?;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let body = r#"{"name":"milk","unit":"bottle"}"#;
+        let stale = with_header(request("PUT", "/api/items/1", body)?, "if-match", r#""0""#);
+        let response = send_request(app.clone(), stale).await?;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        // If-Match compares strongly
+        let weak = with_header(request("PUT", "/api/items/1", body)?, "if-match", &weak_tag);
+        let response = send_request(app.clone(), weak).await?;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"code":"PRECONDITION_FAILED","message":"the entity does not match If-Match or If-None-Match"}"#
+        );
+
+        let fresh = with_header(request("PUT", "/api/items/1", body)?, "if-match", &tag);
+        let response = send_request(app.clone(), fresh).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let new_tag = response.headers()[header::ETAG].to_str()?.to_owned();
+        assert_ne!(new_tag, tag);
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"id":"1","name":"milk","unit":"bottle","version":7}"#
+        );
+
+        let create_only = |uri| {
+            anyhow::Ok(with_header(
+                request("PUT", uri, r#"{"name":"eggs"}"#)?,
+                "if-none-match",
+                "*",
+            ))
+        };
+        let response = send_request(app.clone(), create_only("/api/items/3")?).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send_request(app.clone(), create_only("/api/items/3")?).await?;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let response = send_request(
+            app.clone(),
+            request("PUT", "/api/items/4", r#"{"name":" "}"#)?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let stale = with_header(request("DELETE", "/api/items/1", "")?, "if-match", &tag);
+        let response = send_request(app.clone(), stale).await?;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let fresh = with_header(request("DELETE", "/api/items/1", "")?, "if-match", &new_tag);
+        let response = send_request(app.clone(), fresh).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_request(app, request("GET", "/api/items/1", "")?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checks() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+
+        let response = send_request(app.clone(), request("GET", "/api/check-lists", "")?).await?;
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"checkLists":[{"date":"2020-01-02","id":"1","version":1},{"date":"2020-01-03","id":"2","version":2}]}"#
+        );
+        let response = send_request(
+            app.clone(),
+            request("GET", "/api/check-lists/2020-01-02", "")?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(header::ETAG));
+        let response = send_request(
+            app.clone(),
+            request("GET", "/api/check-lists/2020-1-2", "")?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let uri = "/api/check-lists/2020-01-02/items/2";
+        let body =
+            r#"{"checkedAt":"2020-01-02T03:04:05Z","price":{"amount":298,"currency":"JPY"}}"#;
+        let response = send_request(app.clone(), request("PUT", uri, body)?).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let tag = response.headers()[header::ETAG].to_str()?.to_owned();
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"checkListId":"1","checkedAt":"2020-01-02T03:04:05Z","itemId":"2","price":{"amount":298,"currency":"JPY"},"version":7}"#
+        );
+        let response = send_request(app.clone(), request("GET", uri, "")?).await?;
+        assert_eq!(response.headers()[header::ETAG], tag.as_str());
+
+        let response = send_request(
+            app.clone(),
+            request("GET", "/api/check-lists/2020-01-02/items", "")?,
+        )
+        .await?;
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"checks":[{"checkListId":"1","itemId":"1","version":5},{"checkListId":"1","checkedAt":"2020-01-02T03:04:05Z","itemId":"2","price":{"amount":298,"currency":"JPY"},"version":7}]}"#
+        );
+        let response =
+            send_request(app.clone(), request("GET", "/api/items/2/checks", "")?).await?;
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"checks":[{"checkListId":"1","checkedAt":"2020-01-02T03:04:05Z","itemId":"2","price":{"amount":298,"currency":"JPY"},"version":7},{"checkListId":"2","itemId":"2","version":6}]}"#
+        );
+
+        let new_date = "/api/check-lists/2020-02-01/items/1";
+        let response = send_request(app.clone(), request("PUT", new_date, "{}")?).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send_request(
+            app.clone(),
+            request("GET", "/api/check-lists/2020-02-01", "")?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let stale = with_header(request("DELETE", uri, "")?, "if-match", r#""0""#);
+        let response = send_request(app.clone(), stale).await?;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let fresh = with_header(request("DELETE", uri, "")?, "if-match", &tag);
+        let response = send_request(app.clone(), fresh).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_request(app, request("GET", uri, "")?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    /// Every documented operation is routed. The requests run in order, so the
+    /// deletes find nothing left to delete.
+    #[tokio::test]
+    async fn test_openapi_routes() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let response = send_request(app.clone(), request("GET", "/api/openapi.json", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let document =
+            serde_json::from_str::<serde_json::Value>(&response.into_body_as_string().await?)?;
+        assert_eq!(document, openapi());
+
+        let paths = document["paths"].as_object().expect("paths");
+        assert_eq!(paths.len(), 7);
+        for (path, operations) in paths {
+            let uri = path
+                .replace("{date}", "2020-01-02")
+                .replace("{id}", "1")
+                .replace("{itemId}", "1");
+            for method in operations.as_object().expect("operations").keys() {
+                let method = method.to_ascii_uppercase();
+                let body = if method == "PUT" {
+                    r#"{"name":"item1"}"#
+                } else {
+                    ""
+                };
+                let response = send_request(app.clone(), request(&method, &uri, body)?).await?;
+                let status = response.status();
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {}", method, uri);
+                // the fallback of the router responds with no body
+                if status == StatusCode::NOT_FOUND {
+                    assert_ne!(
+                        response.into_body_as_string().await?,
+                        "",
+                        "{} {}",
+                        method,
+                        uri
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+//! The OpenAPI 3.0 document of the REST API, built from the same names as the
+//! routes and kept in `openapi.json` by a snapshot test.
+
+use serde_json::{json, Value};
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn parameter_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/parameters/{}", name) })
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn error(description: &str) -> Value {
+    json!({ "description": description, "content": json_content(schema_ref("Error")) })
+}
+
+fn entity(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "headers": { "ETag": { "$ref": "#/components/headers/ETag" } },
+        "content": json_content(schema_ref(schema)),
+    })
+}
+
+fn list(property: &str, schema: &str) -> Value {
+    json!({
+        "type": "object",
+        "required": [property],
+        "properties": { property: { "type": "array", "items": schema_ref(schema) } },
+    })
+}
+
+fn get_entity(summary: &str, schema: &str, parameters: &[&str]) -> Value {
+    let mut parameters = parameters
+        .iter()
+        .map(|name| parameter_ref(name))
+        .collect::<Vec<_>>();
+    parameters.push(parameter_ref("IfNoneMatch"));
+    json!({
+        "summary": summary,
+        "parameters": parameters,
+        "responses": {
+            "200": entity("the entity", schema),
+            "304": { "description": "the entity matches If-None-Match" },
+            "400": error("an invalid parameter"),
+            "404": error("no such entity"),
+        },
+    })
+}
+
+fn put_entity(summary: &str, request: &str, schema: &str, parameters: &[&str]) -> Value {
+    let mut parameters = parameters
+        .iter()
+        .map(|name| parameter_ref(name))
+        .collect::<Vec<_>>();
+    parameters.extend([parameter_ref("IfMatch"), parameter_ref("IfNoneMatch")]);
+    json!({
+        "summary": summary,
+        "parameters": parameters,
+        "requestBody": { "required": true, "content": json_content(schema_ref(request)) },
+        "responses": {
+            "200": entity("replaced", schema),
+            "201": entity("created", schema),
+            "400": error("an invalid body"),
+            "412": error("If-Match or If-None-Match does not hold"),
+        },
+    })
+}
+
+fn delete_entity(summary: &str, parameters: &[&str]) -> Value {
+    let mut parameters = parameters
+        .iter()
+        .map(|name| parameter_ref(name))
+        .collect::<Vec<_>>();
+    parameters.push(parameter_ref("IfMatch"));
+    json!({
+        "summary": summary,
+        "parameters": parameters,
+        "responses": {
+            "204": { "description": "deleted" },
+            "404": error("no such entity"),
+            "412": error("If-Match does not hold"),
+        },
+    })
+}
+
+fn get_list(summary: &str, schema: &str, parameters: &[&str]) -> Value {
+    json!({
+        "summary": summary,
+        "parameters": parameters.iter().map(|name| parameter_ref(name)).collect::<Vec<_>>(),
+        "responses": {
+            "200": { "description": "the list", "content": json_content(schema_ref(schema)) },
+            "400": error("an invalid parameter"),
+        },
+    })
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+fn header_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "header",
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+pub fn openapi() -> Value {
+    let optional_string = json!({ "type": "string" });
+    let version = json!({
+        "type": "integer",
+        "format": "int64",
+        "minimum": 0,
+        "description": "changes on every write, omitted while 0",
+    });
+    let item_properties = json!({
+        "archivedAt": { "type": "string", "format": "date-time" },
+        "category": optional_string,
+        "emoji": optional_string,
+        "name": { "type": "string", "minLength": 1 },
+        "note": optional_string,
+        "sortOrder": { "type": "integer", "format": "int64" },
+        "unit": optional_string,
+    });
+    let check_properties = json!({
+        "checkedAt": { "type": "string", "format": "date-time" },
+        "note": optional_string,
+        "price": schema_ref("Price"),
+        "quantity": { "type": "number", "minimum": 0 },
+        "shop": optional_string,
+    });
+    let mut item = item_properties.clone();
+    item["id"] = json!({ "type": "string" });
+    item["version"] = version.clone();
+    let mut check = check_properties.clone();
+    check["checkListId"] = json!({ "type": "string" });
+    check["itemId"] = json!({ "type": "string" });
+    check["version"] = version.clone();
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "kireta",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "A REST/JSON API over the same store as /graphql. Writes take If-Match with the ETag of a GET.",
+        },
+        "paths": {
+            "/api/check-lists": {
+                "get": get_list("List the check lists ordered by id.", "CheckLists", &["After", "Limit"]),
+            },
+            "/api/check-lists/{date}": {
+                "get": get_entity("Get the check list of the date.", "CheckList", &["Date"]),
+            },
+            "/api/check-lists/{date}/items": {
+                "get": get_list("List the checks of the check list of the date.", "Checks", &["Date"]),
+            },
+            "/api/check-lists/{date}/items/{itemId}": {
+                "get": get_entity("Get the check of the item.", "Check", &["Date", "ItemId"]),
+                "put": put_entity(
+                    "Check the item, creating the check list of the date if there is none.",
+                    "PutCheckRequest",
+                    "Check",
+                    &["Date", "ItemId"],
+                ),
+                "delete": delete_entity("Uncheck the item.", &["Date", "ItemId"]),
+            },
+            "/api/items": {
+                "get": get_list("List the items ordered by id.", "Items", &["After", "Limit"]),
+            },
+            "/api/items/{id}": {
+                "get": get_entity("Get the item.", "Item", &["Id"]),
+                "put": put_entity("Create or replace the item.", "PutItemRequest", "Item", &["Id"]),
+                "delete": delete_entity("Delete the item and its checks permanently.", &["Id"]),
+            },
+            "/api/items/{id}/checks": {
+                "get": get_list("List the checks of the item.", "Checks", &["Id"]),
+            },
+        },
+        "components": {
+            "headers": {
+                "ETag": { "description": "the tag for If-Match and If-None-Match", "schema": { "type": "string" } },
+            },
+            "parameters": {
+                "After": {
+                    "name": "after",
+                    "in": "query",
+                    "description": "the last id of the previous page",
+                    "schema": { "type": "string" },
+                },
+                "Date": path_parameter("date", "YYYY-MM-DD"),
+                "Id": path_parameter("id", "the item id"),
+                "IfMatch": header_parameter("If-Match", "the ETag the entity must still have, or *"),
+                "IfNoneMatch": header_parameter("If-None-Match", "an ETag the client has, or * to only create"),
+                "ItemId": path_parameter("itemId", "the item id"),
+                "Limit": {
+                    "name": "limit",
+                    "in": "query",
+                    "schema": { "type": "integer", "minimum": 1, "maximum": super::MAX_LIMIT, "default": super::DEFAULT_LIMIT },
+                },
+            },
+            "schemas": {
+                "Check": { "type": "object", "required": ["checkListId", "itemId"], "properties": check },
+                "CheckList": {
+                    "type": "object",
+                    "required": ["date", "id"],
+                    "properties": {
+                        "date": { "type": "string", "format": "date" },
+                        "id": { "type": "string" },
+                        "version": version,
+                    },
+                },
+                "CheckLists": list("checkLists", "CheckList"),
+                "Checks": list("checks", "Check"),
+                "Error": {
+                    "type": "object",
+                    "required": ["code", "message"],
+                    "properties": {
+                        "code": { "type": "string", "example": "NOT_FOUND" },
+                        "message": { "type": "string" },
+                    },
+                },
+                "Item": { "type": "object", "required": ["id", "name"], "properties": item },
+                "Items": list("items", "Item"),
+                "Price": {
+                    "type": "object",
+                    "required": ["amount", "currency"],
+                    "properties": {
+                        "amount": { "type": "integer", "format": "int64", "minimum": 0, "description": "in the currency's minor unit, e.g. cents" },
+                        "currency": { "type": "string", "description": "ISO 4217, e.g. JPY" },
+                    },
+                },
+                "PutCheckRequest": { "type": "object", "additionalProperties": false, "properties": check_properties },
+                "PutItemRequest": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["name"],
+                    "properties": item_properties,
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// Run with `UPDATE_SCHEMA=1` to accept the changes.
+    #[test]
+    fn test_openapi_snapshot() -> anyhow::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("openapi.json");
+        let document = format!("{}\n", serde_json::to_string_pretty(&openapi())?);
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(&path, &document)?;
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            document,
+            "the document differs from openapi.json, run the test with UPDATE_SCHEMA=1 to accept"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! CORS for the allowed origins, by tower-http's `CorsLayer`. A preflight
+//! request is answered here, with the CORS headers only if its origin is
+//! allowed. Other requests are passed on, with the CORS headers if their origin
+//! is allowed.
+
+use std::time::Duration;
+
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::trace::REQUEST_ID;
+
+const ALLOWED_METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::OPTIONS,
+];
+const ALLOWED_HEADERS: [HeaderName; 5] = [
+    header::AUTHORIZATION,
+    header::CONTENT_TYPE,
+    header::IF_MATCH,
+    header::IF_NONE_MATCH,
+    REQUEST_ID,
+];
+const EXPOSED_HEADERS: [HeaderName; 3] = [header::ETAG, header::RETRY_AFTER, REQUEST_ID];
+/// How long a browser caches the preflight response.
+const MAX_AGE: Duration = Duration::from_secs(600);
+
+/// `*` allows any origin, and the origin is echoed so the response varies by
+/// it. An origin that is not a valid header value never matches.
+pub fn layer(allowed_origins: &[String]) -> CorsLayer {
+    let allow_origin = if allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::mirror_request()
+    } else {
+        AllowOrigin::list(
+            allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(ALLOWED_METHODS)
+        .allow_headers(ALLOWED_HEADERS)
+        .expose_headers(EXPOSED_HEADERS)
+        .max_age(MAX_AGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use crate::{
+        app::App,
+        handler::{route_with, HttpOptions},
+        test_utils::{request, send_request, StatusCode},
+    };
+
+    const DASHBOARD: &str = "https://dashboard.example.com";
+
+    fn cors_request(
+        method: &str,
+        origin: &str,
+    ) -> anyhow::Result<axum::http::Request<axum::body::Body>> {
+        let mut request = request(method, "/graphql", r#"{"query":"{ hello }"}"#)?;
+        request
+            .headers_mut()
+            .insert("origin", HeaderValue::from_str(origin)?);
+        if method == "OPTIONS" {
+            request.headers_mut().insert(
+                "access-control-request-method",
+                HeaderValue::from_static("POST"),
+            );
+        }
+        Ok(request)
+    }
+
+    #[tokio::test]
+    async fn test_cors() -> anyhow::Result<()> {
+        let app = route_with(HttpOptions {
+            allowed_origins: vec![DASHBOARD.to_owned()],
+            ..Default::default()
+        })
+        .with_state(App::example());
+
+        let response = send_request(app.clone(), cors_request("OPTIONS", DASHBOARD)?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers["access-control-allow-origin"], DASHBOARD);
+        assert_eq!(
+            headers["access-control-allow-methods"],
+            "GET,POST,PUT,DELETE,OPTIONS"
+        );
+        assert_eq!(
+            headers["access-control-allow-headers"],
+            "authorization,content-type,if-match,if-none-match,x-request-id"
+        );
+        assert_eq!(headers["access-control-max-age"], "600");
+        assert!(headers
+            .get_all("vary")
+            .iter()
+            .any(|value| value.to_str().is_ok_and(|value| value.contains("origin"))));
+
+        let response = send_request(app.clone(), cors_request("POST", DASHBOARD)?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["access-control-allow-origin"], DASHBOARD);
+        assert_eq!(
+            response.headers()["access-control-expose-headers"],
+            "etag,retry-after,x-request-id"
+        );
+
+        let other = "https://evil.example.com";
+        let response = send_request(app.clone(), cors_request("OPTIONS", other)?).await?;
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+        let response = send_request(app.clone(), cors_request("POST", other)?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+
+        let any = route_with(HttpOptions {
+            allowed_origins: vec!["*".to_owned()],
+            ..Default::default()
+        })
+        .with_state(App::example());
+        let response = send_request(any, cors_request("OPTIONS", other)?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["access-control-allow-origin"], other);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use axum::{
+    body,
+    extract::{Query, State},
+    http::header,
+    response::Response,
+    routing, Router,
+};
+
+use crate::use_case::{
+    self,
+    export::{Format, Sink},
+    HasStore,
+};
+
+use super::{error_response, LimitedBody};
+
+#[derive(serde::Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+}
+
+#[axum::async_trait]
+impl Sink for hyper::body::Sender {
+    async fn send(&mut self, chunk: String) -> Result<(), use_case::Error> {
+        self.send_data(hyper::body::Bytes::from(chunk))
+            .await
+            .map_err(|e| use_case::Error::Unavailable(e.to_string()))
+    }
+}
+
+/// Streams the export. An error after the response has started aborts the body.
+async fn get_handler<T: HasStore>(
+    State(state): State<T>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, Response> {
+    let format = query
+        .format
+        .as_deref()
+        .map(str::parse::<Format>)
+        .transpose()
+        .map_err(error_response)?
+        .unwrap_or_default();
+    let store = state.store();
+    let (mut sender, body) = hyper::Body::channel();
+    tokio::spawn(async move {
+        if use_case::export::export(store.as_ref(), format, &mut sender)
+            .await
+            .is_err()
+        {
+            sender.abort();
+        }
+    });
+    Response::builder()
+        .header(header::CONTENT_TYPE, format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(r#"attachment; filename="kireta.{}""#, format.extension()),
+        )
+        .body(body::boxed(body))
+        .map_err(|e| error_response(use_case::Error::Unknown(e.to_string())))
+}
+
+pub fn route<T: Clone + HasStore + Send + Sync + 'static>() -> Router<T, LimitedBody> {
+    Router::new().route("/export", routing::get(get_handler::<T>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::App,
+        test_utils::{request, send_request, ResponseExt, StatusCode},
+        use_case::export::ExportData,
+    };
+
+    #[tokio::test]
+    async fn test_get() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let response = send_request(app.clone(), request("GET", "/export", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            r#"attachment; filename="kireta.json""#
+        );
+        let data = serde_json::from_str::<ExportData>(&response.into_body_as_string().await?)?;
+        assert_eq!(
+            (data.items.len(), data.check_lists.len(), data.checks.len()),
+            (2, 2, 2)
+        );
+
+        let response = send_request(app.clone(), request("GET", "/export?format=csv", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(response.into_body_as_string().await?.lines().count(), 3);
+
+        let response = send_request(app, request("GET", "/export?format=xml", "")?).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+}
@@ -1,25 +1,52 @@
 mod graphql_data;
+mod graphql_error;
+mod graphql_limits;
 mod graphql_schema;
+mod idempotency_keys;
 mod mutation;
+mod persisted_queries;
 mod query;
+mod rate_limiter;
+mod schema_diff;
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
 
 use async_graphql::http::GraphiQLSource;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     headers::{
         authorization::{Bearer, Credentials},
         HeaderMap,
     },
-    response::{Html, IntoResponse},
+    http::{
+        header::{CONTENT_TYPE, RETRY_AFTER},
+        HeaderName, HeaderValue,
+    },
+    response::{Html, IntoResponse, Response},
     routing, Router,
 };
 use hyper::StatusCode;
+use tracing::Instrument as _;
 
-use crate::use_case::HasStore;
+use crate::{infra::metrics::Metrics, use_case::HasStore};
 
-use self::graphql_data::GraphQLData;
-pub use self::graphql_schema::GraphQLSchema;
+use super::LimitedBody;
+
+pub use self::schema_diff::{diff_schemas, SchemaChange, Severity};
+use self::{
+    graphql_data::GraphQLData,
+    graphql_error::coded_error,
+    idempotency_keys::{Claim, Executed},
+    persisted_queries::sha256,
+};
+pub use self::{
+    graphql_limits::GraphQLLimits, graphql_schema::GraphQLSchema,
+    idempotency_keys::IdempotencyKeys, persisted_queries::PersistedQueries,
+};
 
 pub trait HasGraphQLSchema {
     fn graphql_schema(&self) -> &GraphQLSchema;
@@ -29,26 +56,170 @@ async fn get_handler() -> impl IntoResponse {
     Html(GraphiQLSource::build().endpoint("/graphql").finish())
 }
 
+/// The principal is the bearer token if any, otherwise the client address, see
+/// [`client_address`]. Requests are rate limited and idempotency keys are
+/// scoped by the principal. A request with an idempotency key runs once, see
+/// [`IdempotencyKeys`].
 async fn post_handler<T: HasGraphQLSchema + HasStore>(
     State(state): State<T>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     header_map: HeaderMap,
     request: GraphQLRequest,
-) -> Result<GraphQLResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
     let schema = state.graphql_schema();
     let store = state.store();
-    let request = request.into_inner().data(GraphQLData {
-        bearer: match header_map.get(axum::http::header::AUTHORIZATION) {
-            Some(header_value) => {
-                Some(Bearer::decode(header_value).ok_or(StatusCode::UNAUTHORIZED)?)
+    let bearer = match header_map.get(axum::http::header::AUTHORIZATION) {
+        Some(header_value) => Some(Bearer::decode(header_value).ok_or(StatusCode::UNAUTHORIZED)?),
+        None => None,
+    };
+    let principal = match &bearer {
+        // the rate limiter keeps the principals in memory
+        Some(bearer) => format!("bearer {}", sha256(bearer.token())),
+        None => match client_address(
+            &header_map,
+            connect_info.map(|ConnectInfo(address)| address.ip()),
+            schema.limits().trusted_proxies,
+        ) {
+            Some(address) => format!("ip {}", address),
+            None => "anonymous".to_owned(),
+        },
+    };
+    if let Err(retry_after) = schema.check_rate_limit(&principal) {
+        return Ok(rate_limited(retry_after));
+    }
+    let request = request.into_inner();
+    let claimed_key = match IdempotencyKeys::key(&header_map, &request) {
+        Ok(None) => None,
+        Ok(Some(key)) => match schema
+            .idempotency_keys()
+            .claim(&principal, &key, &request)
+            .await
+        {
+            Ok(Claim::Run(claimed_key)) => Some(claimed_key),
+            Ok(Claim::Replay(body)) => return Ok(replayed(body)),
+            Ok(Claim::InUse) => {
+                return Ok(error_response(
+                    StatusCode::CONFLICT,
+                    coded_error(
+                        "IDEMPOTENCY_KEY_IN_USE",
+                        "a request with the idempotency key is running".to_owned(),
+                    ),
+                ))
             }
-            None => None,
+            Ok(Claim::Reused) => {
+                return Ok(error_response(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    coded_error(
+                        "IDEMPOTENCY_KEY_REUSED",
+                        "the idempotency key was used for another request".to_owned(),
+                    ),
+                ))
+            }
+            Err(error) => return Ok(error_response(StatusCode::OK, error)),
         },
-        store,
-    });
-    Ok(GraphQLResponse::from(schema.execute(request).await))
+        Err(error) => return Ok(error_response(StatusCode::OK, error)),
+    };
+    let executed = Executed::default();
+    let request = request
+        .data(GraphQLData { bearer, store })
+        .data(executed.clone());
+    let operation_name = request.operation_name.clone().unwrap_or_default();
+    let span = tracing::info_span!("graphql", operation_name = %operation_name);
+    let started_at = Instant::now();
+    let response = schema.execute(request).instrument(span.clone()).await;
+    Metrics::global().observe_graphql_request(
+        &operation_name,
+        started_at.elapsed(),
+        response.errors.is_empty(),
+    );
+    for error in response.errors.iter() {
+        let code = match error
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("code"))
+        {
+            Some(async_graphql::Value::String(code)) => code.as_str(),
+            _ => "",
+        };
+        tracing::warn!(parent: &span, code, "{}", error.message);
+    }
+    if let Some(claimed_key) = claimed_key {
+        if let Err(e) = schema
+            .idempotency_keys()
+            .complete(claimed_key, &executed, &response)
+            .await
+        {
+            tracing::warn!(parent: &span, error = %e, "cannot store the idempotent response");
+        }
+    }
+    Ok(GraphQLResponse::from(response).into_response())
+}
+
+/// The peer address, or behind `trusted_proxies` proxies, the address that the
+/// outermost one appended to `X-Forwarded-For`. The entries before it are set
+/// by the client and can be forged.
+fn client_address(
+    header_map: &HeaderMap,
+    peer: Option<IpAddr>,
+    trusted_proxies: usize,
+) -> Option<IpAddr> {
+    if trusted_proxies == 0 {
+        return peer;
+    }
+    let forwarded_for = header_map
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|header_value| header_value.to_str().ok())
+        .flat_map(|header_value| header_value.split(','))
+        .map(str::trim)
+        .collect::<Vec<&str>>();
+    forwarded_for
+        .iter()
+        .rev()
+        .nth(trusted_proxies - 1)
+        .and_then(|address| address.parse().ok())
+        .or(peer)
+}
+
+fn error_response(status: StatusCode, error: async_graphql::ServerError) -> Response {
+    let mut response =
+        GraphQLResponse::from(async_graphql::Response::from_errors(vec![error])).into_response();
+    *response.status_mut() = status;
+    response
+}
+
+/// The stored response of the first request with the idempotency key.
+fn replayed(body: String) -> Response {
+    (
+        [
+            (CONTENT_TYPE, HeaderValue::from_static("application/json")),
+            (
+                HeaderName::from_static("idempotent-replayed"),
+                HeaderValue::from_static("true"),
+            ),
+        ],
+        body,
+    )
+        .into_response()
 }
 
-pub fn route<T: Clone + HasGraphQLSchema + HasStore + Send + Sync + 'static>() -> Router<T> {
+fn rate_limited(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs_f64().ceil() as u64;
+    let mut response = error_response(
+        StatusCode::TOO_MANY_REQUESTS,
+        coded_error(
+            "RATE_LIMITED",
+            format!("rate limit exceeded, retry after {} seconds", seconds),
+        ),
+    );
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
+
+pub fn route<T: Clone + HasGraphQLSchema + HasStore + Send + Sync + 'static>(
+) -> Router<T, LimitedBody> {
     Router::new().route(
         "/graphql",
         routing::get(get_handler).post(post_handler::<T>),
@@ -120,4 +291,226 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_post_limits() -> anyhow::Result<()> {
+        let app = route().with_state(App::example().with_graphql_limits(GraphQLLimits {
+            max_depth: 4,
+            max_complexity: 50,
+            ..Default::default()
+        }));
+        for (query, expected) in [
+            (
+                "{ items { checkedCheckLists { checkedItems { checkedCheckLists { id } } } } }",
+                r#"{"data":null,"errors":[{"message":"query depth 5 exceeds the limit 4","extensions":{"code":"QUERY_TOO_DEEP"}}]}"#,
+            ),
+            (
+                "{ items { checkedCheckLists { id } } }",
+                r#"{"data":null,"errors":[{"message":"query complexity 100 exceeds the limit 50","extensions":{"code":"QUERY_TOO_COMPLEX"}}]}"#,
+            ),
+            (
+                "{ items { id name } }",
+                r#"{"data":{"items":[{"id":"1","name":"item1"},{"id":"2","name":"item2"}]}}"#,
+            ),
+        ] {
+            let request = axum::http::Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header(axum::http::header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(
+                    serde_json::json!({ "query": query }).to_string(),
+                ))?;
+            let response = send_request(app.clone(), request).await?;
+
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            assert_eq!(response.into_body_as_string().await?, expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_rate_limit() -> anyhow::Result<()> {
+        let app = route().with_state(App::example().with_graphql_limits(GraphQLLimits {
+            rate_limit: Some(1),
+            trusted_proxies: 1,
+            ..Default::default()
+        }));
+        let request = |bearer: Option<&str>, forwarded_for: &str| {
+            let mut builder = axum::http::Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header(axum::http::header::CONTENT_TYPE, "application/json")
+                .header("x-forwarded-for", forwarded_for);
+            if let Some(bearer) = bearer {
+                builder = builder.header(
+                    axum::http::header::AUTHORIZATION,
+                    format!("Bearer {}", bearer),
+                );
+            }
+            let mut request = builder.body(axum::body::Body::from(r#"{"query":"{ hello }"}"#))?;
+            // the proxy
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([169, 254, 1, 1], 1234))));
+            anyhow::Ok(request)
+        };
+        let response = send_request(app.clone(), request(Some("a"), "192.0.2.1")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        // the same bearer token from another address
+        let response = send_request(app.clone(), request(Some("a"), "192.0.2.2")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "60");
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"data":null,"errors":[{"message":"rate limit exceeded, retry after 60 seconds","extensions":{"code":"RATE_LIMITED"}}]}"#
+        );
+
+        // another bearer token from the same address
+        let response = send_request(app.clone(), request(Some("b"), "192.0.2.1")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        // without a bearer token, by the address appended by the proxy
+        let response = send_request(app.clone(), request(None, "192.0.2.1")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let response = send_request(app.clone(), request(None, "192.0.2.2")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let response = send_request(app, request(None, "198.51.100.1, 192.0.2.1")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_address() -> anyhow::Result<()> {
+        let peer = Some(IpAddr::from([169, 254, 1, 1]));
+        let mut header_map = HeaderMap::new();
+        header_map.append("x-forwarded-for", "198.51.100.1, 192.0.2.1".parse()?);
+        header_map.append("x-forwarded-for", "192.0.2.2".parse()?);
+        assert_eq!(client_address(&header_map, peer, 0), peer);
+        assert_eq!(
+            client_address(&header_map, peer, 1),
+            Some(IpAddr::from([192, 0, 2, 2]))
+        );
+        assert_eq!(
+            client_address(&header_map, peer, 2),
+            Some(IpAddr::from([192, 0, 2, 1]))
+        );
+        // fewer entries than proxies
+        assert_eq!(client_address(&header_map, peer, 4), peer);
+        assert_eq!(client_address(&HeaderMap::new(), peer, 1), peer);
+        assert_eq!(client_address(&HeaderMap::new(), None, 1), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_persisted_query() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let hash = "001c3174e099bd72b729d0c0a529ba9f5a740c446e2a6e1d71b283cb84ec3065";
+        let extensions = serde_json::json!({
+            "persistedQuery": { "version": 1, "sha256Hash": hash }
+        });
+        for (body, expected) in [
+            (
+                serde_json::json!({ "extensions": extensions }),
+                r#"{"data":null,"errors":[{"message":"PersistedQueryNotFound"}]}"#,
+            ),
+            (
+                serde_json::json!({ "query": "{ hello }", "extensions": extensions }),
+                r#"{"data":{"hello":"Hello, World!"}}"#,
+            ),
+            (
+                serde_json::json!({ "extensions": extensions }),
+                r#"{"data":{"hello":"Hello, World!"}}"#,
+            ),
+        ] {
+            let request = axum::http::Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header(axum::http::header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(body.to_string()))?;
+            let response = send_request(app.clone(), request).await?;
+
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            assert_eq!(response.into_body_as_string().await?, expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_idempotency_key() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let request = |key: &str, name: &str| {
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header(axum::http::header::CONTENT_TYPE, "application/json")
+                .header("idempotency-key", key)
+                .body(axum::body::Body::from(
+                    serde_json::json!({
+                        "query": format!(
+                            r#"mutation {{ updateItem(input: {{ id: "1", name: "{}" }}) {{ version }} }}"#,
+                            name
+                        )
+                    })
+                    .to_string(),
+                ))
+        };
+        let response = send_request(app.clone(), request("key1", "name1")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert!(!response.headers().contains_key("idempotent-replayed"));
+        let body = response.into_body_as_string().await?;
+
+        // the retry does not update the item again
+        let response = send_request(app.clone(), request("key1", "name1")?).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()["idempotent-replayed"], "true");
+        assert_eq!(response.into_body_as_string().await?, body);
+
+        let response = send_request(app.clone(), request("key1", "name2")?).await?;
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"data":null,"errors":[{"message":"the idempotency key was used for another request","extensions":{"code":"IDEMPOTENCY_KEY_REUSED"}}]}"#
+        );
+
+        let response = send_request(app, request("key2", "name1")?).await?;
+        assert_ne!(response.into_body_as_string().await?, body);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_idempotency_key_errors() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let request = |query: &str| {
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/graphql")
+                .header(axum::http::header::CONTENT_TYPE, "application/json")
+                .header("idempotency-key", "key1")
+                .body(axum::body::Body::from(
+                    serde_json::json!({ "query": query }).to_string(),
+                ))
+        };
+
+        // an invalid request runs nothing, so its key is released
+        let invalid = r#"mutation { restoreItem(id: "1", unknown: 1) }"#;
+        for _ in 0..2 {
+            let response = send_request(app.clone(), request(invalid)?).await?;
+            assert!(!response.headers().contains_key("idempotent-replayed"));
+        }
+
+        // an executed request is replayed even if it failed
+        let failed = r#"mutation { restoreItem(id: "not-found") }"#;
+        let response = send_request(app.clone(), request(failed)?).await?;
+        assert!(!response.headers().contains_key("idempotent-replayed"));
+        let body = response.into_body_as_string().await?;
+        assert!(body.starts_with(r#"{"data":null,"errors":"#));
+        let response = send_request(app, request(failed)?).await?;
+        assert_eq!(response.headers()["idempotent-replayed"], "true");
+        assert_eq!(response.into_body_as_string().await?, body);
+        Ok(())
+    }
 }
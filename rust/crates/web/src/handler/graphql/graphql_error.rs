@@ -0,0 +1,97 @@
+use async_graphql::{ErrorExtensions, ServerError};
+
+use crate::use_case::{self, data::EntityData};
+
+impl ErrorExtensions for use_case::Error {
+    /// Sets `code`. A version conflict also sets `current`, the entity as
+    /// stored in the shape of a change's entity (null if it does not exist),
+    /// and `expectedVersion`.
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", self.code());
+            if let use_case::Error::VersionConflict { current, expected } = self {
+                let current = current
+                    .as_deref()
+                    .cloned()
+                    .map(EntityData::from)
+                    .map(async_graphql::to_value)
+                    .transpose()
+                    .expect("entity data is serializable")
+                    .unwrap_or_default();
+                extensions.set("current", current);
+                extensions.set("expectedVersion", *expected);
+            }
+        })
+    }
+}
+
+/// An error of the request as a whole, with `code` in the extensions.
+pub(super) fn coded_error(code: &str, message: String) -> ServerError {
+    let mut error = ServerError::new(message, None);
+    error
+        .extensions
+        .get_or_insert_with(Default::default)
+        .set("code", code);
+    error
+}
+
+impl From<use_case::Error> for ServerError {
+    fn from(e: use_case::Error) -> Self {
+        coded_error(e.code(), e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model;
+
+    #[test]
+    fn test_extend() {
+        let error = use_case::Error::NotFound("item 1".to_owned()).extend();
+        assert_eq!(error.message, "not found item 1");
+        assert_eq!(
+            error
+                .extensions
+                .as_ref()
+                .and_then(|extensions| extensions.get("code"))
+                .cloned(),
+            Some(async_graphql::Value::from("NOT_FOUND"))
+        );
+    }
+
+    #[test]
+    fn test_extend_version_conflict() -> anyhow::Result<()> {
+        let error = use_case::Error::VersionConflict {
+            current: Some(Box::new(model::Entity::Item(model::Item {
+                id: "1".to_owned(),
+                name: "item1".to_owned(),
+                version: 3,
+                ..Default::default()
+            }))),
+            expected: 2,
+        }
+        .extend();
+        let extensions = error.extensions.expect("extensions");
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from("CONFLICT"))
+        );
+        assert_eq!(
+            extensions
+                .get("current")
+                .cloned()
+                .map(|value| value.into_json())
+                .transpose()?,
+            Some(serde_json::json!({
+                "type": "item",
+                "payload": { "id": "1", "name": "item1", "version": 3 }
+            }))
+        );
+        assert_eq!(
+            extensions.get("expectedVersion"),
+            Some(&async_graphql::Value::from(2))
+        );
+        Ok(())
+    }
+}
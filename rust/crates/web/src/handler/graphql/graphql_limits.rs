@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextValidation},
+    ServerError, ValidationResult,
+};
+
+use super::graphql_error::coded_error;
+
+/// The limits of a GraphQL request. List fields cost more than other fields,
+/// see `query::LIST_COST`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GraphQLLimits {
+    /// The default allows the introspection query of GraphiQL.
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    /// Requests per minute per principal (bearer token, otherwise client
+    /// address). `None`, the default, disables rate limiting.
+    pub rate_limit: Option<u32>,
+    /// The number of proxies in front of the server whose `X-Forwarded-For`
+    /// entries are trusted, e.g. 1 on Cloud Run. With 0, the default, the
+    /// client address is the peer address.
+    pub trusted_proxies: usize,
+}
+
+impl Default for GraphQLLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 15,
+            max_complexity: 1_000,
+            rate_limit: None,
+            trusted_proxies: 0,
+        }
+    }
+}
+
+impl ExtensionFactory for GraphQLLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(LimitsExtension(*self))
+    }
+}
+
+/// Rejects the query after validation, with an error code in the extensions,
+/// unlike `SchemaBuilder::limit_depth` and `SchemaBuilder::limit_complexity`.
+struct LimitsExtension(GraphQLLimits);
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for LimitsExtension {
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+        if result.depth > self.0.max_depth {
+            return Err(vec![coded_error(
+                "QUERY_TOO_DEEP",
+                format!(
+                    "query depth {} exceeds the limit {}",
+                    result.depth, self.0.max_depth
+                ),
+            )]);
+        }
+        if result.complexity > self.0.max_complexity {
+            return Err(vec![coded_error(
+                "QUERY_TOO_COMPLEX",
+                format!(
+                    "query complexity {} exceeds the limit {}",
+                    result.complexity, self.0.max_complexity
+                ),
+            )]);
+        }
+        Ok(result)
+    }
+}
@@ -1,18 +1,149 @@
-use super::{mutation::MutationRoot, query::QueryRoot};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{
+    graphql_limits::GraphQLLimits,
+    idempotency_keys::{IdempotencyKeys, MarkExecuted},
+    mutation::MutationRoot,
+    persisted_queries::{sha256, PersistedQueries},
+    query::QueryRoot,
+    rate_limiter::RateLimiter,
+};
 use async_graphql::{EmptySubscription, Request, Response, Schema};
 
 #[derive(Clone)]
-pub struct GraphQLSchema(Schema<QueryRoot, MutationRoot, EmptySubscription>);
+pub struct GraphQLSchema {
+    schema: Schema<QueryRoot, MutationRoot, EmptySubscription>,
+    limits: GraphQLLimits,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    persisted_queries: PersistedQueries,
+    idempotency_keys: IdempotencyKeys,
+}
+
+impl Default for GraphQLSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl GraphQLSchema {
     pub fn new() -> Self {
-        Self(Schema::new(QueryRoot, MutationRoot, EmptySubscription))
+        let limits = GraphQLLimits::default();
+        let persisted_queries = PersistedQueries::default();
+        Self {
+            schema: build_schema(limits, &persisted_queries),
+            limits,
+            rate_limiter: limits.rate_limit.map(RateLimiter::new).map(Arc::new),
+            persisted_queries,
+            idempotency_keys: IdempotencyKeys::default(),
+        }
+    }
+
+    pub fn with_limits(self, limits: GraphQLLimits) -> Self {
+        Self {
+            schema: build_schema(limits, &self.persisted_queries),
+            limits,
+            rate_limiter: limits.rate_limit.map(RateLimiter::new).map(Arc::new),
+            ..self
+        }
+    }
+
+    pub fn with_persisted_queries(self, persisted_queries: PersistedQueries) -> Self {
+        Self {
+            schema: build_schema(self.limits, &persisted_queries),
+            persisted_queries,
+            ..self
+        }
+    }
+
+    pub fn with_idempotency_keys(self, idempotency_keys: IdempotencyKeys) -> Self {
+        Self {
+            idempotency_keys,
+            ..self
+        }
+    }
+
+    pub fn idempotency_keys(&self) -> &IdempotencyKeys {
+        &self.idempotency_keys
+    }
+
+    pub fn limits(&self) -> GraphQLLimits {
+        self.limits
+    }
+
+    /// Counts a request of `client`, or returns how long it has to wait.
+    pub fn check_rate_limit(&self, client: &str) -> Result<(), Duration> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => rate_limiter.check(client, Instant::now()),
+            None => Ok(()),
+        }
     }
 
     pub async fn execute<R>(&self, request: R) -> Response
     where
         R: Into<Request>,
     {
-        self.0.execute(request).await
+        let mut request = request.into();
+        if let Err(error) = self.persisted_queries.resolve(&mut request) {
+            return Response::from_errors(vec![error]);
+        }
+        self.schema.execute(request).await
+    }
+
+    /// Returns the schema in the GraphQL schema definition language.
+    pub fn sdl(&self) -> String {
+        self.schema.sdl()
+    }
+
+    /// Returns the sha256 of `sdl`, which changes when the schema changes.
+    pub fn sdl_hash(&self) -> String {
+        sha256(&self.sdl())
+    }
+}
+
+fn build_schema(
+    limits: GraphQLLimits,
+    persisted_queries: &PersistedQueries,
+) -> Schema<QueryRoot, MutationRoot, EmptySubscription> {
+    let builder = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .extension(limits)
+        .extension(MarkExecuted);
+    match persisted_queries.extension() {
+        Some(extension) => builder.extension(extension),
+        None => builder,
+    }
+    .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::super::diff_schemas;
+    use super::*;
+
+    /// Run with `UPDATE_SCHEMA=1` to accept the changes.
+    #[test]
+    fn test_schema_snapshot() -> anyhow::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema.graphql");
+        let sdl = GraphQLSchema::new().sdl();
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(&path, &sdl)?;
+        }
+        let snapshot = std::fs::read_to_string(&path)?;
+        if snapshot != sdl {
+            let changes = diff_schemas(&snapshot, &sdl)?;
+            panic!(
+                "the schema differs from schema.graphql ({} breaking), run the test with UPDATE_SCHEMA=1 to accept:\n{}",
+                changes.iter().filter(|change| change.is_breaking()).count(),
+                changes
+                    .iter()
+                    .map(|change| format!("  {}\n", change))
+                    .collect::<String>()
+            );
+        }
+        Ok(())
     }
 }
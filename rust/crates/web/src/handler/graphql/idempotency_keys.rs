@@ -0,0 +1,415 @@
+//! Idempotency keys, so that a retried mutation does not run twice.
+//!
+//! A mutation with an `Idempotency-Key` header, `extensions.idempotencyKey` or
+//! an `idempotencyKey` argument runs once per key and principal. The key of a
+//! query is ignored. A retry within the TTL is answered with the original
+//! response. A retry while the request is running fails with
+//! `IDEMPOTENCY_KEY_IN_USE`, and reusing a key for another request fails with
+//! `IDEMPOTENCY_KEY_REUSED`. A running request holds its key for a lease, so
+//! that a request that crashes or times out does not hold it for the TTL.
+//!
+//! A request that fails before it is executed (it does not parse, is invalid,
+//! exceeds the limits or is an unknown persisted query) ran nothing, so its key
+//! is released for the retry. Every response of an executed request is
+//! replayed, errors included: a mutation that fails in a later field has
+//! already run its earlier fields.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute},
+    parser::{
+        parse_query,
+        types::{DocumentOperations, OperationDefinition, OperationType, Selection},
+    },
+    Request, ServerError, Value,
+};
+use axum::http::HeaderMap;
+
+use crate::{
+    infra::idempotency_key_store::InMemoryIdempotencyKeyStore,
+    use_case::{self, idempotency::IdempotencyKeyStore},
+};
+
+use super::persisted_queries::sha256;
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// The argument of a mutation field that holds the key.
+pub const IDEMPOTENCY_KEY_ARGUMENT: &str = "idempotencyKey";
+const MAX_KEY_LEN: usize = 255;
+/// Longer than `HttpOptions::request_timeout` by default, so that the lease of
+/// a running request does not expire.
+const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+
+/// What to do with a request that has a key.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Claim {
+    /// Run the request, then `complete` or `release` the key.
+    Run(ClaimedKey),
+    /// Answer with the response of the first request.
+    Replay(String),
+    InUse,
+    Reused,
+}
+
+/// Whether the request reached execution, set by [`MarkExecuted`] when it is
+/// in the request data.
+#[derive(Clone, Debug, Default)]
+pub struct Executed(Arc<AtomicBool>);
+
+impl Executed {
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Sets the [`Executed`] of the request once it is parsed, validated and
+/// within the limits.
+pub struct MarkExecuted;
+
+impl ExtensionFactory for MarkExecuted {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(MarkExecuted)
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for MarkExecuted {
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> async_graphql::Response {
+        if let Some(executed) = ctx.data_opt::<Executed>() {
+            executed.0.store(true, Ordering::Release);
+        }
+        next.run(ctx, operation_name).await
+    }
+}
+
+/// The key as stored, scoped to the principal.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ClaimedKey(String);
+
+#[derive(Clone)]
+pub struct IdempotencyKeys {
+    store: Arc<dyn IdempotencyKeyStore + Send + Sync>,
+    ttl: Duration,
+    lease: Duration,
+}
+
+impl Default for IdempotencyKeys {
+    fn default() -> Self {
+        Self::new(
+            Arc::new(InMemoryIdempotencyKeyStore::new()),
+            Duration::from_secs(24 * 60 * 60),
+        )
+    }
+}
+
+impl IdempotencyKeys {
+    /// Keeps the responses in `store` for `ttl`.
+    pub fn new(store: Arc<dyn IdempotencyKeyStore + Send + Sync>, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            lease: DEFAULT_LEASE,
+        }
+    }
+
+    /// Holds the key of a running request for `lease`, until it completes.
+    pub fn with_lease(self, lease: Duration) -> Self {
+        Self { lease, ..self }
+    }
+
+    /// Returns the key of the request if it may run a mutation, from the
+    /// header, the extension or the arguments, which must agree.
+    pub fn key(headers: &HeaderMap, request: &Request) -> Result<Option<String>, ServerError> {
+        let invalid = |message: &str| {
+            ServerError::from(use_case::Error::InvalidInput(format!(
+                "idempotency key {}",
+                message
+            )))
+        };
+        let header = match headers.get(IDEMPOTENCY_KEY) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .map_err(|_| invalid("is not visible ASCII"))?
+                    .to_owned(),
+            ),
+            None => None,
+        };
+        let extension = match request.extensions.get("idempotencyKey") {
+            Some(async_graphql::Value::String(key)) => Some(key.clone()),
+            Some(_) => return Err(invalid("is not a string")),
+            None => None,
+        };
+        let arguments = match operation(request) {
+            Operation::Query => return Ok(None),
+            Operation::Persisted => vec![],
+            Operation::Mutation(operation) => argument_keys(&operation, request)
+                .ok_or_else(|| invalid("argument is not a string"))?,
+        };
+        let mut keys = header.into_iter().chain(extension).chain(arguments);
+        let key = keys.next();
+        if keys.any(|other| Some(&other) != key.as_ref()) {
+            return Err(invalid(
+                "differs between the header, the extension and the arguments",
+            ));
+        }
+        match key {
+            Some(key) if key.is_empty() || key.len() > MAX_KEY_LEN => {
+                Err(invalid(&format!("must be 1 to {} characters", MAX_KEY_LEN)))
+            }
+            key => Ok(key),
+        }
+    }
+
+    pub async fn claim(
+        &self,
+        principal: &str,
+        key: &str,
+        request: &Request,
+    ) -> Result<Claim, ServerError> {
+        // the principal may be a bearer token, which is not stored as is
+        let scoped_key = sha256(&format!("{}\n{}", principal, key));
+        let fingerprint = fingerprint(request);
+        Ok(
+            match self
+                .store
+                .claim(scoped_key.clone(), fingerprint.clone(), self.lease)
+                .await?
+            {
+                None => Claim::Run(ClaimedKey(scoped_key)),
+                Some(record) if record.fingerprint != fingerprint => Claim::Reused,
+                Some(record) => match record.response {
+                    Some(response) => Claim::Replay(response),
+                    None => Claim::InUse,
+                },
+            },
+        )
+    }
+
+    /// Stores the response to replay, or releases the key if the request was
+    /// not `executed`.
+    pub async fn complete(
+        &self,
+        ClaimedKey(key): ClaimedKey,
+        executed: &Executed,
+        response: &async_graphql::Response,
+    ) -> Result<(), use_case::Error> {
+        if !executed.get() {
+            return self.store.release(key).await;
+        }
+        let body =
+            serde_json::to_string(response).map_err(|e| use_case::Error::Unknown(e.to_string()))?;
+        self.store.complete(key, body, self.ttl).await
+    }
+}
+
+/// The operation of a request, as far as it is known before it runs.
+enum Operation {
+    /// A query, or a request that does not parse and runs nothing.
+    Query,
+    /// A persisted query sent by its hash alone, which may be a mutation.
+    Persisted,
+    Mutation(OperationDefinition),
+}
+
+fn operation(request: &Request) -> Operation {
+    if request.query.is_empty() {
+        return Operation::Persisted;
+    }
+    let Ok(document) = parse_query(&request.query) else {
+        return Operation::Query;
+    };
+    let operation = match (document.operations, request.operation_name.as_deref()) {
+        (DocumentOperations::Single(operation), _) => Some(operation.node),
+        (DocumentOperations::Multiple(mut operations), Some(name)) => {
+            operations.remove(name).map(|operation| operation.node)
+        }
+        // fails to run unless there is one mutation
+        (DocumentOperations::Multiple(operations), None) => operations
+            .into_values()
+            .map(|operation| operation.node)
+            .find(|operation| operation.ty == OperationType::Mutation),
+    };
+    match operation {
+        Some(operation) if operation.ty == OperationType::Mutation => {
+            Operation::Mutation(operation)
+        }
+        _ => Operation::Query,
+    }
+}
+
+/// Returns the `idempotencyKey` arguments of the fields of the mutation, with
+/// its variables substituted, or `None` if one is not a string.
+fn argument_keys(operation: &OperationDefinition, request: &Request) -> Option<Vec<String>> {
+    let variable = |name: async_graphql::Name| {
+        let default = || {
+            operation
+                .variable_definitions
+                .iter()
+                .find(|definition| definition.node.name.node == name)
+                .and_then(|definition| definition.node.default_value.clone())
+                .map(|default_value| default_value.node)
+        };
+        Ok::<_, ()>(
+            request
+                .variables
+                .get(&name)
+                .cloned()
+                .or_else(default)
+                .unwrap_or(Value::Null),
+        )
+    };
+    let mut keys = vec![];
+    for selection in &operation.selection_set.node.items {
+        let Selection::Field(field) = &selection.node else {
+            continue;
+        };
+        let Some(argument) = field.node.get_argument(IDEMPOTENCY_KEY_ARGUMENT) else {
+            continue;
+        };
+        match argument.node.clone().into_const_with(variable).ok()? {
+            Value::Null => {}
+            Value::String(key) => keys.push(key),
+            _ => return None,
+        }
+    }
+    Some(keys)
+}
+
+/// Identifies the request by what it runs. Other extensions are left out, as
+/// their order is not kept.
+fn fingerprint(request: &Request) -> String {
+    let json = serde_json::json!([
+        request.query,
+        request.operation_name,
+        request.variables,
+        request.extensions.get("persistedQuery"),
+    ]);
+    sha256(&json.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_key() -> anyhow::Result<()> {
+        let mut headers = HeaderMap::new();
+        let request = Request::new("mutation { restoreItem(id: \"1\") }");
+        assert_eq!(IdempotencyKeys::key(&headers, &request).unwrap(), None);
+
+        let mut with_extension = Request::new("mutation { restoreItem(id: \"1\") }");
+        with_extension.extensions.insert(
+            "idempotencyKey".to_owned(),
+            async_graphql::Value::from("key1"),
+        );
+        assert_eq!(
+            IdempotencyKeys::key(&headers, &with_extension).unwrap(),
+            Some("key1".to_owned())
+        );
+
+        headers.insert(IDEMPOTENCY_KEY, HeaderValue::from_static("key1"));
+        assert_eq!(
+            IdempotencyKeys::key(&headers, &request).unwrap(),
+            Some("key1".to_owned())
+        );
+        assert_eq!(
+            IdempotencyKeys::key(&headers, &with_extension).unwrap(),
+            Some("key1".to_owned())
+        );
+
+        // the key of a query is ignored
+        let query = Request::new("{ items { id } }");
+        assert_eq!(IdempotencyKeys::key(&headers, &query).unwrap(), None);
+        let named = |name: &str| {
+            Request::new(r#"query A { items { id } } mutation B { restoreItem(id: "1") }"#)
+                .operation_name(name)
+        };
+        assert_eq!(IdempotencyKeys::key(&headers, &named("A")).unwrap(), None);
+        assert_eq!(
+            IdempotencyKeys::key(&headers, &named("B")).unwrap(),
+            Some("key1".to_owned())
+        );
+        let argument = Request::new(r#"mutation { restoreItem(id: "1", idempotencyKey: "key1") }"#);
+        assert_eq!(
+            IdempotencyKeys::key(&headers, &argument).unwrap(),
+            Some("key1".to_owned())
+        );
+        let variable = Request::new(
+            r#"mutation ($key: String) { restoreItem(id: "1", idempotencyKey: $key) }"#,
+        )
+        .variables(async_graphql::Variables::from_json(
+            serde_json::json!({ "key": "key2" }),
+        ));
+        assert!(IdempotencyKeys::key(&headers, &variable).is_err());
+        assert_eq!(
+            IdempotencyKeys::key(&HeaderMap::new(), &variable).unwrap(),
+            Some("key2".to_owned())
+        );
+        let persisted = Request::new("");
+        assert_eq!(
+            IdempotencyKeys::key(&headers, &persisted).unwrap(),
+            Some("key1".to_owned())
+        );
+
+        headers.insert(IDEMPOTENCY_KEY, HeaderValue::from_static("key2"));
+        assert!(IdempotencyKeys::key(&headers, &with_extension).is_err());
+        headers.insert(IDEMPOTENCY_KEY, HeaderValue::from_static(""));
+        assert!(IdempotencyKeys::key(&headers, &request).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_claim() -> anyhow::Result<()> {
+        let keys = IdempotencyKeys::default();
+        let request = Request::new("mutation { restoreItem(id: \"1\") }");
+        let Claim::Run(claimed) = keys.claim("ip 127.0.0.1", "key1", &request).await.unwrap()
+        else {
+            panic!("the first request must run");
+        };
+        assert_eq!(
+            keys.claim("ip 127.0.0.1", "key1", &request).await.unwrap(),
+            Claim::InUse
+        );
+        let response = async_graphql::Response::new(async_graphql::Value::from_json(
+            serde_json::json!({ "restoreItem": "1" }),
+        )?);
+        let executed = Executed::default();
+        executed.0.store(true, Ordering::Release);
+        keys.complete(claimed, &executed, &response).await?;
+        assert_eq!(
+            keys.claim("ip 127.0.0.1", "key1", &request).await.unwrap(),
+            Claim::Replay(r#"{"data":{"restoreItem":"1"}}"#.to_owned())
+        );
+        assert_eq!(
+            keys.claim(
+                "ip 127.0.0.1",
+                "key1",
+                &Request::new("mutation { restoreItem(id: \"2\") }")
+            )
+            .await
+            .unwrap(),
+            Claim::Reused
+        );
+        // keys are scoped to the principal
+        assert!(matches!(
+            keys.claim("ip 127.0.0.2", "key1", &request).await.unwrap(),
+            Claim::Run(_)
+        ));
+        Ok(())
+    }
+}
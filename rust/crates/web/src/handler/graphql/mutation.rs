@@ -1,9 +1,181 @@
-use async_graphql::Context;
+use async_graphql::{Context, MaybeUndefined, ResultExt as _};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
+use crate::{model, use_case};
+
+use super::{
+    graphql_data::GraphQLData,
+    query::{check::Check, item::Item},
+};
+
+/// The mutations that write take an optional `idempotencyKey`, which the
+/// handler reads before they run, see `idempotency_keys`.
 pub struct MutationRoot;
 
+/// Checking a checked item replaces its details.
+#[derive(async_graphql::InputObject)]
+pub struct CheckItemInput {
+    pub check_list_id: String,
+    pub item_id: String,
+    /// RFC 3339. Defaults to now.
+    pub checked_at: Option<String>,
+    pub note: Option<String>,
+    pub price: Option<PriceInput>,
+    /// in the item's unit
+    pub quantity: Option<f64>,
+    pub shop: Option<String>,
+    /// The version the check must be at, 0 if the item must be unchecked.
+    /// Fails with `CONFLICT` otherwise.
+    pub expected_version: Option<u64>,
+}
+
+#[derive(async_graphql::InputObject)]
+pub struct PriceInput {
+    /// in the currency's minor unit, e.g. cents
+    pub amount: i64,
+    /// ISO 4217, e.g. "JPY"
+    pub currency: String,
+}
+
+/// Omitted fields are left unchanged. `null` clears an optional field.
+#[derive(async_graphql::InputObject)]
+pub struct UpdateItemInput {
+    pub id: String,
+    pub name: Option<String>,
+    pub category: MaybeUndefined<String>,
+    pub emoji: MaybeUndefined<String>,
+    pub note: MaybeUndefined<String>,
+    pub sort_order: MaybeUndefined<i64>,
+    pub unit: MaybeUndefined<String>,
+    /// The version the item must be at. Fails with `CONFLICT` otherwise.
+    pub expected_version: Option<u64>,
+}
+
 #[async_graphql::Object]
 impl MutationRoot {
+    /// Hides the item from `items`. Returns the item id.
+    pub async fn archive_item(
+        &self,
+        context: &Context<'_>,
+        id: String,
+        #[graphql(name = "idempotencyKey")] _idempotency_key: Option<String>,
+    ) -> async_graphql::Result<String> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        let archived_at = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(|e| use_case::Error::Unknown(e.to_string()))
+            .extend()?;
+        store.archive_item(id.clone(), archived_at).await.extend()?;
+        Ok(id)
+    }
+
+    pub async fn check_item(
+        &self,
+        context: &Context<'_>,
+        input: CheckItemInput,
+        #[graphql(name = "idempotencyKey")] _idempotency_key: Option<String>,
+    ) -> async_graphql::Result<Check> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        let checked_at = match input.checked_at {
+            Some(checked_at) => checked_at,
+            None => OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .map_err(|e| use_case::Error::Unknown(e.to_string()))
+                .extend()?,
+        };
+        let check = model::Check {
+            check_list_id: input.check_list_id,
+            item_id: input.item_id,
+            checked_at: Some(checked_at),
+            note: input.note,
+            price: input
+                .price
+                .map(|PriceInput { amount, currency }| model::Price { amount, currency }),
+            quantity: input.quantity,
+            shop: input.shop,
+            ..Default::default()
+        };
+        check.validate().map_err(use_case::Error::from).extend()?;
+        let check = store
+            .compare_and_store_check(check, input.expected_version)
+            .await
+            .extend()?;
+        Ok(Check(check))
+    }
+
+    /// Deletes the item and its checks permanently. Returns the item id.
+    pub async fn purge_item(
+        &self,
+        context: &Context<'_>,
+        id: String,
+        #[graphql(name = "idempotencyKey")] _idempotency_key: Option<String>,
+    ) -> async_graphql::Result<String> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        store.purge_item(id.clone()).await.extend()?;
+        Ok(id)
+    }
+
+    /// Returns the item id.
+    pub async fn restore_item(
+        &self,
+        context: &Context<'_>,
+        id: String,
+        #[graphql(name = "idempotencyKey")] _idempotency_key: Option<String>,
+    ) -> async_graphql::Result<String> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        store.restore_item(id.clone()).await.extend()?;
+        Ok(id)
+    }
+
+    pub async fn update_item(
+        &self,
+        context: &Context<'_>,
+        input: UpdateItemInput,
+        #[graphql(name = "idempotencyKey")] _idempotency_key: Option<String>,
+    ) -> async_graphql::Result<Item> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        // TODO: Store::find_item
+        let mut item = store
+            .find_all_items()
+            .await
+            .extend()?
+            .into_iter()
+            .find(|item| item.id == input.id)
+            .ok_or_else(|| use_case::Error::NotFound(format!("item {}", input.id)))
+            .extend()?;
+        if let Some(name) = input.name {
+            item.name = name;
+        }
+        input.category.update_to(&mut item.category);
+        input.emoji.update_to(&mut item.emoji);
+        input.note.update_to(&mut item.note);
+        input.sort_order.update_to(&mut item.sort_order);
+        input.unit.update_to(&mut item.unit);
+        let item = store
+            .compare_and_store_item(item, input.expected_version)
+            .await
+            .extend()?;
+        Ok(Item(item))
+    }
+
+    /// Returns the item id. `expectedVersion` is the version the check must be
+    /// at, 0 if the item must be unchecked. Fails with `CONFLICT` otherwise.
+    pub async fn uncheck_item(
+        &self,
+        context: &Context<'_>,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+        #[graphql(name = "idempotencyKey")] _idempotency_key: Option<String>,
+    ) -> async_graphql::Result<String> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        store
+            .compare_and_delete_check(check_list_id, item_id.clone(), expected_version)
+            .await
+            .extend()?;
+        Ok(item_id)
+    }
+
     pub async fn sign_in(
         &self,
         _context: &Context<'_>,
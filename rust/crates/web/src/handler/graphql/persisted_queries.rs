@@ -0,0 +1,196 @@
+//! Automatic persisted queries (APQ) and an allow-list of operations.
+//!
+//! A client sends `extensions.persistedQuery.sha256Hash` instead of the query.
+//! If the hash is unknown, it retries with both the query and the hash, and the
+//! query is registered. This is async-graphql's `ApolloPersistedQueries`.
+//!
+//! In the allow-list mode only the queries of a manifest can be executed, sent
+//! by hash or in full. Nothing is registered.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{
+    extensions::apollo_persisted_queries::{ApolloPersistedQueries, LruCacheStorage},
+    Request, ServerError,
+};
+use sha2::{Digest as _, Sha256};
+
+use crate::use_case;
+
+use super::graphql_error::coded_error;
+
+#[derive(Clone, Debug)]
+pub enum PersistedQueries {
+    /// Registers up to `capacity` queries. The least recently used one is
+    /// dropped first.
+    Automatic { capacity: usize },
+    /// The queries of the manifest by their hash.
+    AllowList(Arc<HashMap<String, String>>),
+}
+
+impl Default for PersistedQueries {
+    fn default() -> Self {
+        Self::Automatic { capacity: 1_000 }
+    }
+}
+
+impl PersistedQueries {
+    /// Reads a manifest, a JSON object of queries by their sha256 hash.
+    pub fn allow_list(manifest: &str) -> Result<Self, use_case::Error> {
+        let queries = serde_json::from_str::<HashMap<String, String>>(manifest)
+            .map_err(|e| use_case::Error::InvalidInput(format!("manifest {}", e)))?;
+        for (hash, query) in queries.iter() {
+            if *hash != sha256(query) {
+                return Err(use_case::Error::InvalidInput(format!(
+                    "manifest hash {} does not match its query",
+                    hash
+                )));
+            }
+        }
+        Ok(Self::AllowList(Arc::new(queries)))
+    }
+
+    /// Returns the schema extension of the automatic mode.
+    pub fn extension(&self) -> Option<ApolloPersistedQueries<LruCacheStorage>> {
+        match self {
+            Self::Automatic { capacity } => {
+                Some(ApolloPersistedQueries::new(LruCacheStorage::new(*capacity)))
+            }
+            Self::AllowList(_) => None,
+        }
+    }
+
+    /// Replaces the request with the query of the allow-list, or rejects it.
+    /// Does nothing in the automatic mode, where the extension resolves it.
+    pub fn resolve(&self, request: &mut Request) -> Result<(), ServerError> {
+        let Self::AllowList(queries) = self else {
+            return Ok(());
+        };
+        let hash = match request.extensions.get("persistedQuery") {
+            Some(persisted_query) => Some(parse_extension(persisted_query)?),
+            None => None,
+        };
+        if let Some(hash) = hash.as_deref() {
+            if !request.query.is_empty() && sha256(&request.query) != hash {
+                return Err(use_case::Error::InvalidInput(
+                    "persisted query hash does not match the query".to_owned(),
+                )
+                .into());
+            }
+        }
+        let hash = hash.unwrap_or_else(|| sha256(&request.query));
+        match queries.get(&hash) {
+            Some(query) => request.query = query.clone(),
+            None => {
+                return Err(coded_error(
+                    "OPERATION_NOT_ALLOWED",
+                    "the operation is not in the allow-list".to_owned(),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `{ "version": 1, "sha256Hash": "..." }`
+fn parse_extension(value: &async_graphql::Value) -> Result<String, ServerError> {
+    let invalid = || {
+        ServerError::from(use_case::Error::InvalidInput(
+            "extensions.persistedQuery".to_owned(),
+        ))
+    };
+    let async_graphql::Value::Object(object) = value else {
+        return Err(invalid());
+    };
+    match (object.get("version"), object.get("sha256Hash")) {
+        (Some(async_graphql::Value::Number(version)), Some(async_graphql::Value::String(hash)))
+            if version.as_u64() == Some(1) =>
+        {
+            Ok(hash.to_ascii_lowercase())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub fn sha256(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(query: &str, hash: Option<&str>) -> Request {
+        let mut request = Request::new(query);
+        if let Some(hash) = hash {
+            request.extensions.insert(
+                "persistedQuery".to_owned(),
+                async_graphql::Value::from_json(serde_json::json!({
+                    "version": 1,
+                    "sha256Hash": hash,
+                }))
+                .unwrap(),
+            );
+        }
+        request
+    }
+
+    fn code(error: &ServerError) -> Option<async_graphql::Value> {
+        error
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("code"))
+            .cloned()
+    }
+
+    #[test]
+    fn test_automatic() {
+        let persisted_queries = PersistedQueries::default();
+        assert!(persisted_queries.extension().is_some());
+        let mut plain = request("{ bearer }", Some(&sha256("{ hello }")));
+        assert!(persisted_queries.resolve(&mut plain).is_ok());
+        assert_eq!(plain.query, "{ bearer }");
+    }
+
+    #[test]
+    fn test_allow_list() -> anyhow::Result<()> {
+        let query = "{ hello }";
+        let hash = sha256(query);
+        assert_eq!(
+            hash,
+            "001c3174e099bd72b729d0c0a529ba9f5a740c446e2a6e1d71b283cb84ec3065"
+        );
+        let persisted_queries =
+            PersistedQueries::allow_list(&serde_json::json!({ &hash: query }).to_string())?;
+        assert!(persisted_queries.extension().is_none());
+
+        let mut by_hash = request("", Some(&hash));
+        assert!(persisted_queries.resolve(&mut by_hash).is_ok());
+        assert_eq!(by_hash.query, query);
+
+        let mut in_full = request(query, None);
+        assert!(persisted_queries.resolve(&mut in_full).is_ok());
+
+        let mut mismatch = request("{ bearer }", Some(&hash));
+        let error = persisted_queries.resolve(&mut mismatch).unwrap_err();
+        assert_eq!(
+            code(&error),
+            Some(async_graphql::Value::from("INVALID_INPUT"))
+        );
+
+        for mut request in [
+            request("{ bearer }", None),
+            request("", Some(&sha256("{ bearer }"))),
+        ] {
+            let error = persisted_queries.resolve(&mut request).unwrap_err();
+            assert_eq!(
+                code(&error),
+                Some(async_graphql::Value::from("OPERATION_NOT_ALLOWED"))
+            );
+        }
+
+        assert!(PersistedQueries::allow_list(r#"{"00":"{ hello }"}"#).is_err());
+        assert!(PersistedQueries::allow_list("[]").is_err());
+        Ok(())
+    }
+}
@@ -1,20 +1,37 @@
-mod check;
+mod change;
+pub mod check;
 mod check_list;
-mod item;
+pub mod item;
+mod report;
 
-use async_graphql::Context;
+use async_graphql::{Context, ResultExt as _};
 
-use self::{check_list::CheckList, item::Item};
+use self::{
+    change::Change,
+    check_list::CheckList,
+    item::{Item, ItemOrderBy},
+    report::{Report, ReportGroupBy},
+};
+
+use crate::use_case;
 
 use super::graphql_data::GraphQLData;
 
+/// The complexity of a list field is this times the complexity of its
+/// selection, as the length of the list is not known before it is resolved.
+const LIST_COST: usize = 10;
+
 pub struct QueryRoot;
 
 #[async_graphql::Object]
 impl QueryRoot {
-    async fn bearer<'a>(&self, context: &Context<'a>) -> &'a str {
+    async fn bearer<'a>(&self, context: &Context<'a>) -> async_graphql::Result<&'a str> {
         let data = context.data_unchecked::<GraphQLData>();
-        data.bearer.as_ref().unwrap().token()
+        data.bearer
+            .as_ref()
+            .map(|bearer| bearer.token())
+            .ok_or_else(|| use_case::Error::Unauthorized("bearer".to_owned()))
+            .extend()
     }
 
     async fn hello(&self) -> &'static str {
@@ -25,6 +42,25 @@ impl QueryRoot {
         a + b
     }
 
+    /// Returns up to `limit` changes recorded after the revision `since`, oldest first.
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn changes<'a>(
+        &self,
+        context: &Context<'a>,
+        since: u64,
+        #[graphql(default = 100)] limit: usize,
+    ) -> async_graphql::Result<Vec<Change>> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        Ok(store
+            .changes_since(since, limit)
+            .await
+            .extend()?
+            .into_iter()
+            .map(Change)
+            .collect())
+    }
+
+    #[graphql(complexity = "LIST_COST * child_complexity")]
     async fn check_lists<'a>(
         &self,
         context: &Context<'a>,
@@ -32,19 +68,67 @@ impl QueryRoot {
         let store = &context.data_unchecked::<GraphQLData>().store;
         Ok(store
             .find_all_check_lists()
-            .await?
+            .await
+            .extend()?
             .into_iter()
             .map(CheckList)
             .collect())
     }
 
-    async fn items<'a>(&self, ctx: &Context<'a>) -> async_graphql::Result<Vec<Item>> {
+    /// Archived items are hidden unless `includeArchived` is true.
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn items<'a>(
+        &self,
+        ctx: &Context<'a>,
+        category: Option<String>,
+        #[graphql(default = false)] include_archived: bool,
+        #[graphql(default)] order_by: ItemOrderBy,
+    ) -> async_graphql::Result<Vec<Item>> {
         let store = &ctx.data_unchecked::<GraphQLData>().store;
-        Ok(store
+        let mut items = store
             .find_all_items()
-            .await?
+            .await
+            .extend()?
             .into_iter()
-            .map(Item)
-            .collect())
+            .filter(|item| include_archived || item.archived_at.is_none())
+            .filter(|item| category.is_none() || item.category == category)
+            .collect::<Vec<_>>();
+        match order_by {
+            // find_all_items is ordered by id
+            ItemOrderBy::Id => {}
+            ItemOrderBy::Name => items.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id))),
+            // items without sort order come last
+            ItemOrderBy::SortOrder => items.sort_by(|a, b| {
+                (a.sort_order.is_none(), a.sort_order, &a.id).cmp(&(
+                    b.sort_order.is_none(),
+                    b.sort_order,
+                    &b.id,
+                ))
+            }),
+        }
+        Ok(items.into_iter().map(Item).collect())
+    }
+
+    /// `from` and `to` are inclusive dates (YYYY-MM-DD).
+    async fn report<'a>(
+        &self,
+        context: &Context<'a>,
+        from: String,
+        to: String,
+        #[graphql(default)] group_by: ReportGroupBy,
+    ) -> async_graphql::Result<Report> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        let from = use_case::report::parse_date(&from).extend()?;
+        let to = use_case::report::parse_date(&to).extend()?;
+        Ok(Report(
+            use_case::report::report(
+                store.as_ref(),
+                from,
+                to,
+                use_case::report::GroupBy::from(group_by),
+            )
+            .await
+            .extend()?,
+        ))
     }
 }
@@ -0,0 +1,45 @@
+use crate::model;
+
+use super::{check::Check, check_list::CheckList, item::Item};
+
+#[derive(Clone, Debug)]
+pub struct Change(pub model::Change);
+
+/// a write recorded at a revision
+#[async_graphql::Object]
+impl Change {
+    /// the entity after the change, or the deleted entity
+    async fn entity(&self) -> Entity {
+        match self.0.entity.clone() {
+            model::Entity::Check(check) => Entity::Check(Check(check)),
+            model::Entity::CheckList(check_list) => Entity::CheckList(CheckList(check_list)),
+            model::Entity::Item(item) => Entity::Item(Item(item)),
+        }
+    }
+
+    async fn kind(&self) -> ChangeKind {
+        match self.0.kind {
+            model::ChangeKind::Created => ChangeKind::Created,
+            model::ChangeKind::Updated => ChangeKind::Updated,
+            model::ChangeKind::Deleted => ChangeKind::Deleted,
+        }
+    }
+
+    async fn revision(&self) -> u64 {
+        self.0.revision
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, async_graphql::Enum)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(async_graphql::Union)]
+pub enum Entity {
+    Check(Check),
+    CheckList(CheckList),
+    Item(Item),
+}
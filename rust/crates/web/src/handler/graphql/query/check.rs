@@ -1,10 +1,10 @@
 use crate::model;
 
 #[derive(Clone, Debug)]
-pub struct Check<'a>(pub &'a model::Check);
+pub struct Check(pub model::Check);
 
 #[async_graphql::Object]
-impl<'a> Check<'a> {
+impl Check {
     async fn check_list_id(&self) -> &str {
         &self.0.check_list_id
     }
@@ -12,4 +12,47 @@ impl<'a> Check<'a> {
     async fn item_id(&self) -> &str {
         &self.0.item_id
     }
+
+    /// RFC 3339
+    async fn checked_at(&self) -> Option<&str> {
+        self.0.checked_at.as_deref()
+    }
+
+    async fn note(&self) -> Option<&str> {
+        self.0.note.as_deref()
+    }
+
+    async fn price(&self) -> Option<Price> {
+        self.0.price.clone().map(Price)
+    }
+
+    /// in the item's unit
+    async fn quantity(&self) -> Option<f64> {
+        self.0.quantity
+    }
+
+    async fn shop(&self) -> Option<&str> {
+        self.0.shop.as_deref()
+    }
+
+    /// changes on every write, for `expectedVersion`
+    async fn version(&self) -> u64 {
+        self.0.version
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Price(pub model::Price);
+
+#[async_graphql::Object]
+impl Price {
+    /// in the currency's minor unit, e.g. cents
+    async fn amount(&self) -> i64 {
+        self.0.amount
+    }
+
+    /// ISO 4217, e.g. "JPY"
+    async fn currency(&self) -> &str {
+        &self.0.currency
+    }
 }
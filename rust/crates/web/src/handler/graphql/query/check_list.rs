@@ -1,8 +1,8 @@
-use async_graphql::Context;
+use async_graphql::{Context, ResultExt as _};
 
 use crate::{handler::graphql::graphql_data::GraphQLData, model};
 
-use super::item::Item;
+use super::{check::Check, item::Item, LIST_COST};
 
 #[derive(Clone, Debug)]
 pub struct CheckList(pub model::CheckList);
@@ -18,14 +18,21 @@ impl CheckList {
         &self.0.date
     }
 
+    /// changes on every write
+    async fn version(&self) -> u64 {
+        self.0.version
+    }
+
+    #[graphql(complexity = "LIST_COST * child_complexity")]
     async fn checked_items(&self, context: &Context<'_>) -> async_graphql::Result<Vec<Item>> {
         let store = &context.data_unchecked::<GraphQLData>().store;
-        let items = store.find_all_items().await?;
-        // TODO: Store::find_checks_by_check_list_id
-        let checks = store.find_all_checks().await?;
+        let items = store.find_all_items().await.extend()?;
+        let checks = store
+            .find_checks_by_check_list_id(self.0.id.clone())
+            .await
+            .extend()?;
         Ok(checks
             .into_iter()
-            .filter(|check| check.check_list_id == self.0.id)
             .map(|check| {
                 items
                     .iter()
@@ -36,4 +43,16 @@ impl CheckList {
             .map(Item)
             .collect())
     }
+
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn checks(&self, context: &Context<'_>) -> async_graphql::Result<Vec<Check>> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        Ok(store
+            .find_checks_by_check_list_id(self.0.id.clone())
+            .await
+            .extend()?
+            .into_iter()
+            .map(Check)
+            .collect())
+    }
 }
@@ -1,15 +1,23 @@
-use async_graphql::Context;
+use async_graphql::{Context, ResultExt as _};
 
 use crate::{
     handler::graphql::graphql_data::GraphQLData,
     model::{self},
 };
 
-use super::check_list::CheckList;
+use super::{check::Check, check_list::CheckList, LIST_COST};
 
 #[derive(Clone, Debug)]
 pub struct Item(pub model::Item);
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, async_graphql::Enum)]
+pub enum ItemOrderBy {
+    #[default]
+    Id,
+    Name,
+    SortOrder,
+}
+
 #[async_graphql::Object]
 impl Item {
     async fn id(&self) -> &str {
@@ -20,17 +28,70 @@ impl Item {
         &self.0.name
     }
 
+    /// RFC 3339. null unless the item is archived.
+    async fn archived_at(&self) -> Option<&str> {
+        self.0.archived_at.as_deref()
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+
+    /// an emoji or an icon name
+    async fn emoji(&self) -> Option<&str> {
+        self.0.emoji.as_deref()
+    }
+
+    async fn note(&self) -> Option<&str> {
+        self.0.note.as_deref()
+    }
+
+    /// user-defined display order, ascending
+    async fn sort_order(&self) -> Option<i64> {
+        self.0.sort_order
+    }
+
+    /// e.g. "roll", "bottle", "kg"
+    async fn unit(&self) -> Option<&str> {
+        self.0.unit.as_deref()
+    }
+
+    /// changes on every write, for `expectedVersion`
+    async fn version(&self) -> u64 {
+        self.0.version
+    }
+
+    /// the checks of the item, oldest check list first
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn checks(&self, context: &Context<'_>) -> async_graphql::Result<Vec<Check>> {
+        let store = &context.data_unchecked::<GraphQLData>().store;
+        let check_lists = store.find_all_check_lists().await.extend()?;
+        let mut checks = store
+            .find_checks_by_item_id(self.0.id.clone())
+            .await
+            .extend()?;
+        checks.sort_by_key(|check| {
+            check_lists
+                .iter()
+                .find(|check_list| check_list.id == check.check_list_id)
+                .map(|check_list| check_list.date.clone())
+        });
+        Ok(checks.into_iter().map(Check).collect())
+    }
+
+    #[graphql(complexity = "LIST_COST * child_complexity")]
     async fn checked_check_lists(
         &self,
         context: &Context<'_>,
     ) -> async_graphql::Result<Vec<CheckList>> {
         let store = &context.data_unchecked::<GraphQLData>().store;
-        let check_lists = store.find_all_check_lists().await?;
-        // TODO: Store::find_checks_by_item_id
-        let checks = store.find_all_checks().await?;
+        let check_lists = store.find_all_check_lists().await.extend()?;
+        let checks = store
+            .find_checks_by_item_id(self.0.id.clone())
+            .await
+            .extend()?;
         Ok(checks
             .into_iter()
-            .filter(|check| check.item_id == self.0.id)
             .map(|check| {
                 check_lists
                     .iter()
@@ -0,0 +1,127 @@
+use crate::use_case::report;
+
+use super::{item::Item, LIST_COST};
+
+#[derive(Clone, Debug)]
+pub struct Report(pub report::Report);
+
+/// spend and consumption over a period
+#[async_graphql::Object]
+impl Report {
+    /// ordered by item id
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn consumption(&self) -> Vec<Consumption> {
+        self.0
+            .consumption
+            .iter()
+            .cloned()
+            .map(Consumption)
+            .collect()
+    }
+
+    /// ordered by (key, currency)
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn spend(&self) -> Vec<Spend> {
+        self.0.spend.iter().cloned().map(Spend).collect()
+    }
+
+    /// ordered by (key, currency)
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn year_over_year(&self) -> Vec<YearOverYear> {
+        self.0
+            .year_over_year
+            .iter()
+            .cloned()
+            .map(YearOverYear)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, async_graphql::Enum)]
+pub enum ReportGroupBy {
+    Category,
+    #[default]
+    Month,
+    Shop,
+}
+
+impl From<ReportGroupBy> for report::GroupBy {
+    fn from(group_by: ReportGroupBy) -> Self {
+        match group_by {
+            ReportGroupBy::Category => Self::Category,
+            ReportGroupBy::Month => Self::Month,
+            ReportGroupBy::Shop => Self::Shop,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Consumption(pub report::Consumption);
+
+#[async_graphql::Object]
+impl Consumption {
+    async fn checks(&self) -> usize {
+        self.0.checks
+    }
+
+    async fn item(&self) -> Item {
+        Item(self.0.item.clone())
+    }
+
+    /// checks without a quantity count as one unit
+    async fn quantity(&self) -> f64 {
+        self.0.quantity
+    }
+
+    async fn units_per_30_days(&self) -> f64 {
+        self.0.units_per_30_days
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Spend(pub report::Spend);
+
+#[async_graphql::Object]
+impl Spend {
+    /// in the currency's minor unit, e.g. cents
+    async fn amount(&self) -> i64 {
+        self.0.amount
+    }
+
+    async fn checks(&self) -> usize {
+        self.0.checks
+    }
+
+    async fn currency(&self) -> &str {
+        &self.0.currency
+    }
+
+    /// "YYYY-MM", the category or the shop. null if the check has no category or shop.
+    async fn key(&self) -> Option<&str> {
+        self.0.key.as_deref()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct YearOverYear(pub report::YearOverYear);
+
+#[async_graphql::Object]
+impl YearOverYear {
+    async fn amount(&self) -> i64 {
+        self.0.amount
+    }
+
+    async fn currency(&self) -> &str {
+        &self.0.currency
+    }
+
+    /// the same as `Spend.key`. Months are keyed by the current period's month.
+    async fn key(&self) -> Option<&str> {
+        self.0.key.as_deref()
+    }
+
+    /// the spend in the same period a year earlier
+    async fn previous_amount(&self) -> i64 {
+        self.0.previous_amount
+    }
+}
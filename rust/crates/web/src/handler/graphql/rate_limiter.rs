@@ -0,0 +1,113 @@
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+
+/// The least recently used bucket is dropped to make room for a new one.
+const MAX_BUCKETS: usize = 10_000;
+
+/// A token bucket per client. A client can send `requests_per_minute`
+/// requests at once, and then one every `60 / requests_per_minute` seconds.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    tokens_per_second: f64,
+    buckets: Mutex<LruCache<String, Bucket>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self::with_max_buckets(requests_per_minute, MAX_BUCKETS)
+    }
+
+    fn with_max_buckets(requests_per_minute: u32, max_buckets: usize) -> Self {
+        let capacity = f64::from(requests_per_minute.max(1));
+        Self {
+            capacity,
+            tokens_per_second: capacity / 60.0,
+            buckets: Mutex::new(LruCache::new(
+                NonZeroUsize::new(max_buckets).expect("max_buckets is 0"),
+            )),
+        }
+    }
+
+    /// Takes a token from the bucket of `client`, or returns how long to wait
+    /// for the next one.
+    pub fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().expect("rate limiter poisoned");
+        let bucket = buckets.get_or_insert_mut(client.to_owned(), || Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+        *bucket = self.refill(*bucket, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.tokens_per_second,
+            ))
+        }
+    }
+
+    fn refill(&self, bucket: Bucket, now: Instant) -> Bucket {
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        Bucket {
+            tokens: (bucket.tokens + elapsed.as_secs_f64() * self.tokens_per_second)
+                .min(self.capacity),
+            updated_at: now.max(bucket.updated_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let rate_limiter = RateLimiter::new(2);
+        let now = Instant::now();
+        assert_eq!(rate_limiter.check("a", now), Ok(()));
+        assert_eq!(rate_limiter.check("a", now), Ok(()));
+        assert_eq!(rate_limiter.check("a", now), Err(Duration::from_secs(30)));
+        // another client has its own bucket
+        assert_eq!(rate_limiter.check("b", now), Ok(()));
+        assert_eq!(
+            rate_limiter.check("a", now + Duration::from_secs(20)),
+            Err(Duration::from_secs(10))
+        );
+        assert_eq!(
+            rate_limiter.check("a", now + Duration::from_secs(30)),
+            Ok(())
+        );
+        // the bucket does not fill beyond its capacity
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(rate_limiter.check("a", later), Ok(()));
+        assert_eq!(rate_limiter.check("a", later), Ok(()));
+        assert!(rate_limiter.check("a", later).is_err());
+    }
+
+    #[test]
+    fn test_max_buckets() {
+        let rate_limiter = RateLimiter::with_max_buckets(1, 2);
+        let now = Instant::now();
+        assert_eq!(rate_limiter.check("a", now), Ok(()));
+        assert_eq!(rate_limiter.check("b", now), Ok(()));
+        assert!(rate_limiter.check("a", now).is_err());
+        // "b" is the least recently used bucket
+        assert_eq!(rate_limiter.check("c", now), Ok(()));
+        assert_eq!(rate_limiter.check("b", now), Ok(()));
+        assert!(rate_limiter.check("c", now).is_err());
+        assert_eq!(rate_limiter.buckets.lock().unwrap().len(), 2);
+    }
+}
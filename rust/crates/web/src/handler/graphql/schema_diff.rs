@@ -0,0 +1,348 @@
+//! Classifies the differences between two GraphQL schemas (SDL).
+//!
+//! A change is breaking if a query or a response that was valid for the old
+//! schema can be invalid for the new one, e.g. a removed or retyped field.
+//! Additive changes, such as a new type, field or optional argument, are not.
+//! Descriptions, directives and default values are ignored.
+
+use std::{collections::BTreeMap, fmt};
+
+use async_graphql::parser::{
+    parse_schema,
+    types::{
+        BaseType, FieldDefinition, InputValueDefinition, Type, TypeDefinition, TypeKind,
+        TypeSystemDefinition,
+    },
+    Positioned,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    Breaking,
+    NonBreaking,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SchemaChange {
+    pub severity: Severity,
+    /// e.g. `Item`, `Item.name` or `Query.items(first)`
+    pub path: String,
+    pub description: String,
+}
+
+impl SchemaChange {
+    pub fn is_breaking(&self) -> bool {
+        self.severity == Severity::Breaking
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Breaking => "BREAKING",
+            Severity::NonBreaking => "non-breaking",
+        };
+        write!(f, "{} {}: {}", severity, self.path, self.description)
+    }
+}
+
+/// Returns the changes from `old` to `new`, ordered by type name.
+pub fn diff_schemas(
+    old: &str,
+    new: &str,
+) -> Result<Vec<SchemaChange>, async_graphql::parser::Error> {
+    let old = type_definitions(old)?;
+    let new = type_definitions(new)?;
+    let mut changes = Changes::default();
+    for (name, old_type) in old.iter() {
+        match new.get(name) {
+            Some(new_type) => diff_types(&mut changes, name, old_type, new_type),
+            None => changes.breaking(name, "type removed"),
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.non_breaking(name, "type added");
+    }
+    Ok(changes.0)
+}
+
+#[derive(Default)]
+struct Changes(Vec<SchemaChange>);
+
+impl Changes {
+    fn push<P: fmt::Display, D: Into<String>>(
+        &mut self,
+        severity: Severity,
+        path: P,
+        description: D,
+    ) {
+        self.0.push(SchemaChange {
+            severity,
+            path: path.to_string(),
+            description: description.into(),
+        });
+    }
+
+    fn breaking<P: fmt::Display, D: Into<String>>(&mut self, path: P, description: D) {
+        self.push(Severity::Breaking, path, description);
+    }
+
+    fn non_breaking<P: fmt::Display, D: Into<String>>(&mut self, path: P, description: D) {
+        self.push(Severity::NonBreaking, path, description);
+    }
+}
+
+fn type_definitions(
+    sdl: &str,
+) -> Result<BTreeMap<String, TypeDefinition>, async_graphql::parser::Error> {
+    Ok(parse_schema(sdl)?
+        .definitions
+        .into_iter()
+        .filter_map(|definition| match definition {
+            TypeSystemDefinition::Type(definition) => Some(definition.node),
+            TypeSystemDefinition::Schema(_) | TypeSystemDefinition::Directive(_) => None,
+        })
+        .map(|definition| (definition.name.node.to_string(), definition))
+        .collect())
+}
+
+fn kind_name(kind: &TypeKind) -> &'static str {
+    match kind {
+        TypeKind::Scalar => "scalar",
+        TypeKind::Object(_) => "object",
+        TypeKind::Interface(_) => "interface",
+        TypeKind::Union(_) => "union",
+        TypeKind::Enum(_) => "enum",
+        TypeKind::InputObject(_) => "input object",
+    }
+}
+
+fn diff_types(changes: &mut Changes, name: &str, old: &TypeDefinition, new: &TypeDefinition) {
+    match (&old.kind, &new.kind) {
+        (TypeKind::Scalar, TypeKind::Scalar) => {}
+        (TypeKind::Object(old), TypeKind::Object(new)) => {
+            diff_names(changes, name, "interface", &old.implements, &new.implements);
+            diff_fields(changes, name, &old.fields, &new.fields);
+        }
+        (TypeKind::Interface(old), TypeKind::Interface(new)) => {
+            diff_names(changes, name, "interface", &old.implements, &new.implements);
+            diff_fields(changes, name, &old.fields, &new.fields);
+        }
+        (TypeKind::Union(old), TypeKind::Union(new)) => {
+            diff_names(changes, name, "member", &old.members, &new.members);
+        }
+        (TypeKind::Enum(old), TypeKind::Enum(new)) => {
+            let old = old.values.iter().map(|value| value.node.value.clone());
+            let new = new.values.iter().map(|value| value.node.value.clone());
+            diff_names(
+                changes,
+                name,
+                "value",
+                &old.collect::<Vec<_>>(),
+                &new.collect::<Vec<_>>(),
+            );
+        }
+        (TypeKind::InputObject(old), TypeKind::InputObject(new)) => {
+            diff_input_values(changes, name, "input field", &old.fields, &new.fields);
+        }
+        (old, new) => changes.breaking(
+            name,
+            format!("kind changed from {} to {}", kind_name(old), kind_name(new)),
+        ),
+    }
+}
+
+/// Removing a name from a list (interfaces, union members, enum values) is breaking.
+fn diff_names<N: PartialEq + fmt::Display>(
+    changes: &mut Changes,
+    path: &str,
+    what: &str,
+    old: &[N],
+    new: &[N],
+) {
+    for name in old.iter().filter(|name| !new.contains(name)) {
+        changes.breaking(path, format!("{} {} removed", what, name));
+    }
+    for name in new.iter().filter(|name| !old.contains(name)) {
+        changes.non_breaking(path, format!("{} {} added", what, name));
+    }
+}
+
+fn diff_fields(
+    changes: &mut Changes,
+    type_name: &str,
+    old: &[Positioned<FieldDefinition>],
+    new: &[Positioned<FieldDefinition>],
+) {
+    for old_field in old.iter().map(|field| &field.node) {
+        let path = format!("{}.{}", type_name, old_field.name.node);
+        let Some(new_field) = new
+            .iter()
+            .map(|field| &field.node)
+            .find(|field| field.name.node == old_field.name.node)
+        else {
+            changes.breaking(&path, "field removed");
+            continue;
+        };
+        let (old_type, new_type) = (&old_field.ty.node, &new_field.ty.node);
+        if old_type != new_type {
+            // a response can only get stricter
+            let severity = if is_at_least_as_strict(new_type, old_type) {
+                Severity::NonBreaking
+            } else {
+                Severity::Breaking
+            };
+            changes.push(
+                severity,
+                &path,
+                format!("type changed from {} to {}", old_type, new_type),
+            );
+        }
+        diff_input_values(
+            changes,
+            &path,
+            "argument",
+            &old_field.arguments,
+            &new_field.arguments,
+        );
+    }
+    for new_field in new.iter().map(|field| &field.node) {
+        if !old
+            .iter()
+            .any(|field| field.node.name.node == new_field.name.node)
+        {
+            changes.non_breaking(
+                format!("{}.{}", type_name, new_field.name.node),
+                "field added",
+            );
+        }
+    }
+}
+
+/// Arguments and input object fields.
+fn diff_input_values(
+    changes: &mut Changes,
+    path: &str,
+    what: &str,
+    old: &[Positioned<InputValueDefinition>],
+    new: &[Positioned<InputValueDefinition>],
+) {
+    let path_of = |name: &str| {
+        if what == "argument" {
+            format!("{}({})", path, name)
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+    for old_value in old.iter().map(|value| &value.node) {
+        let path = path_of(&old_value.name.node);
+        let Some(new_value) = new
+            .iter()
+            .map(|value| &value.node)
+            .find(|value| value.name.node == old_value.name.node)
+        else {
+            changes.breaking(&path, format!("{} removed", what));
+            continue;
+        };
+        let (old_type, new_type) = (&old_value.ty.node, &new_value.ty.node);
+        if old_type != new_type {
+            // an input can only get looser
+            let severity = if is_at_least_as_strict(old_type, new_type) {
+                Severity::NonBreaking
+            } else {
+                Severity::Breaking
+            };
+            changes.push(
+                severity,
+                &path,
+                format!("type changed from {} to {}", old_type, new_type),
+            );
+        }
+    }
+    for new_value in new.iter().map(|value| &value.node) {
+        if old
+            .iter()
+            .any(|value| value.node.name.node == new_value.name.node)
+        {
+            continue;
+        }
+        let path = path_of(&new_value.name.node);
+        if !new_value.ty.node.nullable && new_value.default_value.is_none() {
+            changes.breaking(path, format!("required {} added", what));
+        } else {
+            changes.non_breaking(path, format!("{} added", what));
+        }
+    }
+}
+
+/// Returns true if `a` is `b` with zero or more nullable levels made non-null.
+fn is_at_least_as_strict(a: &Type, b: &Type) -> bool {
+    (b.nullable || !a.nullable)
+        && match (&a.base, &b.base) {
+            (BaseType::Named(a), BaseType::Named(b)) => a == b,
+            (BaseType::List(a), BaseType::List(b)) => is_at_least_as_strict(a, b),
+            (BaseType::Named(_), BaseType::List(_)) | (BaseType::List(_), BaseType::Named(_)) => {
+                false
+            }
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_schemas() -> anyhow::Result<()> {
+        let old = r#"
+            type Item { id: String! name: String! note: String tags: [String] }
+            type Query { items(first: Int): [Item!]! item(id: String!): Item }
+            enum GroupBy { MONTH SHOP }
+            input PriceInput { amount: Int! currency: String! }
+            union Entity = Item | Query
+            scalar Removed
+        "#;
+        let new = r#"
+            type Item { id: String! name: Int! note: String! tags: [String!] emoji: String }
+            type Query {
+                items(first: Int, after: String): [Item!]!
+                item(id: String): Item
+                search(q: String!, limit: Int!): [Item!]!
+            }
+            enum GroupBy { MONTH CATEGORY }
+            input PriceInput { amount: Int! currency: String! note: String shop: String! }
+            union Entity = Item
+            type Removed { id: String! }
+            scalar Added
+        "#;
+        let changes = diff_schemas(old, new)?;
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "BREAKING Entity: member Query removed",
+                "BREAKING GroupBy: value SHOP removed",
+                "non-breaking GroupBy: value CATEGORY added",
+                "BREAKING Item.name: type changed from String! to Int!",
+                "non-breaking Item.note: type changed from String to String!",
+                "non-breaking Item.tags: type changed from [String] to [String!]",
+                "non-breaking Item.emoji: field added",
+                "non-breaking PriceInput.note: input field added",
+                "BREAKING PriceInput.shop: required input field added",
+                "non-breaking Query.items(after): argument added",
+                "non-breaking Query.item(id): type changed from String! to String",
+                "non-breaking Query.search: field added",
+                "BREAKING Removed: kind changed from scalar to object",
+                "non-breaking Added: type added",
+            ]
+        );
+        assert_eq!(
+            diff_schemas(new, old)?
+                .iter()
+                .filter(|change| change.is_breaking())
+                .count(),
+            12
+        );
+        assert!(diff_schemas(old, old)?.is_empty());
+        assert!(diff_schemas("type {", old).is_err());
+        Ok(())
+    }
+}
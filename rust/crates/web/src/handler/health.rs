@@ -0,0 +1,151 @@
+//! Probes for a load balancer or an orchestrator.
+
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, routing, Json, Router};
+
+use crate::{shutdown::HasShutdown, use_case::HasStore};
+
+use super::{HasGraphQLSchema, LimitedBody};
+
+/// A store that takes longer than this is not ready.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, serde::Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    store: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionResponse {
+    version: &'static str,
+    git_sha: &'static str,
+    /// sha256 of the GraphQL SDL
+    schema_hash: String,
+}
+
+/// Liveness: the process can serve requests. Does not touch the store.
+async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        store: None,
+    })
+}
+
+/// Readiness: the server is not shutting down and the store answers a cheap
+/// query in time.
+async fn readyz<T: HasShutdown + HasStore>(
+    State(state): State<T>,
+) -> (StatusCode, Json<HealthResponse>) {
+    if state.shutdown().has_begun() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(HealthResponse {
+                status: "shutting_down",
+                store: None,
+            }),
+        );
+    }
+    let store = state.store();
+    let result = match tokio::time::timeout(READINESS_TIMEOUT, store.find_current_revision()).await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("timed out after {:?}", READINESS_TIMEOUT)),
+    };
+    match result {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(HealthResponse {
+                status: "ok",
+                store: Some("ok".to_owned()),
+            }),
+        ),
+        Err(message) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(HealthResponse {
+                status: "unavailable",
+                store: Some(message),
+            }),
+        ),
+    }
+}
+
+async fn version<T: HasGraphQLSchema>(State(state): State<T>) -> Json<VersionResponse> {
+    Json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        schema_hash: state.graphql_schema().sdl_hash(),
+    })
+}
+
+pub fn route<T: Clone + HasGraphQLSchema + HasShutdown + HasStore + Send + Sync + 'static>(
+) -> Router<T, LimitedBody> {
+    Router::new()
+        .route("/healthz", routing::get(healthz))
+        .route("/readyz", routing::get(readyz::<T>))
+        .route("/version", routing::get(version::<T>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::App,
+        test_utils::{request, send_request, ResponseExt},
+    };
+
+    #[tokio::test]
+    async fn test_healthz() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let response = send_request(app, request("GET", "/healthz", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body_as_string().await?, r#"{"status":"ok"}"#);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_readyz() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let response = send_request(app, request("GET", "/readyz", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"status":"ok","store":"ok"}"#
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_readyz_shutting_down() -> anyhow::Result<()> {
+        let app = App::example();
+        app.shutdown().begin();
+        let response =
+            send_request(route().with_state(app), request("GET", "/readyz", "")?).await?;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.into_body_as_string().await?,
+            r#"{"status":"shutting_down"}"#
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_version() -> anyhow::Result<()> {
+        let app = App::example();
+        let schema_hash = app.graphql_schema().sdl_hash();
+        let response =
+            send_request(route().with_state(app), request("GET", "/version", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body =
+            serde_json::from_str::<serde_json::Value>(&response.into_body_as_string().await?)?;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["gitSha"], env!("GIT_SHA"));
+        assert_eq!(body["schemaHash"], schema_hash);
+        assert_eq!(schema_hash.len(), 64);
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+/// The options of the middleware stack around every route.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HttpOptions {
+    /// The origins allowed by CORS, e.g. `https://dashboard.example.com`, or
+    /// `*` for any. None by default.
+    pub allowed_origins: Vec<String>,
+    /// Larger request bodies are rejected with 413.
+    pub max_body_bytes: usize,
+    /// Slower requests are aborted with 408.
+    pub request_timeout: Duration,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            max_body_bytes: 2 * 1024 * 1024,
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse as _, Response},
+    routing, Json, Router,
+};
+
+use crate::use_case::{self, export::Format, HasStore};
+
+use super::{error_response, LimitedBody};
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportQuery {
+    dry_run: Option<bool>,
+    format: Option<String>,
+}
+
+/// Responds with the summary, as 422 if any row is invalid (nothing is written then).
+async fn post_handler<T: HasStore>(
+    State(state): State<T>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<Response, Response> {
+    let format = query
+        .format
+        .as_deref()
+        .map(str::parse::<Format>)
+        .transpose()
+        .map_err(error_response)?
+        .unwrap_or_default();
+    let store = state.store();
+    let summary = use_case::import::import(
+        store.as_ref(),
+        format,
+        &body,
+        query.dry_run.unwrap_or_default(),
+    )
+    .await
+    .map_err(error_response)?;
+    let status = if summary.errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok((status, Json(summary)).into_response())
+}
+
+pub fn route<T: Clone + HasStore + Send + Sync + 'static>() -> Router<T, LimitedBody> {
+    Router::new().route("/import", routing::post(post_handler::<T>))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::App,
+        test_utils::{request, send_request, ResponseExt, StatusCode},
+    };
+
+    #[tokio::test]
+    async fn test_post() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let csv = concat!(
+            "date,check_list_id,item_id,item_name,quantity,price_amount,price_currency,shop,note,checked_at\n",
+            "2020-01-04,,,item3,,,,,,\n",
+        );
+        let response = send_request(
+            app.clone(),
+            request("POST", "/import?format=csv&dryRun=true", csv)?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&response.into_body_as_string().await?)?,
+            serde_json::json!({
+                "dryRun": true,
+                "items": { "created": 1, "updated": 0, "unchanged": 0 },
+                "checkLists": { "created": 1, "updated": 0, "unchanged": 0 },
+                "checks": { "created": 1, "updated": 0, "unchanged": 0 },
+                "errors": []
+            })
+        );
+
+        let response = send_request(
+            app.clone(),
+            request("POST", "/import?format=csv", csv.replace("item3", ""))?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = send_request(app, request("POST", "/import", "{}")?).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+}
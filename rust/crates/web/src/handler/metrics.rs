@@ -0,0 +1,65 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+    routing, Router,
+};
+
+use crate::infra::metrics::Metrics;
+
+use super::LimitedBody;
+
+async fn handler() -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        Metrics::global().render(),
+    )
+        .into_response()
+}
+
+pub fn route<T: Clone + Send + Sync + 'static>() -> Router<T, LimitedBody> {
+    Router::new().route("/metrics", routing::get(handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::App,
+        handler,
+        test_utils::{request, send_request, ResponseExt, StatusCode},
+    };
+
+    #[tokio::test]
+    async fn test_get() -> anyhow::Result<()> {
+        let app = handler::route().with_state(App::example());
+        let response = send_request(
+            app.clone(),
+            request(
+                "POST",
+                "/graphql",
+                r#"{"query":"query MetricsTest { items { id } }","operationName":"MetricsTest"}"#,
+            )?,
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send_request(app, request("GET", "/metrics", "")?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; version=0.0.4"
+        );
+        let body = response.into_body_as_string().await?;
+        // other tests share the global metrics, so only this test's series are checked
+        for line in [
+            r#"graphql_requests_total{operation="MetricsTest",result="ok"} 1"#,
+            r#"graphql_request_duration_seconds_count{operation="MetricsTest"} 1"#,
+            "# TYPE graphql_requests_total counter",
+            "# TYPE store_call_duration_seconds histogram",
+        ] {
+            assert!(body.lines().any(|l| l == line), "{}", line);
+        }
+        assert!(body.contains(r#"store_call_duration_seconds_count{method="find_all_items"}"#));
+        Ok(())
+    }
+}
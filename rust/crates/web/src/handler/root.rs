@@ -1,10 +1,12 @@
 use axum::{routing, Router};
 
+use super::LimitedBody;
+
 async fn handler() -> &'static str {
     "Hello, World!"
 }
 
-pub fn route<T: Clone + Send + Sync + 'static>() -> Router<T> {
+pub fn route<T: Clone + Send + Sync + 'static>() -> Router<T, LimitedBody> {
     Router::new().route("/", routing::get(handler))
 }
 
@@ -0,0 +1,96 @@
+use axum::{extract::State, response::Response, routing, Json, Router};
+
+use crate::use_case::{
+    self,
+    sync::{SyncRequest, SyncResponse, CHANGES_LIMIT},
+    HasStore,
+};
+
+use super::{error_response, LimitedBody};
+
+async fn post_handler<T: HasStore>(
+    State(state): State<T>,
+    Json(request): Json<SyncRequest>,
+) -> Result<Json<SyncResponse>, Response> {
+    let store = state.store();
+    use_case::sync::sync(store.as_ref(), request, CHANGES_LIMIT)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+pub fn route<T: Clone + HasStore + Send + Sync + 'static>() -> Router<T, LimitedBody> {
+    Router::new().route("/sync", routing::post(post_handler::<T>))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        app::App,
+        test_utils::{request, send_request, ResponseExt, StatusCode},
+    };
+
+    #[tokio::test]
+    async fn test_post() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let request = request(
+            "POST",
+            "/sync",
+            json!({
+                "clientId": "android1",
+                "commands": [
+                    {
+                        "command": {
+                            "type": "setChecked",
+                            "payload": { "checkListId": "1", "checked": false, "itemId": "1" }
+                        },
+                        "sequence": 1
+                    }
+                ],
+                "syncToken": "0"
+            })
+            .to_string(),
+        )?;
+        let response = send_request(app, request).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body =
+            serde_json::from_str::<serde_json::Value>(&response.into_body_as_string().await?)?;
+        assert_eq!(
+            body["results"],
+            json!([{ "sequence": 1, "status": "applied" }])
+        );
+        assert_eq!(body["full"], json!(false));
+        let changes = body["changes"].as_array().cloned().unwrap_or_default();
+        assert_eq!(
+            changes.last(),
+            Some(&json!({
+                "entity": { "type": "check", "payload": { "checkListId": "1", "itemId": "1", "version": 7 } },
+                "kind": "deleted",
+                "revision": 7
+            }))
+        );
+        assert_eq!(body["syncToken"], json!("7"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_invalid_sync_token() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let request = request(
+            "POST",
+            "/sync",
+            json!({ "clientId": "android1", "commands": [], "syncToken": "x" }).to_string(),
+        )?;
+        let response = send_request(app, request).await?;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body =
+            serde_json::from_str::<serde_json::Value>(&response.into_body_as_string().await?)?;
+        assert_eq!(body["code"], json!("INVALID_INPUT"));
+        Ok(())
+    }
+}
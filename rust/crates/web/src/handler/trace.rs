@@ -0,0 +1,115 @@
+//! A span per HTTP request, with the request id, and an event when it is
+//! answered, with the status and the latency.
+//!
+//! The request id is taken from the `x-request-id` header, or generated, and
+//! is returned in the same header.
+
+use std::time::Instant;
+
+use axum::{
+    http::{HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument as _;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The longest request id that is accepted from a client.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+pub async fn middleware<B>(request: Request<B>, next: Next<B>) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "http_request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        status = tracing::field::Empty,
+    );
+    let started_at = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let latency_ms = started_at.elapsed().as_secs_f64() * 1_000.0;
+    let status = response.status();
+    span.record("status", status.as_u16());
+    if status.is_server_error() {
+        tracing::error!(parent: &span, status = status.as_u16(), latency_ms, "response");
+    } else {
+        tracing::info!(parent: &span, status = status.as_u16(), latency_ms, "response");
+    }
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, middleware, routing, Router};
+
+    use super::*;
+    use crate::{
+        infra::logging,
+        test_utils::{request, send_request, LogBuffer},
+    };
+
+    #[tokio::test]
+    async fn test_request_id() -> anyhow::Result<()> {
+        let app = Router::<(), Body>::new()
+            .route("/", routing::get(|| async { "" }))
+            .layer(middleware::from_fn(super::middleware));
+
+        let response = send_request(app.clone(), request("GET", "/", "")?).await?;
+        let generated = response.headers()[&REQUEST_ID].to_str()?.to_owned();
+        assert_eq!(uuid::Uuid::parse_str(&generated)?.get_version_num(), 4);
+
+        let mut request = request("GET", "/", "")?;
+        request
+            .headers_mut()
+            .insert(REQUEST_ID, HeaderValue::from_static("abc"));
+        let response = send_request(app, request).await?;
+        assert_eq!(response.headers()[&REQUEST_ID], "abc");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_response_event() -> anyhow::Result<()> {
+        let app = Router::<(), Body>::new()
+            .route("/", routing::get(|| async { "" }))
+            .route(
+                "/error",
+                routing::get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            )
+            .layer(middleware::from_fn(super::middleware));
+        let buffer = LogBuffer::default();
+        let _guard = tracing::subscriber::set_default(logging::subscriber(
+            buffer.clone(),
+            tracing::Level::INFO,
+        ));
+
+        send_request(app.clone(), request("GET", "/", "")?).await?;
+        send_request(app, request("GET", "/error", "")?).await?;
+        let events = buffer
+            .lines()?
+            .into_iter()
+            .filter(|line| line["fields"]["message"] == "response")
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        for (event, level, status, path) in [
+            (&events[0], "INFO", 200, "/"),
+            (&events[1], "ERROR", 500, "/error"),
+        ] {
+            assert_eq!(event["level"], level);
+            assert_eq!(event["fields"]["status"], status);
+            assert!(event["fields"]["latency_ms"].is_f64());
+            assert_eq!(event["span"]["path"], path);
+        }
+        Ok(())
+    }
+}
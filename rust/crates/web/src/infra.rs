@@ -1,3 +1,10 @@
+pub mod file_store;
 pub mod firestore;
+pub mod firestore_idempotency_key_store;
 pub mod firestore_store;
+pub mod idempotency_key_store;
+pub mod instrumented_store;
+pub mod logging;
+pub mod metrics;
+pub mod sqlite_store;
 pub mod store;
@@ -0,0 +1,1165 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead as _, BufReader, Seek as _, Write as _},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use axum::async_trait;
+
+use crate::{
+    model,
+    use_case::{
+        self,
+        data::{CheckListData, Command, EntityData, ItemData, PriceData, UserData},
+        Store,
+    },
+};
+
+use super::store::Indexes;
+
+/// A line of the command log.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+enum LogEntry {
+    Command(Command),
+    /// commands applied all or nothing
+    Batch {
+        batch: Vec<Command>,
+    },
+    /// `sequence` is `null` if the sequence has been deleted
+    #[serde(rename_all = "camelCase")]
+    ClientSequence {
+        client_id: String,
+        sequence: Option<u64>,
+    },
+    /// written by compaction, keeping the version of the entity
+    Entity {
+        entity: EntityData,
+    },
+    /// written last by compaction; the changes before it have been discarded
+    Revision {
+        revision: u64,
+    },
+    User {
+        user: UserData,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid fsync policy {0}")]
+    InvalidFsyncPolicy(String),
+    #[error("io {0}")]
+    Io(#[from] std::io::Error),
+    #[error("join {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("lock poisoned")]
+    LockPoisoned,
+    #[error("replay line {0}: {1}")]
+    Replay(usize, Box<Error>),
+    #[error("serde_json {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("store {0}")]
+    Store(#[from] super::store::Error),
+}
+
+impl From<Error> for use_case::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::InvalidFsyncPolicy(_) => Self::InvalidInput(e.to_string()),
+            Error::Store(e) => Self::from(e),
+            Error::Io(_)
+            | Error::Join(_)
+            | Error::LockPoisoned
+            | Error::Replay(_, _)
+            | Error::SerdeJson(_) => Self::Unknown(e.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FsyncPolicy {
+    /// fsync after every command
+    #[default]
+    Always,
+    /// fsync after every N commands
+    Every(usize),
+    /// leave it to the OS
+    Never,
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => s
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(Self::Every)
+                .ok_or_else(|| Error::InvalidFsyncPolicy(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FileStoreOptions {
+    /// rewrite the log as a snapshot after this many appended commands
+    pub compaction_threshold: Option<usize>,
+    pub fsync_policy: FsyncPolicy,
+}
+
+impl Default for FileStoreOptions {
+    fn default() -> Self {
+        Self {
+            compaction_threshold: Some(1_000),
+            fsync_policy: FsyncPolicy::default(),
+        }
+    }
+}
+
+fn check(indexes: &Indexes, entry: &LogEntry) -> Result<(), Error> {
+    match entry {
+        LogEntry::Command(command) => check_command(indexes, command),
+        LogEntry::Batch { batch } => {
+            let mut indexes = indexes.clone();
+            for command in batch {
+                check_command(&indexes, command)?;
+                handle_command(&mut indexes, command.clone())?;
+            }
+            Ok(())
+        }
+        LogEntry::ClientSequence { .. }
+        | LogEntry::Entity { .. }
+        | LogEntry::Revision { .. }
+        | LogEntry::User { .. } => Ok(()),
+    }
+}
+
+fn check_command(indexes: &Indexes, command: &Command) -> Result<(), Error> {
+    match command {
+        Command::AddCheckList { check_list } => {
+            indexes.can_store_check_list(&model::CheckList::from(check_list.clone()))?
+        }
+        Command::AddItem { .. } | Command::PurgeItem { .. } | Command::SetItem { .. } => {}
+        Command::ArchiveItem { item_id, .. } | Command::RestoreItem { item_id } => {
+            if !indexes.contains_item(item_id) {
+                return Err(Error::from(super::store::Error::ItemNotFound(
+                    item_id.clone(),
+                )));
+            }
+        }
+        Command::SetChecked {
+            check_list_id,
+            checked: true,
+            item_id,
+            ..
+        } => indexes.can_store_check(&model::Check {
+            check_list_id: check_list_id.clone(),
+            item_id: item_id.clone(),
+            ..Default::default()
+        })?,
+        Command::SetChecked { checked: false, .. } => {}
+    }
+    Ok(())
+}
+
+fn handle(indexes: &mut Indexes, entry: LogEntry) -> Result<(), Error> {
+    match entry {
+        LogEntry::Command(command) => handle_command(indexes, command)?,
+        LogEntry::Batch { batch } => {
+            for command in batch {
+                handle_command(indexes, command)?;
+            }
+        }
+        LogEntry::ClientSequence {
+            client_id,
+            sequence: Some(sequence),
+        } => indexes.store_client_sequence(client_id, sequence),
+        LogEntry::ClientSequence {
+            client_id,
+            sequence: None,
+        } => indexes.delete_client_sequence(&client_id),
+        LogEntry::Entity { entity } => indexes.load(model::Entity::from(entity)),
+        LogEntry::Revision { revision } => indexes.compact(revision),
+        LogEntry::User { user } => indexes.store_user(model::User::from(user)),
+    }
+    Ok(())
+}
+
+fn handle_command(indexes: &mut Indexes, command: Command) -> Result<(), Error> {
+    match command {
+        Command::AddCheckList { check_list } => {
+            indexes.store_check_list(model::CheckList::from(check_list))?
+        }
+        Command::AddItem { item } | Command::SetItem { item } => {
+            indexes.store_item(model::Item::from(item));
+        }
+        Command::ArchiveItem {
+            archived_at,
+            item_id,
+        } => indexes.archive_item(&item_id, &archived_at)?,
+        Command::PurgeItem { item_id } => indexes.purge_item(&item_id),
+        Command::RestoreItem { item_id } => indexes.restore_item(&item_id)?,
+        Command::SetChecked {
+            check_list_id,
+            checked: true,
+            checked_at,
+            item_id,
+            note,
+            price,
+            quantity,
+            shop,
+        } => {
+            indexes.store_check(model::Check {
+                check_list_id,
+                item_id,
+                checked_at,
+                note,
+                price: price.map(model::Price::from),
+                quantity,
+                shop,
+                ..Default::default()
+            })?;
+        }
+        Command::SetChecked {
+            check_list_id,
+            checked: false,
+            item_id,
+            ..
+        } => indexes.delete_check(&check_list_id, &item_id),
+    }
+    Ok(())
+}
+
+fn set_checked(check: model::Check) -> Command {
+    Command::SetChecked {
+        check_list_id: check.check_list_id,
+        checked: true,
+        checked_at: check.checked_at,
+        item_id: check.item_id,
+        note: check.note,
+        price: check.price.map(PriceData::from),
+        quantity: check.quantity,
+        shop: check.shop,
+    }
+}
+
+/// Returns the shortest list of entries that rebuilds the indexes.
+fn snapshot(indexes: &Indexes) -> Vec<LogEntry> {
+    let check_lists = indexes
+        .find_all_check_lists()
+        .into_iter()
+        .map(model::Entity::CheckList);
+    let items = indexes
+        .find_all_items()
+        .into_iter()
+        .map(model::Entity::Item);
+    let checks = indexes
+        .find_all_checks()
+        .into_iter()
+        .map(model::Entity::Check);
+    let client_sequences = indexes
+        .client_sequences()
+        .iter()
+        .map(|(client_id, sequence)| LogEntry::ClientSequence {
+            client_id: client_id.clone(),
+            sequence: Some(*sequence),
+        });
+    let users = indexes.users().map(|user| LogEntry::User {
+        user: UserData::from(user.clone()),
+    });
+    check_lists
+        .chain(items)
+        .chain(checks)
+        .map(|entity| LogEntry::Entity {
+            entity: EntityData::from(entity),
+        })
+        .chain(client_sequences)
+        .chain(users)
+        .chain(std::iter::once(LogEntry::Revision {
+            revision: indexes.find_current_revision(),
+        }))
+        .collect()
+}
+
+#[derive(Debug)]
+struct Inner {
+    commands_since_compaction: usize,
+    commands_since_fsync: usize,
+    file: File,
+    indexes: Indexes,
+}
+
+/// A `Store` that appends every write to a command log and replays it on open.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    inner: Arc<Mutex<Inner>>,
+    options: FileStoreOptions,
+    path: PathBuf,
+}
+
+impl FileStore {
+    /// Replays the log. A last line without a newline was cut off by a crash
+    /// before it was acknowledged, so it is truncated away.
+    pub fn open<P: AsRef<Path>>(path: P, options: FileStoreOptions) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let mut indexes = Indexes::default();
+        let mut commands = 0_usize;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        let mut reader = BufReader::new(&file);
+        let mut line = Vec::new();
+        let mut complete_len = 0_u64;
+        let mut index = 0_usize;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            if line.last() != Some(&b'\n') {
+                tracing::warn!(
+                    path = %path.display(),
+                    bytes = line.len(),
+                    "truncating the incomplete last line of the log"
+                );
+                break;
+            }
+            complete_len += line.len() as u64;
+            index += 1;
+            if line.len() == 1 {
+                continue;
+            }
+            serde_json::from_slice::<LogEntry>(&line)
+                .map_err(Error::from)
+                .and_then(|entry| handle(&mut indexes, entry))
+                .map_err(|e| Error::Replay(index, Box::new(e)))?;
+            commands += 1;
+        }
+        drop(reader);
+        if file.seek(std::io::SeekFrom::End(0))? != complete_len {
+            file.set_len(complete_len)?;
+            file.sync_data()?;
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner {
+                commands_since_compaction: commands,
+                commands_since_fsync: 0,
+                file,
+                indexes,
+            })),
+            options,
+            path,
+        })
+    }
+
+    /// Rewrites the log so that it only contains the current state.
+    pub async fn compact(&self) -> Result<(), Error> {
+        let inner = self.inner.clone();
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = inner.lock().map_err(|_| Error::LockPoisoned)?;
+            compact(&path, &mut inner)
+        })
+        .await?
+    }
+
+    /// fsyncs the commands that the fsync policy has not fsynced yet.
+    pub async fn sync(&self) -> Result<(), Error> {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = inner.lock().map_err(|_| Error::LockPoisoned)?;
+            if inner.commands_since_fsync > 0 {
+                inner.file.sync_data()?;
+                inner.commands_since_fsync = 0;
+            }
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn handle(&self, command: Command) -> Result<(), Error> {
+        self.append(LogEntry::Command(command)).await
+    }
+
+    async fn append(&self, entry: LogEntry) -> Result<(), Error> {
+        self.append_if(entry, |_| Ok(()), |_| ()).await
+    }
+
+    /// Appends the entry if `precondition` holds, and returns what `result`
+    /// reads from the indexes after the entry is applied, under the same lock.
+    async fn append_if<P, R, T>(
+        &self,
+        entry: LogEntry,
+        precondition: P,
+        result: R,
+    ) -> Result<T, Error>
+    where
+        P: FnOnce(&Indexes) -> Result<(), super::store::Error> + Send + 'static,
+        R: FnOnce(&Indexes) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let options = self.options;
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = inner.lock().map_err(|_| Error::LockPoisoned)?;
+            precondition(&inner.indexes)?;
+            check(&inner.indexes, &entry)?;
+
+            let mut line = serde_json::to_vec(&entry)?;
+            line.push(b'\n');
+            let fsync = match options.fsync_policy {
+                FsyncPolicy::Always => true,
+                FsyncPolicy::Every(n) => inner.commands_since_fsync + 1 >= n,
+                FsyncPolicy::Never => false,
+            };
+            write_or_truncate(&inner.file, || {
+                (&inner.file).write_all(&line)?;
+                if fsync {
+                    inner.file.sync_data()?;
+                }
+                Ok(())
+            })?;
+            inner.commands_since_fsync = if fsync {
+                0
+            } else {
+                inner.commands_since_fsync + 1
+            };
+
+            handle(&mut inner.indexes, entry)?;
+            let result = result(&inner.indexes);
+            inner.commands_since_compaction += 1;
+            if options
+                .compaction_threshold
+                .is_some_and(|threshold| inner.commands_since_compaction >= threshold)
+            {
+                // the entry is already written, so the write has succeeded;
+                // compaction is retried after the next entry
+                if let Err(e) = compact(&path, &mut inner) {
+                    tracing::warn!(path = %path.display(), error = %e, "cannot compact the log");
+                }
+            }
+            Ok(result)
+        })
+        .await?
+    }
+
+    fn read<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Indexes) -> T,
+    {
+        let inner = self.inner.lock().map_err(|_| Error::LockPoisoned)?;
+        Ok(f(&inner.indexes))
+    }
+}
+
+/// Truncates the log back to its length before `write` if `write` fails, so
+/// that a line cut off by e.g. a full disk is not glued to the next one.
+fn write_or_truncate<F>(file: &File, write: F) -> Result<(), Error>
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    let len = file.metadata()?.len();
+    let result = write();
+    if result.is_err() {
+        if let Err(e) = file.set_len(len) {
+            tracing::error!(error = %e, "cannot truncate the torn line of the log");
+        }
+    }
+    result
+}
+
+/// Writes the snapshot to a temporary file and renames it over the log. Until
+/// the rename succeeds the log is left as it is.
+fn compact(path: &Path, inner: &mut Inner) -> Result<(), Error> {
+    let snapshot = snapshot(&inner.indexes);
+    let tmp_path = path.with_extension("tmp");
+    let write_tmp_file = || -> Result<File, Error> {
+        let mut tmp_file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        for entry in snapshot.iter() {
+            let mut line = serde_json::to_vec(entry)?;
+            line.push(b'\n');
+            tmp_file.write_all(&line)?;
+        }
+        tmp_file.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(tmp_file)
+    };
+    let tmp_file = match write_tmp_file() {
+        Ok(tmp_file) => tmp_file,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    };
+    // the temporary file is the log now, positioned at its end
+    inner.file = tmp_file;
+    inner.commands_since_compaction = 0;
+    inner.commands_since_fsync = 0;
+    let revision = inner.indexes.find_current_revision();
+    inner.indexes.compact(revision);
+    // the rename is durable once the directory is fsynced
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[async_trait]
+impl Store for FileStore {
+    async fn archive_item(
+        &self,
+        item_id: String,
+        archived_at: String,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .handle(Command::ArchiveItem {
+                archived_at,
+                item_id,
+            })
+            .await?)
+    }
+
+    async fn changes_since(
+        &self,
+        revision: u64,
+        limit: usize,
+    ) -> Result<Vec<model::Change>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.changes_since(revision, limit))??)
+    }
+
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        if !self.read(|indexes| indexes.contains_check(&check_list_id, &item_id))? {
+            return Ok(self.read(|indexes| {
+                indexes.compare_check_version(&check_list_id, &item_id, expected_version)
+            })??);
+        }
+        let command = Command::SetChecked {
+            check_list_id: check_list_id.clone(),
+            checked: false,
+            checked_at: None,
+            item_id: item_id.clone(),
+            note: None,
+            price: None,
+            quantity: None,
+            shop: None,
+        };
+        Ok(self
+            .append_if(
+                LogEntry::Command(command),
+                move |indexes| {
+                    indexes.compare_check_version(&check_list_id, &item_id, expected_version)
+                },
+                |_| (),
+            )
+            .await?)
+    }
+
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        if !self.read(|indexes| indexes.contains_item(&item_id))? {
+            return Ok(
+                self.read(|indexes| indexes.compare_item_version(&item_id, expected_version))??
+            );
+        }
+        let command = Command::PurgeItem {
+            item_id: item_id.clone(),
+        };
+        Ok(self
+            .append_if(
+                LogEntry::Command(command),
+                move |indexes| indexes.compare_item_version(&item_id, expected_version),
+                |_| (),
+            )
+            .await?)
+    }
+
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, use_case::Error> {
+        let check_list_id = check.check_list_id.clone();
+        let item_id = check.item_id.clone();
+        let stored = self
+            .append_if(
+                LogEntry::Command(set_checked(check)),
+                {
+                    let (check_list_id, item_id) = (check_list_id.clone(), item_id.clone());
+                    move |indexes| {
+                        indexes.compare_check_version(&check_list_id, &item_id, expected_version)
+                    }
+                },
+                {
+                    let (check_list_id, item_id) = (check_list_id.clone(), item_id.clone());
+                    move |indexes| indexes.find_check(&check_list_id, &item_id)
+                },
+            )
+            .await?;
+        Ok(stored.ok_or_else(|| {
+            use_case::Error::NotFound(format!("check {} {}", check_list_id, item_id))
+        })?)
+    }
+
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<u64>,
+        sequence: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .append_if(
+                LogEntry::ClientSequence {
+                    client_id: client_id.clone(),
+                    sequence,
+                },
+                move |indexes| indexes.compare_client_sequence(&client_id, expected),
+                |_| (),
+            )
+            .await?)
+    }
+
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, use_case::Error> {
+        let item_id = item.id.clone();
+        let exists = self.read(|indexes| indexes.contains_item(&item.id))?;
+        let item = ItemData::from(model::Item { version: 0, ..item });
+        let command = if exists {
+            Command::SetItem { item }
+        } else {
+            Command::AddItem { item }
+        };
+        let stored = self
+            .append_if(
+                LogEntry::Command(command),
+                {
+                    let item_id = item_id.clone();
+                    move |indexes| indexes.compare_item_version(&item_id, expected_version)
+                },
+                {
+                    let item_id = item_id.clone();
+                    move |indexes| indexes.find_item(&item_id)
+                },
+            )
+            .await?;
+        Ok(stored.ok_or_else(|| use_case::Error::NotFound(format!("item {}", item_id)))?)
+    }
+
+    async fn delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+    ) -> Result<(), use_case::Error> {
+        self.compare_and_delete_check(check_list_id, item_id, None)
+            .await
+    }
+
+    async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, use_case::Error> {
+        Ok(self.read(Indexes::find_all_check_lists)?)
+    }
+
+    async fn find_all_checks(&self) -> Result<Vec<model::Check>, use_case::Error> {
+        Ok(self.read(Indexes::find_all_checks)?)
+    }
+
+    async fn find_all_items(&self) -> Result<Vec<model::Item>, use_case::Error> {
+        Ok(self.read(Indexes::find_all_items)?)
+    }
+
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_check_lists(after.as_deref(), limit))?)
+    }
+
+    async fn find_checks_by_check_list_id(
+        &self,
+        check_list_id: String,
+    ) -> Result<Vec<model::Check>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_checks_by_check_list_id(&check_list_id))?)
+    }
+
+    async fn find_checks_by_item_id(
+        &self,
+        item_id: String,
+    ) -> Result<Vec<model::Check>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_checks_by_item_id(&item_id))?)
+    }
+
+    async fn find_client_sequence(
+        &self,
+        client_id: String,
+    ) -> Result<Option<u64>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_client_sequence(&client_id))?)
+    }
+
+    async fn find_current_revision(&self) -> Result<u64, use_case::Error> {
+        Ok(self.read(Indexes::find_current_revision)?)
+    }
+
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_items(after.as_deref(), limit))?)
+    }
+
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_user(&user_id))?)
+    }
+
+    async fn flush(&self) -> Result<(), use_case::Error> {
+        Ok(self.sync().await?)
+    }
+
+    async fn purge_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        self.compare_and_purge_item(item_id, None).await
+    }
+
+    async fn restore_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        Ok(self.handle(Command::RestoreItem { item_id }).await?)
+    }
+
+    async fn store_batch(&self, batch: use_case::Batch) -> Result<(), use_case::Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let items = self.read(|indexes| {
+            batch
+                .items
+                .into_iter()
+                .map(|item| {
+                    let exists = indexes.contains_item(&item.id);
+                    let item = ItemData::from(model::Item { version: 0, ..item });
+                    if exists {
+                        Command::SetItem { item }
+                    } else {
+                        Command::AddItem { item }
+                    }
+                })
+                .collect::<Vec<_>>()
+        })?;
+        let check_lists = batch
+            .check_lists
+            .into_iter()
+            .map(|check_list| Command::AddCheckList {
+                check_list: CheckListData::from(model::CheckList {
+                    version: 0,
+                    ..check_list
+                }),
+            });
+        let checks = batch.checks.into_iter().map(set_checked);
+        Ok(self
+            .append(LogEntry::Batch {
+                batch: items.into_iter().chain(check_lists).chain(checks).collect(),
+            })
+            .await?)
+    }
+
+    async fn store_check(&self, check: model::Check) -> Result<(), use_case::Error> {
+        self.compare_and_store_check(check, None).await?;
+        Ok(())
+    }
+
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), use_case::Error> {
+        Ok(self
+            .handle(Command::AddCheckList {
+                check_list: CheckListData::from(model::CheckList {
+                    version: 0,
+                    ..check_list
+                }),
+            })
+            .await?)
+    }
+
+    async fn store_client_sequence(
+        &self,
+        client_id: String,
+        sequence: u64,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .append(LogEntry::ClientSequence {
+                client_id,
+                sequence: Some(sequence),
+            })
+            .await?)
+    }
+
+    async fn store_item(&self, item: model::Item) -> Result<(), use_case::Error> {
+        self.compare_and_store_item(item, None).await?;
+        Ok(())
+    }
+
+    async fn store_user(&self, user: model::User) -> Result<(), use_case::Error> {
+        Ok(self
+            .append(LogEntry::User {
+                user: UserData::from(user),
+            })
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::store_conformance;
+
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("kireta-{}.jsonl", uuid::Uuid::new_v4()))
+    }
+
+    fn line_count(path: &Path) -> anyhow::Result<usize> {
+        Ok(std::fs::read_to_string(path)?.lines().count())
+    }
+
+    #[tokio::test]
+    async fn test_conformance() -> anyhow::Result<()> {
+        store_conformance::run(|| async {
+            Ok(FileStore::open(temp_path(), FileStoreOptions::default())?)
+        })
+        .await
+    }
+
+    #[test]
+    fn test_log_entry_format() -> anyhow::Result<()> {
+        for (entry, json) in [
+            (
+                LogEntry::Command(Command::SetChecked {
+                    check_list_id: "1".to_owned(),
+                    checked: true,
+                    checked_at: None,
+                    item_id: "2".to_owned(),
+                    note: None,
+                    price: None,
+                    quantity: None,
+                    shop: None,
+                }),
+                r#"{"type":"setChecked","payload":{"checkListId":"1","checked":true,"itemId":"2"}}"#,
+            ),
+            (
+                LogEntry::ClientSequence {
+                    client_id: "android".to_owned(),
+                    sequence: Some(3),
+                },
+                r#"{"clientId":"android","sequence":3}"#,
+            ),
+            (
+                LogEntry::Batch {
+                    batch: vec![Command::RestoreItem {
+                        item_id: "2".to_owned(),
+                    }],
+                },
+                r#"{"batch":[{"type":"restoreItem","payload":{"itemId":"2"}}]}"#,
+            ),
+            (LogEntry::Revision { revision: 4 }, r#"{"revision":4}"#),
+            (
+                LogEntry::User {
+                    user: UserData {
+                        id: "user1".to_owned(),
+                        password_hash: "hash1".to_owned(),
+                    },
+                },
+                r#"{"user":{"id":"user1","passwordHash":"hash1"}}"#,
+            ),
+        ] {
+            assert_eq!(serde_json::to_string(&entry)?, json);
+            assert_eq!(serde_json::from_str::<LogEntry>(json)?, entry);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fsync_policy_from_str() -> anyhow::Result<()> {
+        assert_eq!(FsyncPolicy::from_str("always")?, FsyncPolicy::Always);
+        assert_eq!(FsyncPolicy::from_str("never")?, FsyncPolicy::Never);
+        assert_eq!(FsyncPolicy::from_str("10")?, FsyncPolicy::Every(10));
+        assert!(FsyncPolicy::from_str("0").is_err());
+        assert!(FsyncPolicy::from_str("sometimes").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay() -> anyhow::Result<()> {
+        let path = temp_path();
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        store
+            .store_check_list(model::CheckList {
+                id: "1".to_owned(),
+                date: "2020-01-02".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        store
+            .store_item(model::Item {
+                id: "2".to_owned(),
+                name: "item2".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        let check = model::Check {
+            check_list_id: "1".to_owned(),
+            item_id: "2".to_owned(),
+            price: Some(model::Price {
+                amount: 298,
+                currency: "JPY".to_owned(),
+            }),
+            quantity: Some(1.5),
+            ..Default::default()
+        };
+        store.store_check(check.clone()).await?;
+        // rejected commands are not logged
+        assert!(store
+            .store_check_list(model::CheckList {
+                id: "3".to_owned(),
+                date: "2020-01-02".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .is_err());
+        assert_eq!(line_count(&path)?, 3);
+        drop(store);
+
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        assert_eq!(
+            store.find_all_check_lists().await?,
+            vec![model::CheckList {
+                id: "1".to_owned(),
+                date: "2020-01-02".to_owned(),
+                version: 1,
+            }]
+        );
+        assert_eq!(
+            store.find_checks_by_item_id("2".to_owned()).await?,
+            vec![check.with_version(3)]
+        );
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_torn_append() -> anyhow::Result<()> {
+        let path = temp_path();
+        let item = |id: &str| model::Item {
+            id: id.to_owned(),
+            name: format!("item{}", id),
+            ..Default::default()
+        };
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        store.store_item(item("1")).await?;
+        let complete = std::fs::read_to_string(&path)?;
+        {
+            // e.g. the disk fills up in the middle of the line
+            let inner = store.inner.lock().unwrap();
+            let result = write_or_truncate(&inner.file, || {
+                (&inner.file).write_all(br#"{"type":"addItem","payload":{"id":"#)?;
+                Err(Error::from(std::io::Error::other(
+                    "no space left on device",
+                )))
+            });
+            assert!(result.is_err());
+        }
+        assert_eq!(std::fs::read_to_string(&path)?, complete);
+        store.store_item(item("2")).await?;
+        drop(store);
+
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        assert_eq!(
+            store.find_all_items().await?,
+            vec![item("1").with_version(1), item("2").with_version(2)]
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_incomplete_last_line() -> anyhow::Result<()> {
+        let path = temp_path();
+        let item = |id: &str| model::Item {
+            id: id.to_owned(),
+            name: format!("item{}", id),
+            ..Default::default()
+        };
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        store.store_item(item("1")).await?;
+        drop(store);
+        // a crash in the middle of writing the next line
+        let complete = std::fs::read_to_string(&path)?;
+        OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(br#"{"type":"addItem","payload":{"id":"#)?;
+
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        assert_eq!(std::fs::read_to_string(&path)?, complete);
+        assert_eq!(
+            store.find_all_items().await?,
+            vec![item("1").with_version(1)]
+        );
+        store.store_item(item("2")).await?;
+        drop(store);
+
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        assert_eq!(
+            store.find_all_items().await?,
+            vec![item("1").with_version(1), item("2").with_version(2)]
+        );
+
+        // a complete line that does not parse is not a crash
+        std::fs::write(&path, format!("{}{{}}\n", complete))?;
+        assert!(matches!(
+            FileStore::open(&path, FileStoreOptions::default()),
+            Err(Error::Replay(2, _))
+        ));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush() -> anyhow::Result<()> {
+        let path = temp_path();
+        let store = FileStore::open(
+            &path,
+            FileStoreOptions {
+                fsync_policy: FsyncPolicy::Never,
+                ..Default::default()
+            },
+        )?;
+        store
+            .store_item(model::Item {
+                id: "1".to_owned(),
+                name: "item1".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(store.inner.lock().unwrap().commands_since_fsync, 1);
+        store.flush().await?;
+        assert_eq!(store.inner.lock().unwrap().commands_since_fsync, 0);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compaction() -> anyhow::Result<()> {
+        let path = temp_path();
+        let store = FileStore::open(
+            &path,
+            FileStoreOptions {
+                compaction_threshold: Some(5),
+                fsync_policy: FsyncPolicy::Never,
+            },
+        )?;
+        for i in 0..4 {
+            store
+                .store_item(model::Item {
+                    id: "1".to_owned(),
+                    name: format!("item{}", i),
+                    ..Default::default()
+                })
+                .await?;
+        }
+        assert_eq!(line_count(&path)?, 4);
+        store
+            .store_item(model::Item {
+                id: "1".to_owned(),
+                name: "item4".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        // the snapshot and the revision marker
+        assert_eq!(line_count(&path)?, 2);
+        assert_eq!(store.find_current_revision().await?, 5);
+        assert!(store.changes_since(4, 10).await.is_err());
+        store
+            .store_item(model::Item {
+                id: "2".to_owned(),
+                name: "item5".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(line_count(&path)?, 3);
+        drop(store);
+
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        // the snapshot keeps the versions
+        assert_eq!(
+            store.find_all_items().await?,
+            vec![
+                model::Item {
+                    id: "1".to_owned(),
+                    name: "item4".to_owned(),
+                    version: 5,
+                    ..Default::default()
+                },
+                model::Item {
+                    id: "2".to_owned(),
+                    name: "item5".to_owned(),
+                    version: 6,
+                    ..Default::default()
+                }
+            ]
+        );
+
+        assert_eq!(store.find_current_revision().await?, 6);
+        assert_eq!(store.changes_since(5, 10).await?.len(), 1);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_client_sequences_and_users() -> anyhow::Result<()> {
+        let path = temp_path();
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        let user = model::User {
+            id: "user1".to_owned(),
+            password_hash: "hash1".to_owned(),
+        };
+        store.store_client_sequence("a".to_owned(), 2).await?;
+        store.store_user(user.clone()).await?;
+        store.compact().await?;
+        store.store_client_sequence("b".to_owned(), 1).await?;
+        drop(store);
+
+        let store = FileStore::open(&path, FileStoreOptions::default())?;
+        assert_eq!(store.find_client_sequence("a".to_owned()).await?, Some(2));
+        assert_eq!(store.find_client_sequence("b".to_owned()).await?, Some(1));
+        assert_eq!(store.find_client_sequence("c".to_owned()).await?, None);
+        assert_eq!(store.find_user("user1".to_owned()).await?, Some(user));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
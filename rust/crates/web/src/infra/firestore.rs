@@ -117,6 +117,7 @@ mod tests {
         client
             .run_transaction(|transaction| {
                 let p = document_name.clone();
+                let input = input.clone();
                 Box::pin(async move {
                     transaction.create(&p, input)?;
                     Ok(())
@@ -126,7 +127,7 @@ mod tests {
         assert!(client.get::<V>(&document_name).await.is_ok());
 
         let result = client
-            .run_transaction(|transaction| {
+            .run_transaction::<_, ()>(|transaction| {
                 let p = document_name.clone();
                 Box::pin(async move {
                     let got = transaction.get::<V>(&p).await?;
@@ -1,22 +1,185 @@
-use std::{future::Future, pin::Pin};
+use std::{future::Future, pin::Pin, time::Duration};
 
 use firestore_path::{CollectionName, CollectionPath, DatabaseName, DocumentName};
 use google_api_proto::google::firestore::v1::{
-    firestore_client::FirestoreClient, get_document_request::ConsistencySelector,
-    precondition::ConditionType, value::ValueType, write::Operation, BeginTransactionRequest,
-    BeginTransactionResponse, CommitRequest, CommitResponse, CreateDocumentRequest,
-    DeleteDocumentRequest, GetDocumentRequest, ListDocumentsRequest, ListDocumentsResponse,
-    MapValue, Precondition, RollbackRequest, UpdateDocumentRequest, Write,
+    firestore_client::FirestoreClient,
+    get_document_request::ConsistencySelector,
+    precondition::ConditionType,
+    run_query_request::{self, QueryType},
+    structured_query::{
+        composite_filter, field_filter, filter::FilterType, CollectionSelector, CompositeFilter,
+        Direction, FieldFilter, FieldReference, Filter, Order,
+    },
+    transaction_options,
+    value::ValueType,
+    write::Operation,
+    BeginTransactionRequest, BeginTransactionResponse, CommitRequest, CommitResponse,
+    CreateDocumentRequest, DeleteDocumentRequest, GetDocumentRequest, ListDocumentsRequest,
+    ListDocumentsResponse, MapValue, Precondition, RollbackRequest, RunQueryRequest,
+    StructuredQuery, TransactionOptions, UpdateDocumentRequest, Write,
 };
 use google_authz::{Credentials, GoogleAuthz};
+use rand::Rng as _;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_firestore_value::to_value;
 use tonic::transport::Channel;
 
-use crate::{infra::firestore::document, use_case};
+use crate::{
+    infra::{firestore::document, metrics::Metrics},
+    use_case,
+};
 
 use super::{document::Document, timestamp::Timestamp};
 
+/// Counts an RPC in `Metrics` by its status code.
+trait CountRpc {
+    fn count_rpc(self, rpc: &str) -> Self;
+}
+
+impl<T> CountRpc for Result<T, tonic::Status> {
+    fn count_rpc(self, rpc: &str) -> Self {
+        let code = match &self {
+            Ok(_) => tonic::Code::Ok,
+            Err(status) => status.code(),
+        };
+        Metrics::global().count_firestore_rpc(rpc, code);
+        self
+    }
+}
+
+/// A query on the documents of one collection, run by `Client::query` or
+/// `Transaction::query`.
+#[derive(Clone, Debug)]
+pub struct Query {
+    collection_name: CollectionName,
+    filters: Vec<Filter>,
+    order_by: Vec<Order>,
+    limit: Option<i32>,
+}
+
+impl Query {
+    pub fn new(collection_name: CollectionName) -> Self {
+        Self {
+            collection_name,
+            filters: vec![],
+            order_by: vec![],
+            limit: None,
+        }
+    }
+
+    /// Matches the documents whose `field` is greater than `value`.
+    pub fn where_greater_than<T>(self, field: &str, value: T) -> Result<Self, Error>
+    where
+        T: Serialize,
+    {
+        self.with_filter(field, field_filter::Operator::GreaterThan, value)
+    }
+
+    /// Matches the documents whose `field` is equal to `value`.
+    pub fn where_equal_to<T>(self, field: &str, value: T) -> Result<Self, Error>
+    where
+        T: Serialize,
+    {
+        self.with_filter(field, field_filter::Operator::Equal, value)
+    }
+
+    /// Orders the documents by `field`, ascending.
+    pub fn order_by(mut self, field: &str) -> Self {
+        self.order_by.push(Order {
+            field: Some(FieldReference {
+                field_path: field.to_owned(),
+            }),
+            direction: Direction::Ascending as i32,
+        });
+        self
+    }
+
+    /// Returns up to `limit` documents.
+    pub fn limit(self, limit: usize) -> Self {
+        Self {
+            limit: Some(i32::try_from(limit).unwrap_or(i32::MAX)),
+            ..self
+        }
+    }
+
+    fn with_filter<T>(
+        mut self,
+        field: &str,
+        op: field_filter::Operator,
+        value: T,
+    ) -> Result<Self, Error>
+    where
+        T: Serialize,
+    {
+        self.filters.push(Filter {
+            filter_type: Some(FilterType::FieldFilter(FieldFilter {
+                field: Some(FieldReference {
+                    field_path: field.to_owned(),
+                }),
+                op: op as i32,
+                value: Some(to_value(&value)?),
+            })),
+        });
+        Ok(self)
+    }
+
+    fn into_request(
+        self,
+        consistency_selector: Option<run_query_request::ConsistencySelector>,
+    ) -> RunQueryRequest {
+        let r#where = match self.filters.len() {
+            0 => None,
+            1 => self.filters.into_iter().next(),
+            _ => Some(Filter {
+                filter_type: Some(FilterType::CompositeFilter(CompositeFilter {
+                    op: composite_filter::Operator::And as i32,
+                    filters: self.filters,
+                })),
+            }),
+        };
+        RunQueryRequest {
+            parent: self
+                .collection_name
+                .clone()
+                .parent()
+                .map(|parent| parent.to_string())
+                .unwrap_or_else(|| self.collection_name.database_name().to_string()),
+            query_type: Some(QueryType::StructuredQuery(StructuredQuery {
+                from: vec![CollectionSelector {
+                    collection_id: self.collection_name.collection_id().to_string(),
+                    all_descendants: false,
+                }],
+                r#where,
+                order_by: self.order_by,
+                limit: self.limit,
+                ..Default::default()
+            })),
+            consistency_selector,
+        }
+    }
+}
+
+async fn run_query<U>(
+    client: &mut FirestoreClient<GoogleAuthz<Channel>>,
+    request: RunQueryRequest,
+) -> Result<Vec<Document<U>>, Error>
+where
+    U: DeserializeOwned,
+{
+    let response = client.run_query(request).await.count_rpc("RunQuery")?;
+    let mut stream = response.into_inner();
+    let mut documents = vec![];
+    while let Some(response) = stream.message().await? {
+        if let Some(document) = response.document {
+            documents.push(Document::new(document).map_err(Error::Deserialize)?);
+        }
+    }
+    Ok(documents)
+}
+
+/// The attempts of `Client::run_transaction`, including the first.
+const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+
 pub struct Transaction {
     client: Client,
     transaction: prost::bytes::Bytes,
@@ -71,6 +234,7 @@ impl Transaction {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "GetDocument", document = %document_name), err(level = "warn", Display))]
     pub async fn get<U>(&mut self, document_name: &DocumentName) -> Result<Document<U>, Error>
     where
         U: DeserializeOwned,
@@ -85,9 +249,59 @@ impl Transaction {
                     self.transaction.clone(),
                 )),
             })
-            .await?;
+            .await
+            .count_rpc("GetDocument")?;
         Document::new(response.into_inner()).map_err(Error::Deserialize)
     }
+
+    /// Reads the documents that match the query, so that the commit fails if
+    /// a document is added to or removed from the result.
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "RunQuery", collection = %query.collection_name), err(level = "warn", Display))]
+    pub async fn query<U>(&mut self, query: Query) -> Result<Vec<Document<U>>, Error>
+    where
+        U: DeserializeOwned,
+    {
+        let request = query.into_request(Some(
+            run_query_request::ConsistencySelector::Transaction(self.transaction.clone()),
+        ));
+        run_query(&mut self.client.client, request).await
+    }
+
+    pub fn update<T>(
+        &mut self,
+        document_name: &DocumentName,
+        fields: T,
+        current_update_time: Timestamp,
+    ) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        self.writes.push(Write {
+            operation: Some(Operation::Update(
+                google_api_proto::google::firestore::v1::Document {
+                    name: document_name.to_string(),
+                    fields: {
+                        let ser = to_value(&fields)?;
+                        if let Some(ValueType::MapValue(MapValue { fields })) = ser.value_type {
+                            fields
+                        } else {
+                            return Err(Error::ValueType);
+                        }
+                    },
+                    create_time: None,
+                    update_time: None,
+                },
+            )),
+            update_mask: None,
+            update_transforms: vec![],
+            current_document: Some(Precondition {
+                condition_type: Some(ConditionType::UpdateTime(prost_types::Timestamp::from(
+                    current_update_time,
+                ))),
+            }),
+        });
+        Ok(())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -101,26 +315,114 @@ pub enum TransactionError {
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("credentials {0}")]
-    Credentials(#[from] google_authz::CredentialsError),
+    Credentials(Box<google_authz::CredentialsError>),
     #[error("deserialize {0}")]
     Deserialize(#[from] document::Error),
+    #[error("invalid uri {0}")]
+    InvalidUri(#[from] axum::http::uri::InvalidUri),
     #[error("path {0}")]
     Path(#[from] firestore_path::Error),
     #[error("serialize {0}")]
     Serialize(#[from] serde_firestore_value::Error),
     #[error("status {0}")]
-    Status(#[from] tonic::Status),
+    Status(Box<tonic::Status>),
     #[error("transaction {0}")]
-    Transaction(#[from] TransactionError),
+    Transaction(Box<TransactionError>),
     #[error("transport {0}")]
     Transport(#[from] tonic::transport::Error),
     #[error("value_type")]
     ValueType,
 }
 
+impl From<google_authz::CredentialsError> for Error {
+    fn from(value: google_authz::CredentialsError) -> Self {
+        Self::Credentials(Box::new(value))
+    }
+}
+
+impl From<tonic::Status> for Error {
+    fn from(value: tonic::Status) -> Self {
+        Self::Status(Box::new(value))
+    }
+}
+
+impl From<TransactionError> for Error {
+    fn from(value: TransactionError) -> Self {
+        Self::Transaction(Box::new(value))
+    }
+}
+
+pub fn is_not_found(e: &Error) -> bool {
+    matches!(e, Error::Status(status) if status.code() == tonic::Code::NotFound)
+}
+
+/// Whether the transaction was aborted by contention, by its commit or by a
+/// read in the callback.
+fn is_aborted(e: &Error) -> bool {
+    let is_aborted_status = |e: &(dyn std::error::Error + 'static)| {
+        matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::Status(status)) if status.code() == tonic::Code::Aborted
+        )
+    };
+    match e {
+        Error::Status(status) => status.code() == tonic::Code::Aborted,
+        Error::Transaction(transaction_error) => match transaction_error.as_ref() {
+            TransactionError::Callback(e) | TransactionError::Rollback(_, e) => {
+                std::iter::successors(
+                    Some(e.as_ref() as &(dyn std::error::Error + 'static)),
+                    |e| e.source(),
+                )
+                .any(is_aborted_status)
+            }
+        },
+        _ => false,
+    }
+}
+
+/// Backs off exponentially from 100 ms, with jitter so that the writers that
+/// aborted each other do not retry together.
+fn transaction_retry_delay(attempt: u32) -> Duration {
+    let base = Duration::from_millis(100) * 2u32.pow(attempt - 1);
+    base.mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+}
+
 impl From<Error> for use_case::Error {
     fn from(value: Error) -> Self {
-        use_case::Error::Unknown(value.to_string())
+        match value {
+            Error::Credentials(_) => use_case::Error::Unauthorized(value.to_string()),
+            Error::Path(_) => use_case::Error::InvalidInput(value.to_string()),
+            Error::Status(ref status) => match status.code() {
+                tonic::Code::AlreadyExists => use_case::Error::AlreadyExists(value.to_string()),
+                tonic::Code::InvalidArgument | tonic::Code::OutOfRange => {
+                    use_case::Error::InvalidInput(value.to_string())
+                }
+                tonic::Code::NotFound => use_case::Error::NotFound(value.to_string()),
+                tonic::Code::PermissionDenied | tonic::Code::Unauthenticated => {
+                    use_case::Error::Unauthorized(value.to_string())
+                }
+                // a transaction aborted by contention succeeds when retried
+                tonic::Code::Aborted
+                | tonic::Code::DeadlineExceeded
+                | tonic::Code::ResourceExhausted
+                | tonic::Code::Unavailable => use_case::Error::Unavailable(value.to_string()),
+                _ => use_case::Error::Unknown(value.to_string()),
+            },
+            Error::Transaction(transaction_error) => match *transaction_error {
+                TransactionError::Callback(e) => match e.downcast::<Error>() {
+                    Ok(e) => use_case::Error::from(*e),
+                    Err(e) => use_case::Error::Unknown(e.to_string()),
+                },
+                TransactionError::Rollback(_, _) => {
+                    use_case::Error::Unknown(transaction_error.to_string())
+                }
+            },
+            Error::Transport(_) => use_case::Error::Unavailable(value.to_string()),
+            Error::Deserialize(_)
+            | Error::InvalidUri(_)
+            | Error::Serialize(_)
+            | Error::ValueType => use_case::Error::Unknown(value.to_string()),
+        }
     }
 }
 
@@ -133,9 +435,12 @@ pub struct Client {
 impl Client {
     // TODO: run_query
 
-    pub async fn new(database_name: DatabaseName, endpoint: &'static str) -> Result<Self, Error> {
+    pub async fn new<S>(database_name: DatabaseName, endpoint: S) -> Result<Self, Error>
+    where
+        S: Into<String>,
+    {
         let credentials = Credentials::builder().no_credentials().build().await?;
-        let channel = Channel::from_static(endpoint).connect().await?;
+        let channel = Channel::from_shared(endpoint.into())?.connect().await?;
         let channel = GoogleAuthz::builder(channel)
             .credentials(credentials)
             .build()
@@ -147,6 +452,13 @@ impl Client {
         })
     }
 
+    #[tracing::instrument(
+        level = "debug",
+        name = "firestore",
+        skip_all,
+        fields(rpc = "BeginTransaction"),
+        err(level = "warn", Display)
+    )]
     pub async fn begin_transaction(&mut self) -> Result<Transaction, Error> {
         let response = self
             .client
@@ -154,7 +466,8 @@ impl Client {
                 database: self.database_name.to_string(),
                 options: None,
             })
-            .await?;
+            .await
+            .count_rpc("BeginTransaction")?;
         let BeginTransactionResponse { transaction } = response.into_inner();
         Ok(Transaction {
             client: self.clone(),
@@ -170,6 +483,7 @@ impl Client {
         Ok(self.database_name.clone().collection(collection_path)?)
     }
 
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "CreateDocument", document = %document_name), err(level = "warn", Display))]
     pub async fn create<T, U>(
         &mut self,
         document_name: &DocumentName,
@@ -205,10 +519,12 @@ impl Client {
                 }),
                 mask: None,
             })
-            .await?;
+            .await
+            .count_rpc("CreateDocument")?;
         Document::new(response.into_inner()).map_err(Error::Deserialize)
     }
 
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "DeleteDocument", document = %document_name), err(level = "warn", Display))]
     pub async fn delete(
         &mut self,
         document_name: &DocumentName,
@@ -223,10 +539,12 @@ impl Client {
                     ))),
                 }),
             })
-            .await?;
+            .await
+            .count_rpc("DeleteDocument")?;
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "GetDocument", document = %document_name), err(level = "warn", Display))]
     pub async fn get<U>(&mut self, document_name: &DocumentName) -> Result<Document<U>, Error>
     where
         U: DeserializeOwned,
@@ -238,15 +556,29 @@ impl Client {
                 mask: None,
                 consistency_selector: None,
             })
-            .await?;
+            .await
+            .count_rpc("GetDocument")?;
         Document::new(response.into_inner()).map_err(Error::Deserialize)
     }
 
-    // TODO: support some params
     pub async fn list<U>(
         &mut self,
         collection_name: &CollectionName,
     ) -> Result<(Vec<Document<U>>, Option<String>), Error>
+    where
+        U: DeserializeOwned,
+    {
+        self.list_page(collection_name, 100, None).await
+    }
+
+    // TODO: support some params
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "ListDocuments", collection = %collection_name), err(level = "warn", Display))]
+    pub async fn list_page<U>(
+        &mut self,
+        collection_name: &CollectionName,
+        page_size: i32,
+        page_token: Option<String>,
+    ) -> Result<(Vec<Document<U>>, Option<String>), Error>
     where
         U: DeserializeOwned,
     {
@@ -259,10 +591,12 @@ impl Client {
                     .map(|parent| parent.to_string())
                     .unwrap_or_else(|| collection_name.database_name().to_string()),
                 collection_id: collection_name.collection_id().to_string(),
-                page_size: 100,
+                page_size,
+                page_token: page_token.unwrap_or_default(),
                 ..Default::default()
             })
-            .await?;
+            .await
+            .count_rpc("ListDocuments")?;
         let ListDocumentsResponse {
             documents,
             next_page_token,
@@ -280,13 +614,68 @@ impl Client {
             })
     }
 
-    pub async fn run_transaction<F>(&mut self, callback: F) -> Result<(), Error>
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "RunQuery", collection = %query.collection_name), err(level = "warn", Display))]
+    pub async fn query<U>(&mut self, query: Query) -> Result<Vec<Document<U>>, Error>
+    where
+        U: DeserializeOwned,
+    {
+        run_query(&mut self.client, query.into_request(None)).await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "firestore",
+        skip_all,
+        fields(rpc = "Transaction"),
+        err(level = "warn", Display)
+    )]
+    /// Runs `callback` in a transaction and commits its writes. An attempt
+    /// aborted by contention is retried, up to `MAX_TRANSACTION_ATTEMPTS` in
+    /// total, so `callback` may run more than once.
+    pub async fn run_transaction<F, T>(&mut self, mut callback: F) -> Result<T, Error>
+    where
+        F: FnMut(
+            &mut Transaction,
+        ) -> Pin<
+            Box<
+                dyn Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>
+                    + Send
+                    + '_,
+            >,
+        >,
+    {
+        let mut retry_transaction = None;
+        let mut attempt = 1;
+        loop {
+            match self
+                .try_transaction(&mut callback, retry_transaction.take())
+                .await
+            {
+                Ok(value) => return Ok(value),
+                Err((e, transaction)) if attempt < MAX_TRANSACTION_ATTEMPTS && is_aborted(&e) => {
+                    tracing::debug!(attempt, error = %e, "retry the aborted transaction");
+                    tokio::time::sleep(transaction_retry_delay(attempt)).await;
+                    retry_transaction = Some(transaction);
+                    attempt += 1;
+                }
+                Err((e, _)) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns the error with the id of the transaction, which the retry
+    /// passes to Firestore to keep its place in the lock queue.
+    async fn try_transaction<F, T>(
+        &mut self,
+        callback: &mut F,
+        retry_transaction: Option<prost::bytes::Bytes>,
+    ) -> Result<T, (Error, prost::bytes::Bytes)>
     where
-        F: FnOnce(
+        F: FnMut(
             &mut Transaction,
         ) -> Pin<
             Box<
-                dyn Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>
+                dyn Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>
                     + Send
                     + '_,
             >,
@@ -296,9 +685,15 @@ impl Client {
             .client
             .begin_transaction(BeginTransactionRequest {
                 database: self.database_name.to_string(),
-                options: None,
+                options: retry_transaction.map(|retry_transaction| TransactionOptions {
+                    mode: Some(transaction_options::Mode::ReadWrite(
+                        transaction_options::ReadWrite { retry_transaction },
+                    )),
+                }),
             })
-            .await?;
+            .await
+            .count_rpc("BeginTransaction")
+            .map_err(|status| (Error::from(status), prost::bytes::Bytes::new()))?;
         let BeginTransactionResponse { transaction } = response.into_inner();
         let mut transaction = Transaction {
             client: self.clone(),
@@ -306,38 +701,45 @@ impl Client {
             writes: vec![],
         };
         match callback(&mut transaction).await {
-            Ok(()) => {
+            Ok(value) => {
                 let response = self
                     .client
                     .commit(CommitRequest {
                         database: self.database_name.to_string(),
                         writes: transaction.writes,
-                        transaction: transaction.transaction,
+                        transaction: transaction.transaction.clone(),
                     })
-                    .await?;
+                    .await
+                    .count_rpc("Commit")
+                    .map_err(|status| (Error::from(status), transaction.transaction))?;
                 // TODO: commit_time and write_results
                 let CommitResponse { .. } = response.into_inner();
-                Ok(())
+                Ok(value)
             }
             Err(callback_err) => {
                 match self
                     .client
                     .rollback(RollbackRequest {
                         database: self.database_name.to_string(),
-                        transaction: transaction.transaction,
+                        transaction: transaction.transaction.clone(),
                     })
                     .await
+                    .count_rpc("Rollback")
                 {
-                    Ok(_) => Err(Error::Transaction(TransactionError::Callback(callback_err))),
-                    Err(rollback_err) => Err(Error::Transaction(TransactionError::Rollback(
-                        rollback_err,
-                        callback_err,
-                    ))),
+                    Ok(_) => Err((
+                        Error::from(TransactionError::Callback(callback_err)),
+                        transaction.transaction,
+                    )),
+                    Err(rollback_err) => Err((
+                        Error::from(TransactionError::Rollback(rollback_err, callback_err)),
+                        transaction.transaction,
+                    )),
                 }
             }
         }
     }
 
+    #[tracing::instrument(level = "debug", name = "firestore", skip_all, fields(rpc = "UpdateDocument", document = %document_name), err(level = "warn", Display))]
     pub async fn update<T, U>(
         &mut self,
         document_name: &DocumentName,
@@ -372,13 +774,16 @@ impl Client {
                     ))),
                 }),
             })
-            .await?;
+            .await
+            .count_rpc("UpdateDocument")?;
         Document::new(response.into_inner()).map_err(Error::Deserialize)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr as _;
+
     use super::*;
 
     #[test]
@@ -386,4 +791,100 @@ mod tests {
         fn assert_send_sync<T: Send + Sync>() {}
         assert_send_sync::<Error>()
     }
+
+    #[test]
+    fn test_query() -> anyhow::Result<()> {
+        let collection_name = CollectionName::from_str(
+            "projects/demo-project1/databases/(default)/documents/checks",
+        )?;
+        let request = Query::new(collection_name)
+            .where_equal_to("item_id", "1")?
+            .where_greater_than("check_list_id", "2")?
+            .order_by("check_list_id")
+            .limit(10)
+            .into_request(None);
+        assert_eq!(
+            request.parent,
+            "projects/demo-project1/databases/(default)/documents"
+        );
+        let Some(QueryType::StructuredQuery(query)) = request.query_type else {
+            anyhow::bail!("not a structured query");
+        };
+        assert_eq!(query.from[0].collection_id, "checks");
+        let Some(FilterType::CompositeFilter(filter)) =
+            query.r#where.and_then(|filter| filter.filter_type)
+        else {
+            anyhow::bail!("not a composite filter");
+        };
+        assert_eq!(filter.op, composite_filter::Operator::And as i32);
+        assert_eq!(filter.filters.len(), 2);
+        assert_eq!(query.order_by.len(), 1);
+        assert_eq!(query.limit, Some(10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_aborted() {
+        let status = |code: tonic::Code| Error::from(tonic::Status::new(code, ""));
+        assert!(is_aborted(&status(tonic::Code::Aborted)));
+        assert!(!is_aborted(&status(tonic::Code::FailedPrecondition)));
+        // a read in the callback aborted, wrapped by the store's error
+        #[derive(Debug, thiserror::Error)]
+        #[error("store")]
+        struct StoreError(#[source] Error);
+        let callback = |code: tonic::Code| {
+            Error::from(TransactionError::Callback(Box::new(StoreError(status(
+                code,
+            )))))
+        };
+        assert!(is_aborted(&callback(tonic::Code::Aborted)));
+        assert!(!is_aborted(&callback(tonic::Code::NotFound)));
+    }
+
+    #[test]
+    fn test_impl_from_error_for_use_case_error() {
+        let f =
+            |code: tonic::Code| use_case::Error::from(Error::from(tonic::Status::new(code, "")));
+        assert!(matches!(
+            f(tonic::Code::AlreadyExists),
+            use_case::Error::AlreadyExists(_)
+        ));
+        assert!(matches!(
+            f(tonic::Code::Aborted),
+            use_case::Error::Unavailable(_)
+        ));
+        // e.g. a missing index, which fails again when retried
+        assert!(matches!(
+            f(tonic::Code::FailedPrecondition),
+            use_case::Error::Unknown(_)
+        ));
+        assert!(matches!(
+            f(tonic::Code::InvalidArgument),
+            use_case::Error::InvalidInput(_)
+        ));
+        assert!(matches!(
+            f(tonic::Code::NotFound),
+            use_case::Error::NotFound(_)
+        ));
+        assert!(matches!(
+            f(tonic::Code::Unauthenticated),
+            use_case::Error::Unauthorized(_)
+        ));
+        assert!(matches!(
+            f(tonic::Code::Unavailable),
+            use_case::Error::Unavailable(_)
+        ));
+        assert!(matches!(
+            f(tonic::Code::Internal),
+            use_case::Error::Unknown(_)
+        ));
+
+        let callback_error = Error::from(TransactionError::Callback(Box::new(Error::from(
+            tonic::Status::not_found(""),
+        ))));
+        assert!(matches!(
+            use_case::Error::from(callback_error),
+            use_case::Error::NotFound(_)
+        ));
+    }
 }
@@ -0,0 +1,203 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use axum::async_trait;
+use firestore_path::DocumentName;
+
+use crate::use_case::{
+    self,
+    idempotency::{IdempotencyKeyStore, IdempotencyRecord},
+};
+
+use super::firestore::client::{is_not_found, Client};
+
+/// idempotency_keys/{key}. A TTL policy on `expires_at` can delete the
+/// expired claims; until then they are overwritten by the next claim.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct IdempotencyKeyDocumentData {
+    #[serde(with = "serde_firestore_value::with::timestamp")]
+    pub expires_at: prost_types::Timestamp,
+    pub fingerprint: String,
+    #[serde(default)]
+    pub response: Option<String>,
+}
+
+impl IdempotencyKeyDocumentData {
+    fn has_expired(&self, now: SystemTime) -> bool {
+        SystemTime::try_from(self.expires_at.clone()).map_or(true, |expires_at| expires_at <= now)
+    }
+}
+
+/// Shares the claims between instances, in the database of `FirestoreStore`.
+#[derive(Clone, Debug)]
+pub struct FirestoreIdempotencyKeyStore {
+    client: Arc<tokio::sync::Mutex<Client>>,
+}
+
+impl FirestoreIdempotencyKeyStore {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(tokio::sync::Mutex::new(client)),
+        }
+    }
+}
+
+fn document_name(client: &Client, key: &str) -> Result<DocumentName, use_case::Error> {
+    client
+        .collection("idempotency_keys")?
+        .doc(key)
+        .map_err(|e| use_case::Error::InvalidInput(format!("idempotency key {}", e)))
+}
+
+#[async_trait]
+impl IdempotencyKeyStore for FirestoreIdempotencyKeyStore {
+    async fn claim(
+        &self,
+        key: String,
+        fingerprint: String,
+        lease: Duration,
+    ) -> Result<Option<IdempotencyRecord>, use_case::Error> {
+        let mut client = self.client.lock().await;
+        let document_name = document_name(&client, &key)?;
+        Ok(client
+            .run_transaction(|transaction| {
+                let document_name = document_name.clone();
+                let fingerprint = fingerprint.clone();
+                Box::pin(async move {
+                    let now = SystemTime::now();
+                    let data = IdempotencyKeyDocumentData {
+                        expires_at: prost_types::Timestamp::from(now + lease),
+                        fingerprint,
+                        response: None,
+                    };
+                    match transaction
+                        .get::<IdempotencyKeyDocumentData>(&document_name)
+                        .await
+                    {
+                        Ok(document) => {
+                            let update_time = document.update_time();
+                            let current = document.data();
+                            if !current.has_expired(now) {
+                                return Ok(Some(IdempotencyRecord {
+                                    fingerprint: current.fingerprint,
+                                    response: current.response,
+                                }));
+                            }
+                            transaction.update(&document_name, data, update_time)?;
+                        }
+                        Err(e) if is_not_found(&e) => transaction.create(&document_name, data)?,
+                        Err(e) => Err(e)?,
+                    }
+                    Ok(None)
+                })
+            })
+            .await?)
+    }
+
+    async fn complete(
+        &self,
+        key: String,
+        response: String,
+        ttl: Duration,
+    ) -> Result<(), use_case::Error> {
+        let mut client = self.client.lock().await;
+        let document_name = document_name(&client, &key)?;
+        let document = match client
+            .get::<IdempotencyKeyDocumentData>(&document_name)
+            .await
+        {
+            Ok(document) => document,
+            Err(e) if is_not_found(&e) => return Ok(()),
+            Err(e) => Err(e)?,
+        };
+        let update_time = document.update_time();
+        client
+            .update::<_, IdempotencyKeyDocumentData>(
+                &document_name,
+                IdempotencyKeyDocumentData {
+                    expires_at: prost_types::Timestamp::from(SystemTime::now() + ttl),
+                    response: Some(response),
+                    ..document.data()
+                },
+                update_time,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: String) -> Result<(), use_case::Error> {
+        let mut client = self.client.lock().await;
+        let document_name = document_name(&client, &key)?;
+        match client
+            .get::<IdempotencyKeyDocumentData>(&document_name)
+            .await
+        {
+            Ok(document) => {
+                client
+                    .delete(&document_name, document.update_time())
+                    .await?
+            }
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => Err(e)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use firestore_path::{DatabaseId, DatabaseName, ProjectId};
+
+    use crate::test_utils::idempotency_key_store_conformance;
+
+    use super::*;
+
+    #[test]
+    fn test_document_data() -> anyhow::Result<()> {
+        let data = IdempotencyKeyDocumentData {
+            expires_at: prost_types::Timestamp {
+                seconds: 1_577_934_245,
+                nanos: 0,
+            },
+            fingerprint: "request1".to_owned(),
+            response: None,
+        };
+        let value = serde_firestore_value::to_value(&data)?;
+        let deserialized = serde_firestore_value::from_value::<IdempotencyKeyDocumentData>(&value)?;
+        assert_eq!(deserialized.expires_at, data.expires_at);
+        assert_eq!(deserialized.response, None);
+        assert!(deserialized.has_expired(SystemTime::now()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conformance() -> anyhow::Result<()> {
+        idempotency_key_store_conformance::run(|| async {
+            let mut client = Client::new(
+                DatabaseName::new(
+                    ProjectId::from_str("demo-project1")?,
+                    DatabaseId::from_str("(default)")?,
+                ),
+                "http://firebase:8080",
+            )
+            .await?;
+            // reset
+            let collection_name = client.collection("idempotency_keys")?;
+            loop {
+                let (documents, _) = client.list::<serde_json::Value>(&collection_name).await?;
+                if documents.is_empty() {
+                    break;
+                }
+                for doc in documents {
+                    client.delete(doc.name(), doc.update_time()).await?;
+                }
+            }
+            Ok(FirestoreIdempotencyKeyStore::new(client))
+        })
+        .await
+    }
+}
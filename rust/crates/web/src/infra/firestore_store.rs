@@ -1,69 +1,574 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 
 use axum::async_trait;
 
+use firestore_path::{CollectionName, DocumentName};
+
 use crate::{
     model::{self, Check, CheckList, Item},
-    use_case::{self, Store},
+    use_case::{
+        self,
+        data::{ChangeKindData, EntityData, PriceData},
+        Store,
+    },
 };
 
-use super::firestore::client::Client;
+use super::firestore::{
+    client::{self, is_not_found, Client, Query, Transaction},
+    timestamp::Timestamp,
+};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct CheckListDocumentData {
     pub date: String,
     pub id: String,
+    #[serde(default)]
+    pub version: Option<i64>,
 }
 
 impl From<CheckListDocumentData> for model::CheckList {
-    fn from(CheckListDocumentData { date, id }: CheckListDocumentData) -> Self {
-        Self { date, id }
+    fn from(CheckListDocumentData { date, id, version }: CheckListDocumentData) -> Self {
+        Self {
+            date,
+            id,
+            version: version_from_i64(version),
+        }
+    }
+}
+
+impl From<model::CheckList> for CheckListDocumentData {
+    fn from(model::CheckList { date, id, version }: model::CheckList) -> Self {
+        Self {
+            date,
+            id,
+            version: version_to_i64(version),
+        }
     }
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct CheckDocumentData {
     pub check_list_id: String,
+    #[serde(default)]
+    pub checked_at: Option<String>,
     pub item_id: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub price: Option<PriceData>,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    #[serde(default)]
+    pub shop: Option<String>,
+    #[serde(default)]
+    pub version: Option<i64>,
 }
 
 impl From<CheckDocumentData> for model::Check {
     fn from(
         CheckDocumentData {
             check_list_id,
+            checked_at,
             item_id,
+            note,
+            price,
+            quantity,
+            shop,
+            version,
         }: CheckDocumentData,
     ) -> Self {
         Self {
             check_list_id,
             item_id,
+            checked_at,
+            note,
+            price: price.map(model::Price::from),
+            quantity,
+            shop,
+            version: version_from_i64(version),
         }
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+impl From<model::Check> for CheckDocumentData {
+    fn from(
+        model::Check {
+            check_list_id,
+            item_id,
+            checked_at,
+            note,
+            price,
+            quantity,
+            shop,
+            version,
+        }: model::Check,
+    ) -> Self {
+        Self {
+            check_list_id,
+            checked_at,
+            item_id,
+            note,
+            price: price.map(PriceData::from),
+            quantity,
+            shop,
+            version: version_to_i64(version),
+        }
+    }
+}
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
 pub struct ItemDocumentData {
+    #[serde(default)]
+    pub archived_at: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub emoji: Option<String>,
     pub id: String,
     pub name: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub sort_order: Option<i64>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub version: Option<i64>,
 }
 
 impl From<ItemDocumentData> for model::Item {
-    fn from(ItemDocumentData { id, name }: ItemDocumentData) -> Self {
-        Self { id, name }
+    fn from(
+        ItemDocumentData {
+            archived_at,
+            category,
+            emoji,
+            id,
+            name,
+            note,
+            sort_order,
+            unit,
+            version,
+        }: ItemDocumentData,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            archived_at,
+            category,
+            emoji,
+            note,
+            sort_order,
+            unit,
+            version: version_from_i64(version),
+        }
+    }
+}
+
+impl From<model::Item> for ItemDocumentData {
+    fn from(
+        model::Item {
+            id,
+            name,
+            archived_at,
+            category,
+            emoji,
+            note,
+            sort_order,
+            unit,
+            version,
+        }: model::Item,
+    ) -> Self {
+        Self {
+            archived_at,
+            category,
+            emoji,
+            id,
+            name,
+            note,
+            sort_order,
+            unit,
+            version: version_to_i64(version),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct CheckListDateDocumentData {
+    pub check_list_id: String,
+}
+
+/// changes/{revision:020}
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ChangeDocumentData {
+    pub entity: EntityData,
+    pub kind: ChangeKindData,
+    pub revision: i64,
+}
+
+impl TryFrom<ChangeDocumentData> for model::Change {
+    type Error = Error;
+
+    fn try_from(
+        ChangeDocumentData {
+            entity,
+            kind,
+            revision,
+        }: ChangeDocumentData,
+    ) -> Result<Self, Self::Error> {
+        let revision = u64::try_from(revision).map_err(|_| Error::RevisionOutOfRange(revision))?;
+        Ok(Self {
+            entity: model::Entity::from(entity).with_version(revision),
+            kind: model::ChangeKind::from(kind),
+            revision,
+        })
+    }
+}
+
+/// revisions/current holds the latest revision
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct RevisionDocumentData {
+    pub revision: i64,
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ClientSequenceDocumentData {
+    pub sequence: i64,
+}
+
+/// users/{id}
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct UserDocumentData {
+    pub id: String,
+    pub password_hash: String,
+}
+
+impl From<UserDocumentData> for model::User {
+    fn from(UserDocumentData { id, password_hash }: UserDocumentData) -> Self {
+        Self { id, password_hash }
+    }
+}
+
+impl From<model::User> for UserDocumentData {
+    fn from(model::User { id, password_hash }: model::User) -> Self {
+        Self { id, password_hash }
     }
 }
 
 #[derive(Debug, thiserror::Error)]
-enum Error {
+pub enum Error {
+    #[error("check list not found {0}")]
+    CheckListNotFound(String),
     #[error("client {0}")]
     Client(#[from] super::firestore::client::Error),
+    #[error("client sequence conflict {0}")]
+    ClientSequenceConflict(String),
     #[error("invalid path {0}")]
     InvalidPath(#[from] firestore_path::Error),
+    #[error("item not found {0}")]
+    ItemNotFound(String),
+    #[error("revision out of range {0}")]
+    RevisionOutOfRange(i64),
+    #[error("sequence out of range {0}")]
+    SequenceOutOfRange(u64),
+    #[error("version conflict expected {expected}")]
+    VersionConflict {
+        current: Option<Box<model::Entity>>,
+        expected: u64,
+    },
+}
+
+/// Joins the ids with `:`, which `model::validate_id` forbids. The ids are
+/// escaped all the same, so that ids stored without validation cannot make
+/// two checks share a document.
+fn check_document_id(check_list_id: &str, item_id: &str) -> String {
+    let escape = |id: &str| id.replace('%', "%25").replace(':', "%3A");
+    format!("{}:{}", escape(check_list_id), escape(item_id))
+}
+
+/// The version of a document written before versions were recorded. It is
+/// not 0, which means that the entity does not exist, so a create-only write
+/// fails against the document. Unlike SQLite, the versions are not backfilled
+/// from the changes, which would take a query per document.
+const LEGACY_VERSION: u64 = 1;
+
+/// Versions are revisions, which are stored as `i64` like
+/// [`RevisionDocumentData::revision`]. Documents written before versions were
+/// recorded have none, see [`LEGACY_VERSION`].
+fn version_from_i64(version: Option<i64>) -> u64 {
+    version
+        .and_then(|version| u64::try_from(version).ok())
+        .unwrap_or(LEGACY_VERSION)
+}
+
+fn version_to_i64(version: u64) -> Option<i64> {
+    Some(i64::try_from(version).unwrap_or(i64::MAX))
+}
+
+/// The changes of a transaction. revisions/current is read before the
+/// writes, so that each write can store the revision of its change as the
+/// entity's version.
+struct ChangeLog {
+    changes: Vec<ChangeDocumentData>,
+    current: Option<Timestamp>,
+    revision: i64,
+}
+
+impl ChangeLog {
+    async fn read(
+        transaction: &mut Transaction,
+        revision_document_name: &DocumentName,
+    ) -> Result<Self, Error> {
+        let (current, revision) = match transaction
+            .get::<RevisionDocumentData>(revision_document_name)
+            .await
+        {
+            Ok(document) => (Some(document.update_time()), document.data().revision),
+            Err(e) if is_not_found(&e) => (None, 0),
+            Err(e) => Err(e)?,
+        };
+        Ok(Self {
+            changes: vec![],
+            current,
+            revision,
+        })
+    }
+
+    /// Increments the revision and records the change. Returns the revision,
+    /// which is the version of the entity after the change.
+    fn record(&mut self, kind: model::ChangeKind, entity: model::Entity) -> u64 {
+        self.revision += 1;
+        self.changes.push(ChangeDocumentData {
+            // Firestore has no u64, the version is restored from the revision
+            entity: EntityData::from(entity.with_version(0)),
+            kind: ChangeKindData::from(kind),
+            revision: self.revision,
+        });
+        version_from_i64(Some(self.revision))
+    }
+
+    /// Writes the recorded changes and the latest revision in the transaction.
+    fn write(
+        self,
+        transaction: &mut Transaction,
+        revision_document_name: &DocumentName,
+        changes_collection_name: &CollectionName,
+    ) -> Result<(), Error> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        for change in self.changes {
+            transaction.create(
+                &changes_collection_name
+                    .clone()
+                    .doc(format!("{:020}", change.revision))?,
+                change,
+            )?;
+        }
+        let data = RevisionDocumentData {
+            revision: self.revision,
+        };
+        match self.current {
+            Some(update_time) => transaction.update(revision_document_name, data, update_time)?,
+            None => transaction.create(revision_document_name, data)?,
+        }
+        Ok(())
+    }
+}
+
+fn compare_version(
+    current: Option<model::Entity>,
+    expected_version: Option<u64>,
+) -> Result<(), Error> {
+    let current_version = current.as_ref().map_or(0, model::Entity::version);
+    match expected_version {
+        Some(expected) if expected != current_version => Err(Error::VersionConflict {
+            current: current.map(Box::new),
+            expected,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Fails with `not_found()` unless the document exists.
+async fn ensure_exists<U>(
+    transaction: &mut Transaction,
+    document_name: &DocumentName,
+    not_found: impl FnOnce() -> Error,
+) -> Result<(), Error>
+where
+    U: serde::de::DeserializeOwned,
+{
+    match transaction.get::<U>(document_name).await {
+        Ok(_) => Ok(()),
+        Err(e) if is_not_found(&e) => Err(not_found()),
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+/// Upserts the check in the transaction and returns it as stored. Storing
+/// the same details again keeps the version.
+async fn write_check(
+    transaction: &mut Transaction,
+    changes: &mut ChangeLog,
+    document_name: &DocumentName,
+    check: model::Check,
+    expected_version: Option<u64>,
+) -> Result<model::Check, Error> {
+    let current = match transaction.get::<CheckDocumentData>(document_name).await {
+        Ok(document) => Some((document.update_time(), Check::from(document.data()))),
+        Err(e) if is_not_found(&e) => None,
+        Err(e) => Err(e)?,
+    };
+    compare_version(
+        current
+            .as_ref()
+            .map(|(_, check)| model::Entity::Check(check.clone())),
+        expected_version,
+    )?;
+    let kind = match &current {
+        Some((_, previous))
+            if &Check {
+                version: previous.version,
+                ..check.clone()
+            } == previous =>
+        {
+            return Ok(previous.clone())
+        }
+        Some(_) => model::ChangeKind::Updated,
+        None => model::ChangeKind::Created,
+    };
+    let version = changes.record(kind, model::Entity::Check(check.clone()));
+    let check = Check { version, ..check };
+    let data = CheckDocumentData::from(check.clone());
+    match current {
+        Some((update_time, _)) => transaction.update(document_name, data, update_time)?,
+        None => transaction.create(document_name, data)?,
+    }
+    Ok(check)
+}
+
+/// Creates the check list and its date in the transaction. The date document
+/// keeps the date unique.
+fn write_check_list(
+    transaction: &mut Transaction,
+    changes: &mut ChangeLog,
+    check_list_document_name: &DocumentName,
+    check_list_date_document_name: &DocumentName,
+    check_list: model::CheckList,
+) -> Result<(), Error> {
+    transaction.create(
+        check_list_date_document_name,
+        CheckListDateDocumentData {
+            check_list_id: check_list.id.clone(),
+        },
+    )?;
+    let version = changes.record(
+        model::ChangeKind::Created,
+        model::Entity::CheckList(check_list.clone()),
+    );
+    transaction.create(
+        check_list_document_name,
+        CheckListDocumentData {
+            version: version_to_i64(version),
+            ..CheckListDocumentData::from(check_list)
+        },
+    )?;
+    Ok(())
+}
+
+/// Upserts the item in the transaction and returns it as stored.
+async fn write_item(
+    transaction: &mut Transaction,
+    changes: &mut ChangeLog,
+    document_name: &DocumentName,
+    item: model::Item,
+    expected_version: Option<u64>,
+) -> Result<model::Item, Error> {
+    let current = match transaction.get::<ItemDocumentData>(document_name).await {
+        Ok(document) => Some((document.update_time(), Item::from(document.data()))),
+        Err(e) if is_not_found(&e) => None,
+        Err(e) => Err(e)?,
+    };
+    compare_version(
+        current
+            .as_ref()
+            .map(|(_, item)| model::Entity::Item(item.clone())),
+        expected_version,
+    )?;
+    let kind = if current.is_some() {
+        model::ChangeKind::Updated
+    } else {
+        model::ChangeKind::Created
+    };
+    let version = changes.record(kind, model::Entity::Item(item.clone()));
+    let item = Item { version, ..item };
+    let data = ItemDocumentData::from(item.clone());
+    match current {
+        Some((update_time, _)) => transaction.update(document_name, data, update_time)?,
+        None => transaction.create(document_name, data)?,
+    }
+    Ok(item)
+}
+
+async fn list_all<U>(client: &mut Client, collection_path: &str) -> Result<Vec<U>, Error>
+where
+    U: serde::de::DeserializeOwned,
+{
+    let collection_name = client.collection(collection_path)?;
+    let mut data = vec![];
+    let mut page_token = None;
+    loop {
+        let (documents, next_page_token) = client
+            .list_page::<U>(&collection_name, 100, page_token)
+            .await?;
+        data.extend(documents.into_iter().map(|doc| doc.data()));
+        match next_page_token {
+            Some(next_page_token) => page_token = Some(next_page_token),
+            None => break,
+        }
+    }
+    Ok(data)
+}
+
+/// A page of the collection ordered by id, starting after the id `after`.
+fn page_query(
+    collection_name: CollectionName,
+    after: Option<String>,
+    limit: usize,
+) -> Result<Query, Error> {
+    let query = Query::new(collection_name);
+    let query = match after {
+        Some(after) => query.where_greater_than("id", after)?,
+        None => query,
+    };
+    Ok(query.order_by("id").limit(limit))
 }
 
 impl From<Error> for use_case::Error {
     fn from(e: Error) -> Self {
-        Self::Unknown(e.to_string())
+        match e {
+            Error::CheckListNotFound(id) => Self::NotFound(format!("check list {}", id)),
+            Error::Client(client::Error::Transaction(transaction_error)) => {
+                match *transaction_error {
+                    client::TransactionError::Callback(e) => match e.downcast::<Error>() {
+                        Ok(e) => Self::from(*e),
+                        Err(e) => {
+                            Self::from(client::Error::from(client::TransactionError::Callback(e)))
+                        }
+                    },
+                    e => Self::from(client::Error::from(e)),
+                }
+            }
+            Error::Client(e) => Self::from(e),
+            Error::ClientSequenceConflict(id) => Self::Conflict(format!("client sequence {}", id)),
+            Error::InvalidPath(_) => Self::InvalidInput(e.to_string()),
+            Error::ItemNotFound(id) => Self::NotFound(format!("item {}", id)),
+            Error::RevisionOutOfRange(_) => Self::Unknown(e.to_string()),
+            Error::SequenceOutOfRange(_) => Self::InvalidInput(e.to_string()),
+            Error::VersionConflict { current, expected } => {
+                Self::VersionConflict { current, expected }
+            }
+        }
     }
 }
 
@@ -73,76 +578,728 @@ pub struct FirestoreStore {
 }
 
 impl FirestoreStore {
-    async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, Error> {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(tokio::sync::Mutex::new(client)),
+        }
+    }
+
+    async fn archive_item(&self, item_id: String, archived_at: String) -> Result<(), Error> {
+        self.set_archived_at(item_id, Some(archived_at)).await
+    }
+
+    async fn changes_since(
+        &self,
+        revision: u64,
+        limit: usize,
+    ) -> Result<Vec<model::Change>, Error> {
         let mut client = self.client.lock().await;
-        let collection_name = client.collection("check_lists")?;
-        // TODO: pagination
-        Ok(client
-            .list::<CheckListDocumentData>(&collection_name)
+        let collection_name = client.collection("changes")?;
+        let query = Query::new(collection_name)
+            .where_greater_than("revision", i64::try_from(revision).unwrap_or(i64::MAX))?
+            .order_by("revision")
+            .limit(limit);
+        client
+            .query::<ChangeDocumentData>(query)
             .await?
-            .0
             .into_iter()
-            .map(|doc| doc.data())
-            .map(CheckList::from)
-            .collect())
+            .map(|document| model::Change::try_from(document.data()))
+            .collect()
     }
 
-    async fn find_all_checks(&self) -> Result<Vec<model::Check>, Error> {
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error> {
         let mut client = self.client.lock().await;
-        let collection_name = client.collection("checks")?;
+        let document_name = client
+            .collection("checks")?
+            .doc(check_document_id(&check_list_id, &item_id))?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
+        client
+            .run_transaction(|transaction| {
+                let document_name = document_name.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                Box::pin(async move {
+                    let current = match transaction.get::<CheckDocumentData>(&document_name).await {
+                        Ok(document) => {
+                            Some((document.update_time(), Check::from(document.data())))
+                        }
+                        Err(e) if is_not_found(&e) => None,
+                        Err(e) => Err(e)?,
+                    };
+                    compare_version(
+                        current
+                            .as_ref()
+                            .map(|(_, check)| model::Entity::Check(check.clone())),
+                        expected_version,
+                    )?;
+                    let Some((update_time, check)) = current else {
+                        return Ok(());
+                    };
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    transaction.delete(&document_name, update_time)?;
+                    changes.record(model::ChangeKind::Deleted, model::Entity::Check(check));
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error> {
+        let mut client = self.client.lock().await;
+        let item_document_name = client.collection("items")?.doc(item_id.as_str())?;
+        let checks_query =
+            Query::new(client.collection("checks")?).where_equal_to("item_id", &item_id)?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
+        client
+            .run_transaction(|transaction| {
+                let item_document_name = item_document_name.clone();
+                let checks_query = checks_query.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                Box::pin(async move {
+                    let current = match transaction
+                        .get::<ItemDocumentData>(&item_document_name)
+                        .await
+                    {
+                        Ok(document) => Some((document.update_time(), Item::from(document.data()))),
+                        Err(e) if is_not_found(&e) => None,
+                        Err(e) => Err(e)?,
+                    };
+                    compare_version(
+                        current
+                            .as_ref()
+                            .map(|(_, item)| model::Entity::Item(item.clone())),
+                        expected_version,
+                    )?;
+                    let Some((update_time, item)) = current else {
+                        return Ok(());
+                    };
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    for check_document in
+                        transaction.query::<CheckDocumentData>(checks_query).await?
+                    {
+                        transaction.delete(check_document.name(), check_document.update_time())?;
+                        changes.record(
+                            model::ChangeKind::Deleted,
+                            model::Entity::Check(Check::from(check_document.data())),
+                        );
+                    }
+                    transaction.delete(&item_document_name, update_time)?;
+                    changes.record(model::ChangeKind::Deleted, model::Entity::Item(item));
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, Error> {
+        let mut client = self.client.lock().await;
+        let check_list_document_name = client
+            .collection("check_lists")?
+            .doc(check.check_list_id.as_str())?;
+        let item_document_name = client.collection("items")?.doc(check.item_id.as_str())?;
+        let check_document_name = client
+            .collection("checks")?
+            .doc(check_document_id(&check.check_list_id, &check.item_id))?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
+        Ok(client
+            .run_transaction(|transaction| {
+                let check_list_document_name = check_list_document_name.clone();
+                let item_document_name = item_document_name.clone();
+                let check_document_name = check_document_name.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                let check = check.clone();
+                Box::pin(async move {
+                    ensure_exists::<CheckListDocumentData>(
+                        transaction,
+                        &check_list_document_name,
+                        || Error::CheckListNotFound(check.check_list_id.clone()),
+                    )
+                    .await?;
+                    ensure_exists::<ItemDocumentData>(transaction, &item_document_name, || {
+                        Error::ItemNotFound(check.item_id.clone())
+                    })
+                    .await?;
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    let check = write_check(
+                        transaction,
+                        &mut changes,
+                        &check_document_name,
+                        check,
+                        expected_version,
+                    )
+                    .await?;
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(check)
+                })
+            })
+            .await?)
+    }
+
+    /// Writes the sequence, or deletes it if `sequence` is `None`, if
+    /// `expected` is `None` or holds the stored sequence.
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<Option<u64>>,
+        sequence: Option<u64>,
+    ) -> Result<(), Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client
+            .collection("client_sequences")?
+            .doc(client_id.as_str())?;
+        let data = sequence
+            .map(|sequence| {
+                i64::try_from(sequence)
+                    .map(|sequence| ClientSequenceDocumentData { sequence })
+                    .map_err(|_| Error::SequenceOutOfRange(sequence))
+            })
+            .transpose()?;
+        client
+            .run_transaction(|transaction| {
+                let document_name = document_name.clone();
+                let client_id = client_id.clone();
+                let data = data.clone();
+                Box::pin(async move {
+                    let current = match transaction
+                        .get::<ClientSequenceDocumentData>(&document_name)
+                        .await
+                    {
+                        Ok(document) => Some((document.update_time(), document.data().sequence)),
+                        Err(e) if is_not_found(&e) => None,
+                        Err(e) => Err(e)?,
+                    };
+                    if expected.is_some_and(|expected| {
+                        current.and_then(|(_, sequence)| u64::try_from(sequence).ok()) != expected
+                    }) {
+                        Err(Error::ClientSequenceConflict(client_id))?;
+                    }
+                    match (current, data) {
+                        (Some((update_time, _)), Some(data)) => {
+                            transaction.update(&document_name, data, update_time)?
+                        }
+                        (Some((update_time, _)), None) => {
+                            transaction.delete(&document_name, update_time)?
+                        }
+                        (None, Some(data)) => transaction.create(&document_name, data)?,
+                        (None, None) => {}
+                    }
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client.collection("items")?.doc(item.id.as_str())?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
         Ok(client
-            .list::<CheckDocumentData>(&collection_name)
+            .run_transaction(|transaction| {
+                let document_name = document_name.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                let item = item.clone();
+                Box::pin(async move {
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    let item = write_item(
+                        transaction,
+                        &mut changes,
+                        &document_name,
+                        item,
+                        expected_version,
+                    )
+                    .await?;
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(item)
+                })
+            })
+            .await?)
+    }
+
+    async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, Error> {
+        let mut client = self.client.lock().await;
+        Ok(
+            list_all::<CheckListDocumentData>(&mut client, "check_lists")
+                .await?
+                .into_iter()
+                .map(CheckList::from)
+                .collect(),
+        )
+    }
+
+    async fn find_all_checks(&self) -> Result<Vec<model::Check>, Error> {
+        let mut client = self.client.lock().await;
+        let mut checks = list_all::<CheckDocumentData>(&mut client, "checks")
             .await?
-            .0
             .into_iter()
-            .map(|doc| doc.data())
             .map(Check::from)
-            .collect())
+            .collect::<Vec<Check>>();
+        // document ids do not sort in the same order as (check_list_id, item_id)
+        checks.sort_by(|a, b| (&a.check_list_id, &a.item_id).cmp(&(&b.check_list_id, &b.item_id)));
+        Ok(checks)
     }
 
     async fn find_all_items(&self) -> Result<Vec<model::Item>, Error> {
         let mut client = self.client.lock().await;
-        let collection_name = client.collection("items")?;
-        // TODO: pagination
-        Ok(client
-            .list::<ItemDocumentData>(&collection_name)
+        Ok(list_all::<ItemDocumentData>(&mut client, "items")
             .await?
-            .0
             .into_iter()
-            .map(|doc| doc.data())
             .map(Item::from)
             .collect())
     }
 
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, Error> {
+        let mut client = self.client.lock().await;
+        let query = page_query(client.collection("check_lists")?, after, limit)?;
+        Ok(client
+            .query::<CheckListDocumentData>(query)
+            .await?
+            .into_iter()
+            .map(|document| CheckList::from(document.data()))
+            .collect())
+    }
+
     async fn find_checks_by_check_list_id(
         &self,
         check_list_id: String,
     ) -> Result<Vec<model::Check>, use_case::Error> {
-        // TODO: improve perfomance
         Ok(self
-            .find_all_checks()
-            .await?
-            .into_iter()
-            .filter(|check| check.check_list_id == check_list_id)
-            .collect())
+            .find_checks_where("check_list_id", check_list_id)
+            .await?)
     }
 
     async fn find_checks_by_item_id(
         &self,
         item_id: String,
     ) -> Result<Vec<model::Check>, use_case::Error> {
-        // TODO: improve perfomance
-        Ok(self
-            .find_all_checks()
+        Ok(self.find_checks_where("item_id", item_id).await?)
+    }
+
+    /// Returns the checks whose `field` is `value`, ordered as
+    /// `find_all_checks` orders them.
+    async fn find_checks_where(&self, field: &str, value: String) -> Result<Vec<Check>, Error> {
+        let mut client = self.client.lock().await;
+        let query = Query::new(client.collection("checks")?).where_equal_to(field, value)?;
+        let mut checks = client
+            .query::<CheckDocumentData>(query)
             .await?
             .into_iter()
-            .filter(|check| check.item_id == item_id)
+            .map(|document| Check::from(document.data()))
+            .collect::<Vec<Check>>();
+        checks.sort_by(|a, b| (&a.check_list_id, &a.item_id).cmp(&(&b.check_list_id, &b.item_id)));
+        Ok(checks)
+    }
+
+    async fn find_client_sequence(&self, client_id: String) -> Result<Option<u64>, Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client
+            .collection("client_sequences")?
+            .doc(client_id.as_str())?;
+        match client
+            .get::<ClientSequenceDocumentData>(&document_name)
+            .await
+        {
+            Ok(document) => Ok(u64::try_from(document.data().sequence).ok()),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    async fn find_current_revision(&self) -> Result<u64, Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client.collection("revisions")?.doc("current")?;
+        match client.get::<RevisionDocumentData>(&document_name).await {
+            Ok(document) => {
+                let revision = document.data().revision;
+                u64::try_from(revision).map_err(|_| Error::RevisionOutOfRange(revision))
+            }
+            Err(e) if is_not_found(&e) => Ok(0),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, Error> {
+        let mut client = self.client.lock().await;
+        let query = page_query(client.collection("items")?, after, limit)?;
+        Ok(client
+            .query::<ItemDocumentData>(query)
+            .await?
+            .into_iter()
+            .map(|document| Item::from(document.data()))
             .collect())
     }
+
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client.collection("users")?.doc(user_id.as_str())?;
+        match client.get::<UserDocumentData>(&document_name).await {
+            Ok(document) => Ok(Some(model::User::from(document.data()))),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    async fn restore_item(&self, item_id: String) -> Result<(), Error> {
+        self.set_archived_at(item_id, None).await
+    }
+
+    /// Archives (`Some`) or restores (`None`) the item. Archiving an archived item is a no-op.
+    async fn set_archived_at(
+        &self,
+        item_id: String,
+        archived_at: Option<String>,
+    ) -> Result<(), Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client.collection("items")?.doc(item_id.as_str())?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
+        client
+            .run_transaction(|transaction| {
+                let document_name = document_name.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                let item_id = item_id.clone();
+                let archived_at = archived_at.clone();
+                Box::pin(async move {
+                    let document = match transaction.get::<ItemDocumentData>(&document_name).await {
+                        Ok(document) => document,
+                        Err(e) if is_not_found(&e) => Err(Error::ItemNotFound(item_id))?,
+                        Err(e) => Err(e)?,
+                    };
+                    let update_time = document.update_time();
+                    let item = Item::from(document.data());
+                    if item.archived_at.is_some() == archived_at.is_some() {
+                        return Ok(());
+                    }
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    let item = Item {
+                        archived_at,
+                        ..item
+                    };
+                    let version = changes.record(
+                        model::ChangeKind::Updated,
+                        model::Entity::Item(item.clone()),
+                    );
+                    transaction.update(
+                        &document_name,
+                        ItemDocumentData {
+                            version: version_to_i64(version),
+                            ..ItemDocumentData::from(item)
+                        },
+                        update_time,
+                    )?;
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Writes the batch in one transaction. Each entity takes up to 3 of the
+    /// 500 writes that a transaction allows (its documents and its change).
+    async fn store_batch(&self, batch: use_case::Batch) -> Result<(), Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let mut client = self.client.lock().await;
+        let items = batch
+            .items
+            .into_iter()
+            .map(|item| Ok((client.collection("items")?.doc(item.id.as_str())?, item)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let check_lists = batch
+            .check_lists
+            .into_iter()
+            .map(|check_list| {
+                Ok((
+                    client
+                        .collection("check_lists")?
+                        .doc(check_list.id.as_str())?,
+                    client
+                        .collection("check_list_dates")?
+                        .doc(check_list.date.as_str())?,
+                    check_list,
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let checks = batch
+            .checks
+            .into_iter()
+            .map(|check| {
+                Ok((
+                    client
+                        .collection("check_lists")?
+                        .doc(check.check_list_id.as_str())?,
+                    client.collection("items")?.doc(check.item_id.as_str())?,
+                    client
+                        .collection("checks")?
+                        .doc(check_document_id(&check.check_list_id, &check.item_id))?,
+                    check,
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
+        client
+            .run_transaction(|transaction| {
+                let items = items.clone();
+                let check_lists = check_lists.clone();
+                let checks = checks.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                Box::pin(async move {
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    // written in this transaction, so not readable yet
+                    let mut item_ids = BTreeSet::new();
+                    let mut check_list_ids = BTreeSet::new();
+                    for (document_name, item) in items {
+                        item_ids.insert(item.id.clone());
+                        write_item(transaction, &mut changes, &document_name, item, None).await?;
+                    }
+                    for (check_list_document_name, check_list_date_document_name, check_list) in
+                        check_lists
+                    {
+                        check_list_ids.insert(check_list.id.clone());
+                        write_check_list(
+                            transaction,
+                            &mut changes,
+                            &check_list_document_name,
+                            &check_list_date_document_name,
+                            check_list,
+                        )?;
+                    }
+                    for (
+                        check_list_document_name,
+                        item_document_name,
+                        check_document_name,
+                        check,
+                    ) in checks
+                    {
+                        if !check_list_ids.contains(&check.check_list_id) {
+                            ensure_exists::<CheckListDocumentData>(
+                                transaction,
+                                &check_list_document_name,
+                                || Error::CheckListNotFound(check.check_list_id.clone()),
+                            )
+                            .await?;
+                        }
+                        if !item_ids.contains(&check.item_id) {
+                            ensure_exists::<ItemDocumentData>(
+                                transaction,
+                                &item_document_name,
+                                || Error::ItemNotFound(check.item_id.clone()),
+                            )
+                            .await?;
+                        }
+                        write_check(transaction, &mut changes, &check_document_name, check, None)
+                            .await?;
+                    }
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), Error> {
+        let mut client = self.client.lock().await;
+        let check_list_document_name = client
+            .collection("check_lists")?
+            .doc(check_list.id.as_str())?;
+        let check_list_date_document_name = client
+            .collection("check_list_dates")?
+            .doc(check_list.date.as_str())?;
+        let revision_document_name = client.collection("revisions")?.doc("current")?;
+        let changes_collection_name = client.collection("changes")?;
+        client
+            .run_transaction(|transaction| {
+                let check_list_document_name = check_list_document_name.clone();
+                let check_list_date_document_name = check_list_date_document_name.clone();
+                let check_list = check_list.clone();
+                let revision_document_name = revision_document_name.clone();
+                let changes_collection_name = changes_collection_name.clone();
+                Box::pin(async move {
+                    let mut changes = ChangeLog::read(transaction, &revision_document_name).await?;
+                    write_check_list(
+                        transaction,
+                        &mut changes,
+                        &check_list_document_name,
+                        &check_list_date_document_name,
+                        check_list,
+                    )?;
+                    changes.write(
+                        transaction,
+                        &revision_document_name,
+                        &changes_collection_name,
+                    )?;
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn store_user(&self, user: model::User) -> Result<(), Error> {
+        let mut client = self.client.lock().await;
+        let document_name = client.collection("users")?.doc(user.id.as_str())?;
+        let data = UserDocumentData::from(user);
+        match client.get::<UserDocumentData>(&document_name).await {
+            Ok(document) => {
+                client
+                    .update::<_, UserDocumentData>(&document_name, data, document.update_time())
+                    .await?;
+            }
+            Err(e) if is_not_found(&e) => {
+                client
+                    .create::<_, UserDocumentData>(&document_name, data)
+                    .await?;
+            }
+            Err(e) => Err(e)?,
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl Store for FirestoreStore {
+    async fn archive_item(
+        &self,
+        item_id: String,
+        archived_at: String,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.archive_item(item_id, archived_at).await?)
+    }
+
+    async fn changes_since(
+        &self,
+        revision: u64,
+        limit: usize,
+    ) -> Result<Vec<model::Change>, use_case::Error> {
+        Ok(self.changes_since(revision, limit).await?)
+    }
+
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .compare_and_delete_check(check_list_id, item_id, expected_version)
+            .await?)
+    }
+
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .compare_and_purge_item(item_id, expected_version)
+            .await?)
+    }
+
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, use_case::Error> {
+        Ok(self
+            .compare_and_store_check(check, expected_version)
+            .await?)
+    }
+
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<u64>,
+        sequence: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .compare_and_store_client_sequence(client_id, Some(expected), sequence)
+            .await?)
+    }
+
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, use_case::Error> {
+        Ok(self.compare_and_store_item(item, expected_version).await?)
+    }
+
+    async fn delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .compare_and_delete_check(check_list_id, item_id, None)
+            .await?)
+    }
+
     async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, use_case::Error> {
         Ok(self.find_all_check_lists().await?)
     }
@@ -156,6 +1313,14 @@ impl Store for FirestoreStore {
         Ok(self.find_all_items().await?)
     }
 
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, use_case::Error> {
+        Ok(self.find_check_lists(after, limit).await?)
+    }
+
     async fn find_checks_by_check_list_id(
         &self,
         check_list_id: String,
@@ -169,6 +1334,74 @@ impl Store for FirestoreStore {
     ) -> Result<Vec<model::Check>, use_case::Error> {
         Ok(self.find_checks_by_item_id(item_id).await?)
     }
+
+    async fn find_client_sequence(
+        &self,
+        client_id: String,
+    ) -> Result<Option<u64>, use_case::Error> {
+        Ok(self.find_client_sequence(client_id).await?)
+    }
+
+    async fn find_current_revision(&self) -> Result<u64, use_case::Error> {
+        Ok(self.find_current_revision().await?)
+    }
+
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, use_case::Error> {
+        Ok(self.find_items(after, limit).await?)
+    }
+
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, use_case::Error> {
+        Ok(self.find_user(user_id).await?)
+    }
+
+    /// Every write is committed before it returns.
+    async fn flush(&self) -> Result<(), use_case::Error> {
+        Ok(())
+    }
+
+    async fn purge_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        Ok(self.compare_and_purge_item(item_id, None).await?)
+    }
+
+    async fn restore_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        Ok(self.restore_item(item_id).await?)
+    }
+
+    async fn store_batch(&self, batch: use_case::Batch) -> Result<(), use_case::Error> {
+        Ok(self.store_batch(batch).await?)
+    }
+
+    async fn store_check(&self, check: model::Check) -> Result<(), use_case::Error> {
+        self.compare_and_store_check(check, None).await?;
+        Ok(())
+    }
+
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), use_case::Error> {
+        Ok(self.store_check_list(check_list).await?)
+    }
+
+    async fn store_client_sequence(
+        &self,
+        client_id: String,
+        sequence: u64,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .compare_and_store_client_sequence(client_id, None, Some(sequence))
+            .await?)
+    }
+
+    async fn store_item(&self, item: model::Item) -> Result<(), use_case::Error> {
+        self.compare_and_store_item(item, None).await?;
+        Ok(())
+    }
+
+    async fn store_user(&self, user: model::User) -> Result<(), use_case::Error> {
+        Ok(self.store_user(user).await?)
+    }
 }
 
 #[cfg(test)]
@@ -177,10 +1410,129 @@ mod tests {
 
     use firestore_path::{DatabaseId, DatabaseName, ProjectId};
 
-    use crate::infra::firestore::document::Document;
+    use crate::{infra::firestore::document::Document, test_utils::store_conformance};
 
     use super::*;
 
+    #[test]
+    fn test_check_document_id() {
+        assert_eq!(check_document_id("1", "2"), "1:2");
+        // "_" is valid in ids
+        assert_ne!(check_document_id("a_b", "c"), check_document_id("a", "b_c"));
+        assert_ne!(check_document_id("a:b", "c"), check_document_id("a", "b:c"));
+        assert_ne!(check_document_id("a%3A", "c"), check_document_id("a:", "c"));
+    }
+
+    #[test]
+    fn test_version_from_i64() {
+        assert_eq!(version_from_i64(Some(3)), 3);
+        // a document written before versions were recorded exists
+        assert_eq!(version_from_i64(None), LEGACY_VERSION);
+        assert_ne!(LEGACY_VERSION, 0);
+    }
+
+    /// Connects to the emulator and deletes the documents of the store.
+    async fn reset() -> anyhow::Result<Client> {
+        let mut client = Client::new(
+            DatabaseName::new(
+                ProjectId::from_str("demo-project1")?,
+                DatabaseId::from_str("(default)")?,
+            ),
+            "http://firebase:8080",
+        )
+        .await?;
+        for collection_path in [
+            "changes",
+            "check_list_dates",
+            "check_lists",
+            "checks",
+            "client_sequences",
+            "items",
+            "revisions",
+        ] {
+            let collection_name = client.collection(collection_path)?;
+            loop {
+                let (documents, _) = client.list::<serde_json::Value>(&collection_name).await?;
+                if documents.is_empty() {
+                    break;
+                }
+                for doc in documents {
+                    client.delete(doc.name(), doc.update_time()).await?;
+                }
+            }
+        }
+        Ok(client)
+    }
+
+    #[tokio::test]
+    async fn test_conformance() -> anyhow::Result<()> {
+        store_conformance::run(|| async { Ok(FirestoreStore::new(reset().await?)) }).await
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writers() -> anyhow::Result<()> {
+        // each store has its own client, as two instances of the server would
+        let client = reset().await?;
+        let stores = [
+            FirestoreStore::new(client.clone()),
+            FirestoreStore::new(client),
+        ];
+        let write = |store: FirestoreStore, prefix: &'static str| async move {
+            for i in 0..5 {
+                store
+                    .compare_and_store_item(
+                        model::Item {
+                            id: format!("{}{}", prefix, i),
+                            name: format!("{}{}", prefix, i),
+                            ..Default::default()
+                        },
+                        None,
+                    )
+                    .await?;
+            }
+            anyhow::Ok(())
+        };
+        let [a, b] = stores;
+        let (result_a, result_b) = tokio::join!(write(a.clone(), "a"), write(b, "b"));
+        result_a?;
+        result_b?;
+        // every write takes its own revision
+        let revisions = a
+            .changes_since(0, 100)
+            .await?
+            .into_iter()
+            .map(|change| change.revision)
+            .collect::<BTreeSet<u64>>();
+        assert_eq!(revisions, (1..=10).collect::<BTreeSet<u64>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_document_data() -> anyhow::Result<()> {
+        let data = ChangeDocumentData {
+            entity: EntityData::from(model::Entity::Check(Check {
+                check_list_id: "1".to_owned(),
+                item_id: "2".to_owned(),
+                price: Some(model::Price {
+                    amount: 298,
+                    currency: "JPY".to_owned(),
+                }),
+                quantity: Some(1.5),
+                ..Default::default()
+            })),
+            kind: ChangeKindData::from(model::ChangeKind::Deleted),
+            revision: 3,
+        };
+        let value = serde_firestore_value::to_value(&data)?;
+        assert_eq!(
+            serde_firestore_value::from_value::<ChangeDocumentData>(&value)?,
+            data
+        );
+        // the entity is at the version of its change
+        assert_eq!(model::Change::try_from(data)?.entity.version(), 3);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_find_all_check_lists() -> anyhow::Result<()> {
         let endpoint = "http://firebase:8080";
@@ -198,6 +1550,7 @@ mod tests {
         let input = CheckListDocumentData {
             date: "2020-01-02".to_string(),
             id: "1".to_string(),
+            version: None,
         };
         let created: Document<CheckListDocumentData> = client.create(&doc, input).await?;
 
@@ -209,7 +1562,9 @@ mod tests {
             found,
             vec![CheckList {
                 id: "1".to_string(),
-                date: "2020-01-02".to_string()
+                date: "2020-01-02".to_string(),
+                // written before versions were recorded
+                version: LEGACY_VERSION,
             }]
         );
 
@@ -235,6 +1590,7 @@ mod tests {
         let input = ItemDocumentData {
             id: "1".to_string(),
             name: "name1".to_string(),
+            ..Default::default()
         };
         let created: Document<ItemDocumentData> = client.create(&doc, input).await?;
 
@@ -246,7 +1602,10 @@ mod tests {
             found,
             vec![Item {
                 id: "1".to_string(),
-                name: "name1".to_string()
+                name: "name1".to_string(),
+                // written before versions were recorded
+                version: LEGACY_VERSION,
+                ..Default::default()
             }]
         );
 
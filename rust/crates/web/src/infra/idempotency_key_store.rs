@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+
+use crate::use_case::{
+    idempotency::{IdempotencyKeyStore, IdempotencyRecord},
+    Error,
+};
+
+/// Expired claims are dropped at most once per interval. An expired claim
+/// that is looked up is replaced in the meantime.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Holds the claims in the process, so a retry has to reach the same instance.
+#[derive(Clone, Debug)]
+pub struct InMemoryIdempotencyKeyStore {
+    records: Arc<Mutex<Records>>,
+}
+
+#[derive(Debug)]
+struct Records {
+    by_key: HashMap<String, (IdempotencyRecord, Instant)>,
+    swept_at: Instant,
+}
+
+impl Default for InMemoryIdempotencyKeyStore {
+    fn default() -> Self {
+        Self {
+            records: Arc::new(Mutex::new(Records {
+                by_key: HashMap::new(),
+                swept_at: Instant::now(),
+            })),
+        }
+    }
+}
+
+impl InMemoryIdempotencyKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Records>, Error> {
+        self.records
+            .lock()
+            .map_err(|_| Error::Unknown("idempotency keys lock poisoned".to_owned()))
+    }
+}
+
+#[async_trait]
+impl IdempotencyKeyStore for InMemoryIdempotencyKeyStore {
+    async fn claim(
+        &self,
+        key: String,
+        fingerprint: String,
+        lease: Duration,
+    ) -> Result<Option<IdempotencyRecord>, Error> {
+        let now = Instant::now();
+        let mut records = self.lock()?;
+        if now.duration_since(records.swept_at) >= SWEEP_INTERVAL {
+            records
+                .by_key
+                .retain(|_, (_, expires_at)| *expires_at > now);
+            records.swept_at = now;
+        }
+        match records.by_key.get(&key) {
+            Some((record, expires_at)) if *expires_at > now => Ok(Some(record.clone())),
+            _ => {
+                records.by_key.insert(
+                    key,
+                    (
+                        IdempotencyRecord {
+                            fingerprint,
+                            response: None,
+                        },
+                        now + lease,
+                    ),
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    async fn complete(&self, key: String, response: String, ttl: Duration) -> Result<(), Error> {
+        let mut records = self.lock()?;
+        if let Some((record, expires_at)) = records.by_key.get_mut(&key) {
+            record.response = Some(response);
+            *expires_at = Instant::now() + ttl;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, key: String) -> Result<(), Error> {
+        let mut records = self.lock()?;
+        records.by_key.remove(&key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::idempotency_key_store_conformance;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conformance() -> anyhow::Result<()> {
+        idempotency_key_store_conformance::run(|| async { Ok(InMemoryIdempotencyKeyStore::new()) })
+            .await
+    }
+}
@@ -0,0 +1,277 @@
+//! A `Store` that records a span and the metrics of each call.
+
+use std::{future::Future, sync::Arc, time::Instant};
+
+use axum::async_trait;
+
+use crate::{
+    infra::metrics::Metrics,
+    model,
+    use_case::{Batch, Error, Store},
+};
+
+/// Wraps any `Store`. The span fields are ids and counts, never the entities.
+pub struct InstrumentedStore(pub Arc<dyn Store + Send + Sync>);
+
+async fn observe<T, F>(method: &str, future: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    let started_at = Instant::now();
+    let result = future.await;
+    Metrics::global().observe_store_call(
+        method,
+        started_at.elapsed(),
+        result.as_ref().err().map(Error::code),
+    );
+    result
+}
+
+#[async_trait]
+impl Store for InstrumentedStore {
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "archive_item", item_id = %item_id), err(level = "warn", Display))]
+    async fn archive_item(&self, item_id: String, archived_at: String) -> Result<(), Error> {
+        observe("archive_item", self.0.archive_item(item_id, archived_at)).await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "store",
+        skip_all,
+        fields(method = "changes_since", revision, limit),
+        err(level = "warn", Display)
+    )]
+    async fn changes_since(
+        &self,
+        revision: u64,
+        limit: usize,
+    ) -> Result<Vec<model::Change>, Error> {
+        observe("changes_since", self.0.changes_since(revision, limit)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "compare_and_delete_check", check_list_id = %check_list_id, item_id = %item_id, expected_version = ?expected_version), err(level = "warn", Display))]
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error> {
+        observe(
+            "compare_and_delete_check",
+            self.0
+                .compare_and_delete_check(check_list_id, item_id, expected_version),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "compare_and_purge_item", item_id = %item_id, expected_version = ?expected_version), err(level = "warn", Display))]
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error> {
+        observe(
+            "compare_and_purge_item",
+            self.0.compare_and_purge_item(item_id, expected_version),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "compare_and_store_check", check_list_id = %check.check_list_id, item_id = %check.item_id, expected_version = ?expected_version), err(level = "warn", Display))]
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, Error> {
+        observe(
+            "compare_and_store_check",
+            self.0.compare_and_store_check(check, expected_version),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "compare_and_store_client_sequence", client_id = %client_id, expected = ?expected, sequence = ?sequence), err(level = "warn", Display))]
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<u64>,
+        sequence: Option<u64>,
+    ) -> Result<(), Error> {
+        observe(
+            "compare_and_store_client_sequence",
+            self.0
+                .compare_and_store_client_sequence(client_id, expected, sequence),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "compare_and_store_item", item_id = %item.id, expected_version = ?expected_version), err(level = "warn", Display))]
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, Error> {
+        observe(
+            "compare_and_store_item",
+            self.0.compare_and_store_item(item, expected_version),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "delete_check", check_list_id = %check_list_id, item_id = %item_id), err(level = "warn", Display))]
+    async fn delete_check(&self, check_list_id: String, item_id: String) -> Result<(), Error> {
+        observe("delete_check", self.0.delete_check(check_list_id, item_id)).await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "store",
+        skip_all,
+        fields(method = "find_all_check_lists"),
+        err(level = "warn", Display)
+    )]
+    async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, Error> {
+        observe("find_all_check_lists", self.0.find_all_check_lists()).await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "store",
+        skip_all,
+        fields(method = "find_all_checks"),
+        err(level = "warn", Display)
+    )]
+    async fn find_all_checks(&self) -> Result<Vec<model::Check>, Error> {
+        observe("find_all_checks", self.0.find_all_checks()).await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "store",
+        skip_all,
+        fields(method = "find_all_items"),
+        err(level = "warn", Display)
+    )]
+    async fn find_all_items(&self) -> Result<Vec<model::Item>, Error> {
+        observe("find_all_items", self.0.find_all_items()).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "find_check_lists", after = ?after, limit), err(level = "warn", Display))]
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, Error> {
+        observe("find_check_lists", self.0.find_check_lists(after, limit)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "find_checks_by_check_list_id", check_list_id = %check_list_id), err(level = "warn", Display))]
+    async fn find_checks_by_check_list_id(
+        &self,
+        check_list_id: String,
+    ) -> Result<Vec<model::Check>, Error> {
+        observe(
+            "find_checks_by_check_list_id",
+            self.0.find_checks_by_check_list_id(check_list_id),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "find_checks_by_item_id", item_id = %item_id), err(level = "warn", Display))]
+    async fn find_checks_by_item_id(&self, item_id: String) -> Result<Vec<model::Check>, Error> {
+        observe(
+            "find_checks_by_item_id",
+            self.0.find_checks_by_item_id(item_id),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "find_client_sequence", client_id = %client_id), err(level = "warn", Display))]
+    async fn find_client_sequence(&self, client_id: String) -> Result<Option<u64>, Error> {
+        observe(
+            "find_client_sequence",
+            self.0.find_client_sequence(client_id),
+        )
+        .await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "store",
+        skip_all,
+        fields(method = "find_current_revision"),
+        err(level = "warn", Display)
+    )]
+    async fn find_current_revision(&self) -> Result<u64, Error> {
+        observe("find_current_revision", self.0.find_current_revision()).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "find_items", after = ?after, limit), err(level = "warn", Display))]
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, Error> {
+        observe("find_items", self.0.find_items(after, limit)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "find_user", user_id = %user_id), err(level = "warn", Display))]
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, Error> {
+        observe("find_user", self.0.find_user(user_id)).await
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "store",
+        skip_all,
+        fields(method = "flush"),
+        err(level = "warn", Display)
+    )]
+    async fn flush(&self) -> Result<(), Error> {
+        observe("flush", self.0.flush()).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "purge_item", item_id = %item_id), err(level = "warn", Display))]
+    async fn purge_item(&self, item_id: String) -> Result<(), Error> {
+        observe("purge_item", self.0.purge_item(item_id)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "restore_item", item_id = %item_id), err(level = "warn", Display))]
+    async fn restore_item(&self, item_id: String) -> Result<(), Error> {
+        observe("restore_item", self.0.restore_item(item_id)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "store_batch", batch_len = batch.len()), err(level = "warn", Display))]
+    async fn store_batch(&self, batch: Batch) -> Result<(), Error> {
+        observe("store_batch", self.0.store_batch(batch)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "store_check", check_list_id = %check.check_list_id, item_id = %check.item_id), err(level = "warn", Display))]
+    async fn store_check(&self, check: model::Check) -> Result<(), Error> {
+        observe("store_check", self.0.store_check(check)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "store_check_list", check_list_id = %check_list.id), err(level = "warn", Display))]
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), Error> {
+        observe("store_check_list", self.0.store_check_list(check_list)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "store_client_sequence", client_id = %client_id, sequence), err(level = "warn", Display))]
+    async fn store_client_sequence(&self, client_id: String, sequence: u64) -> Result<(), Error> {
+        observe(
+            "store_client_sequence",
+            self.0.store_client_sequence(client_id, sequence),
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "store_item", item_id = %item.id), err(level = "warn", Display))]
+    async fn store_item(&self, item: model::Item) -> Result<(), Error> {
+        observe("store_item", self.0.store_item(item)).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "store", skip_all, fields(method = "store_user", user_id = %user.id), err(level = "warn", Display))]
+    async fn store_user(&self, user: model::User) -> Result<(), Error> {
+        observe("store_user", self.0.store_user(user)).await
+    }
+}
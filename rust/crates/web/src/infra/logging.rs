@@ -0,0 +1,239 @@
+//! Logs as JSON lines, written by `tracing_subscriber`.
+//!
+//! An event is written with its span and the list of its spans, outermost
+//! first:
+//!
+//! ```text
+//! {"timestamp":"2020-01-02T03:04:05.678901Z","level":"INFO","fields":{"message":"response","status":200,"latency_ms":1.2},"target":"web::handler::trace","span":{"request_id":"...","name":"http_request"},"spans":[{"request_id":"...","name":"http_request"}]}
+//! ```
+//!
+//! A span is written when it closes, with the message `close` and its latency
+//! in `time.busy` and `time.idle`. Fields named like a secret (e.g. `password`)
+//! and bearer tokens in values are redacted.
+
+use std::io::Write;
+
+use serde_json::Value;
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{format::FmtSpan, MakeWriter},
+    layer::SubscriberExt as _,
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Field names that contain one of these are redacted.
+const SECRET_FIELDS: [&str; 6] = [
+    "authorization",
+    "bearer",
+    "cookie",
+    "password",
+    "secret",
+    "token",
+];
+
+/// Writes the events of this crate up to `max_level`. Other crates (e.g. hyper
+/// and tonic) are limited to `INFO`.
+pub fn subscriber<W>(make_writer: W, max_level: Level) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_max_level(max_level)
+        .with_writer(Redact(make_writer))
+        .finish()
+        .with(
+            Targets::new()
+                .with_target(env!("CARGO_CRATE_NAME"), max_level)
+                .with_default(max_level.min(Level::INFO)),
+        )
+}
+
+/// Redacts each line written by the inner writer.
+struct Redact<W>(W);
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for Redact<W> {
+    type Writer = RedactWriter<W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactWriter {
+            inner: self.0.make_writer(),
+            line: vec![],
+        }
+    }
+}
+
+struct RedactWriter<W: Write> {
+    inner: W,
+    /// The bytes after the last newline.
+    line: Vec<u8>,
+}
+
+impl<W: Write> RedactWriter<W> {
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        let line = String::from_utf8_lossy(line);
+        let redacted = match serde_json::from_str::<Value>(&line) {
+            Ok(mut value) => {
+                redact(&mut value);
+                value.to_string()
+            }
+            Err(_) => redact_bearer(&line),
+        };
+        writeln!(self.inner, "{}", redacted)
+    }
+}
+
+impl<W: Write> Write for RedactWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        while let Some(end) = self.line.iter().position(|b| *b == b'\n') {
+            let line = self.line.drain(..=end).collect::<Vec<u8>>();
+            self.write_line(&line[..end])?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for RedactWriter<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            // a log line that cannot be written is dropped
+            let _ = self.write_line(&line);
+        }
+    }
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        Value::Object(object) => {
+            for (name, value) in object.iter_mut() {
+                if is_secret(name) {
+                    *value = Value::from(REDACTED);
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::String(s) => *s = redact_bearer(s),
+        Value::Bool(_) | Value::Null | Value::Number(_) => {}
+    }
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_FIELDS.iter().any(|secret| name.contains(secret))
+}
+
+/// Replaces the token after each `Bearer ` (case-insensitive).
+fn redact_bearer(s: &str) -> String {
+    const PREFIX: &str = "bearer ";
+    let lowercase = s.to_ascii_lowercase();
+    let mut redacted = String::with_capacity(s.len());
+    let mut rest = 0;
+    while let Some(start) = lowercase[rest..]
+        .find(PREFIX)
+        .map(|i| rest + i + PREFIX.len())
+    {
+        redacted.push_str(&s[rest..start]);
+        redacted.push_str(REDACTED);
+        rest = s[start..]
+            .find(|c: char| c.is_whitespace() || c == '"' || c == ',')
+            .map(|i| start + i)
+            .unwrap_or(s.len());
+    }
+    redacted.push_str(&s[rest..]);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Instrument as _;
+
+    use super::*;
+    use crate::test_utils::LogBuffer;
+
+    #[test]
+    fn test_subscriber() -> anyhow::Result<()> {
+        let buffer = LogBuffer::default();
+        tracing::subscriber::with_default(subscriber(buffer.clone(), Level::INFO), || {
+            let span =
+                tracing::info_span!("request", request_id = "1", status = tracing::field::Empty);
+            let _guard = span.enter();
+            tracing::info!(password = "secret", count = 2, "hello {}", "world");
+            tracing::debug!("hidden");
+            span.record("status", 200);
+            tracing::warn!(header = "Bearer abc.def, Bearer xyz", "auth");
+        });
+        let lines = buffer.lines()?;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["target"], "web::infra::logging::tests");
+        assert_eq!(
+            lines[0]["fields"],
+            serde_json::json!({ "message": "hello world", "password": "[REDACTED]", "count": 2 })
+        );
+        assert_eq!(
+            lines[0]["spans"],
+            serde_json::json!([{ "name": "request", "request_id": "1" }])
+        );
+        assert_eq!(
+            lines[1]["fields"],
+            serde_json::json!({ "message": "auth", "header": "Bearer [REDACTED], Bearer [REDACTED]" })
+        );
+        assert_eq!(
+            lines[1]["span"],
+            serde_json::json!({ "name": "request", "request_id": "1", "status": 200 })
+        );
+        assert_eq!(lines[2]["fields"]["message"], "close");
+        assert!(lines[2]["fields"]["time.busy"].is_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscriber_async() -> anyhow::Result<()> {
+        let buffer = LogBuffer::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        tracing::subscriber::with_default(subscriber(buffer.clone(), Level::INFO), || {
+            let task = |request_id: &'static str| {
+                async move {
+                    tokio::task::yield_now().await;
+                    tracing::info!(request_id, "event");
+                }
+                .instrument(tracing::info_span!("request", request_id))
+            };
+            runtime.block_on(async { tokio::join!(task("1"), task("2")) });
+        });
+        let events = buffer
+            .lines()?
+            .into_iter()
+            .filter(|line| line["fields"]["message"] == "event")
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        for event in events {
+            // each event is in the span of its own task
+            assert_eq!(event["span"]["request_id"], event["fields"]["request_id"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_redact_bearer() {
+        assert_eq!(redact_bearer("no token"), "no token");
+        assert_eq!(redact_bearer("bearer abc"), "bearer [REDACTED]");
+        assert_eq!(
+            redact_bearer(r#"{"authorization":"Bearer abc"}"#),
+            r#"{"authorization":"Bearer [REDACTED]"}"#
+        );
+    }
+}
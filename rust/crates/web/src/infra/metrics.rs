@@ -0,0 +1,226 @@
+//! Metrics in the Prometheus text format, served at `/metrics`.
+//!
+//! | name | type | labels |
+//! | --- | --- | --- |
+//! | `graphql_requests_total` | counter | `operation`, `result` (`ok` or `error`) |
+//! | `graphql_request_duration_seconds` | histogram | `operation` |
+//! | `store_call_duration_seconds` | histogram | `method` |
+//! | `store_errors_total` | counter | `method`, `code` (e.g. `NOT_FOUND`) |
+//! | `firestore_rpcs_total` | counter | `rpc`, `code` (gRPC status code, e.g. `OK`) |
+//!
+//! `operation` is the GraphQL operation name, empty for an anonymous
+//! operation. As the name is chosen by the client, only the first
+//! `MAX_OPERATIONS` names get their own series; the rest are `other`.
+
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder};
+
+const MAX_OPERATIONS: usize = 100;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    /// The operation names that have their own series.
+    operations: Mutex<HashSet<String>>,
+    graphql_requests: IntCounterVec,
+    graphql_request_duration: HistogramVec,
+    store_call_duration: HistogramVec,
+    store_errors: IntCounterVec,
+    firestore_rpcs: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter =
+                IntCounterVec::new(Opts::new(name, help), labels).expect("invalid counter");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("duplicate metric");
+            counter
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str]| {
+            let histogram = HistogramVec::new(HistogramOpts::new(name, help), labels)
+                .expect("invalid histogram");
+            registry
+                .register(Box::new(histogram.clone()))
+                .expect("duplicate metric");
+            histogram
+        };
+        Self {
+            graphql_requests: counter(
+                "graphql_requests_total",
+                "GraphQL requests by operation name and result.",
+                &["operation", "result"],
+            ),
+            graphql_request_duration: histogram(
+                "graphql_request_duration_seconds",
+                "GraphQL request latency by operation name.",
+                &["operation"],
+            ),
+            store_call_duration: histogram(
+                "store_call_duration_seconds",
+                "Store call latency by method.",
+                &["method"],
+            ),
+            store_errors: counter(
+                "store_errors_total",
+                "Failed store calls by method and error code.",
+                &["method", "code"],
+            ),
+            firestore_rpcs: counter(
+                "firestore_rpcs_total",
+                "Firestore RPCs by method and gRPC status code.",
+                &["rpc", "code"],
+            ),
+            operations: Mutex::new(HashSet::new()),
+            registry,
+        }
+    }
+
+    /// The metrics of the process.
+    pub fn global() -> &'static Self {
+        METRICS.get_or_init(Self::new)
+    }
+
+    pub fn observe_graphql_request(&self, operation: &str, duration: Duration, ok: bool) {
+        let operation = {
+            let mut operations = self.operations.lock().expect("operations poisoned");
+            if operations.contains(operation) || operations.len() < MAX_OPERATIONS {
+                operations.insert(operation.to_owned());
+                operation
+            } else {
+                "other"
+            }
+        };
+        self.graphql_requests
+            .with_label_values(&[operation, if ok { "ok" } else { "error" }])
+            .inc();
+        self.graphql_request_duration
+            .with_label_values(&[operation])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// `error_code` is `use_case::Error::code` of a failed call.
+    pub fn observe_store_call(&self, method: &str, duration: Duration, error_code: Option<&str>) {
+        self.store_call_duration
+            .with_label_values(&[method])
+            .observe(duration.as_secs_f64());
+        if let Some(code) = error_code {
+            self.store_errors.with_label_values(&[method, code]).inc();
+        }
+    }
+
+    pub fn count_firestore_rpc(&self, rpc: &str, code: tonic::Code) {
+        self.firestore_rpcs
+            .with_label_values(&[rpc, &grpc_code_name(code)])
+            .inc();
+    }
+
+    /// Renders the text exposition format (version 0.0.4).
+    pub fn render(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap_or_default()
+    }
+}
+
+/// e.g. `NOT_FOUND` for `tonic::Code::NotFound`
+fn grpc_code_name(code: tonic::Code) -> String {
+    let mut name = String::new();
+    for (index, c) in format!("{:?}", code).chars().enumerate() {
+        if c.is_ascii_uppercase() && index > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.observe_graphql_request("Items", Duration::from_millis(20), true);
+        metrics.observe_graphql_request("Items", Duration::from_millis(200), false);
+        metrics.observe_store_call("find_user", Duration::from_millis(1), Some("NOT_FOUND"));
+        metrics.count_firestore_rpc("GetDocument", tonic::Code::Ok);
+        metrics.count_firestore_rpc("GetDocument", tonic::Code::DeadlineExceeded);
+        assert_eq!(
+            metrics.render(),
+            r#"# HELP firestore_rpcs_total Firestore RPCs by method and gRPC status code.
+# TYPE firestore_rpcs_total counter
+firestore_rpcs_total{code="DEADLINE_EXCEEDED",rpc="GetDocument"} 1
+firestore_rpcs_total{code="OK",rpc="GetDocument"} 1
+# HELP graphql_request_duration_seconds GraphQL request latency by operation name.
+# TYPE graphql_request_duration_seconds histogram
+graphql_request_duration_seconds_bucket{operation="Items",le="0.005"} 0
+graphql_request_duration_seconds_bucket{operation="Items",le="0.01"} 0
+graphql_request_duration_seconds_bucket{operation="Items",le="0.025"} 1
+graphql_request_duration_seconds_bucket{operation="Items",le="0.05"} 1
+graphql_request_duration_seconds_bucket{operation="Items",le="0.1"} 1
+graphql_request_duration_seconds_bucket{operation="Items",le="0.25"} 2
+graphql_request_duration_seconds_bucket{operation="Items",le="0.5"} 2
+graphql_request_duration_seconds_bucket{operation="Items",le="1"} 2
+graphql_request_duration_seconds_bucket{operation="Items",le="2.5"} 2
+graphql_request_duration_seconds_bucket{operation="Items",le="5"} 2
+graphql_request_duration_seconds_bucket{operation="Items",le="10"} 2
+graphql_request_duration_seconds_bucket{operation="Items",le="+Inf"} 2
+graphql_request_duration_seconds_sum{operation="Items"} 0.22
+graphql_request_duration_seconds_count{operation="Items"} 2
+# HELP graphql_requests_total GraphQL requests by operation name and result.
+# TYPE graphql_requests_total counter
+graphql_requests_total{operation="Items",result="error"} 1
+graphql_requests_total{operation="Items",result="ok"} 1
+# HELP store_call_duration_seconds Store call latency by method.
+# TYPE store_call_duration_seconds histogram
+store_call_duration_seconds_bucket{method="find_user",le="0.005"} 1
+store_call_duration_seconds_bucket{method="find_user",le="0.01"} 1
+store_call_duration_seconds_bucket{method="find_user",le="0.025"} 1
+store_call_duration_seconds_bucket{method="find_user",le="0.05"} 1
+store_call_duration_seconds_bucket{method="find_user",le="0.1"} 1
+store_call_duration_seconds_bucket{method="find_user",le="0.25"} 1
+store_call_duration_seconds_bucket{method="find_user",le="0.5"} 1
+store_call_duration_seconds_bucket{method="find_user",le="1"} 1
+store_call_duration_seconds_bucket{method="find_user",le="2.5"} 1
+store_call_duration_seconds_bucket{method="find_user",le="5"} 1
+store_call_duration_seconds_bucket{method="find_user",le="10"} 1
+store_call_duration_seconds_bucket{method="find_user",le="+Inf"} 1
+store_call_duration_seconds_sum{method="find_user"} 0.001
+store_call_duration_seconds_count{method="find_user"} 1
+# HELP store_errors_total Failed store calls by method and error code.
+# TYPE store_errors_total counter
+store_errors_total{code="NOT_FOUND",method="find_user"} 1
+"#
+        );
+    }
+
+    #[test]
+    fn test_operation_cardinality() {
+        let metrics = Metrics::new();
+        for i in 0..MAX_OPERATIONS + 1 {
+            metrics.observe_graphql_request(&format!("Op{}", i), Duration::ZERO, true);
+        }
+        metrics.observe_graphql_request("Op0", Duration::ZERO, true);
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"graphql_requests_total{operation="Op0",result="ok"} 2"#));
+        assert!(rendered.contains(r#"graphql_requests_total{operation="other",result="ok"} 1"#));
+    }
+}
@@ -0,0 +1,925 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::async_trait;
+use rusqlite::{params, Connection, OptionalExtension as _, Row, Transaction};
+
+use crate::{
+    model,
+    use_case::{
+        self,
+        data::{ChangeKindData, EntityData},
+        Store,
+    },
+};
+
+const MIGRATIONS: &[&str] = &[
+    include_str!("sqlite_store/migrations/0001_create_tables.sql"),
+    include_str!("sqlite_store/migrations/0002_create_changes.sql"),
+    include_str!("sqlite_store/migrations/0003_add_items_archived_at.sql"),
+    include_str!("sqlite_store/migrations/0004_add_items_metadata.sql"),
+    include_str!("sqlite_store/migrations/0005_add_checks_details.sql"),
+    include_str!("sqlite_store/migrations/0006_create_users.sql"),
+    include_str!("sqlite_store/migrations/0007_add_versions.sql"),
+    include_str!("sqlite_store/migrations/0008_backfill_versions.sql"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("check list already exists {0}")]
+    CheckListAlreadyExists(String),
+    #[error("check list not found {0}")]
+    CheckListNotFound(String),
+    #[error("client sequence conflict {0}")]
+    ClientSequenceConflict(String),
+    #[error("item not found {0}")]
+    ItemNotFound(String),
+    #[error("join {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("lock poisoned")]
+    LockPoisoned,
+    #[error("serde_json {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("sqlite {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("version conflict expected {expected}")]
+    VersionConflict {
+        current: Option<Box<model::Entity>>,
+        expected: u64,
+    },
+}
+
+impl From<Error> for use_case::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::CheckListAlreadyExists(id) => Self::AlreadyExists(format!("check list {}", id)),
+            Error::CheckListNotFound(id) => Self::NotFound(format!("check list {}", id)),
+            Error::ClientSequenceConflict(id) => Self::Conflict(format!("client sequence {}", id)),
+            Error::ItemNotFound(id) => Self::NotFound(format!("item {}", id)),
+            Error::Sqlite(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked,
+                    ..
+                },
+                _,
+            )) => Self::Unavailable(e.to_string()),
+            Error::Sqlite(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: rusqlite::ErrorCode::ConstraintViolation,
+                    ..
+                },
+                _,
+            )) => Self::Conflict(e.to_string()),
+            Error::VersionConflict { current, expected } => {
+                Self::VersionConflict { current, expected }
+            }
+            Error::Join(_) | Error::LockPoisoned | Error::SerdeJson(_) | Error::Sqlite(_) => {
+                Self::Unknown(e.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::new(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut connection: Connection) -> Result<Self, Error> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn with_connection<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().map_err(|_| Error::LockPoisoned)?;
+            f(&mut connection)
+        })
+        .await?
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), Error> {
+    let user_version: usize =
+        connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let transaction = connection.transaction()?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(user_version) {
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Records a change in the same transaction as the write. Returns its
+/// revision, which is the version of the entity after the change.
+fn record_change(
+    transaction: &Transaction<'_>,
+    kind: model::ChangeKind,
+    entity: model::Entity,
+) -> Result<u64, Error> {
+    // a concurrent writer that takes the same revision fails the insert
+    let revision: u64 = transaction.query_row(
+        "SELECT COALESCE((SELECT seq FROM sqlite_sequence WHERE name = 'changes'), 0) + 1",
+        [],
+        |row| row.get(0),
+    )?;
+    transaction.execute(
+        "INSERT INTO changes (revision, kind, entity) VALUES (?1, ?2, ?3)",
+        params![
+            revision,
+            serde_json::to_value(ChangeKindData::from(kind))?
+                .as_str()
+                .unwrap_or_default(),
+            serde_json::to_string(&EntityData::from(entity.with_version(revision)))?
+        ],
+    )?;
+    Ok(revision)
+}
+
+fn compare_version(
+    current: Option<model::Entity>,
+    expected_version: Option<u64>,
+) -> Result<(), Error> {
+    let current_version = current.as_ref().map_or(0, model::Entity::version);
+    match expected_version {
+        Some(expected) if expected != current_version => Err(Error::VersionConflict {
+            current: current.map(Box::new),
+            expected,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn find_check(
+    transaction: &Transaction<'_>,
+    check_list_id: &str,
+    item_id: &str,
+) -> Result<Option<model::Check>, Error> {
+    Ok(transaction
+        .query_row(
+            "SELECT check_list_id, item_id, checked_at, note, price_amount, price_currency, quantity, shop, version FROM checks WHERE check_list_id = ?1 AND item_id = ?2",
+            params![check_list_id, item_id],
+            check_from_row,
+        )
+        .optional()?)
+}
+
+fn find_item(transaction: &Transaction<'_>, item_id: &str) -> Result<Option<model::Item>, Error> {
+    Ok(transaction
+        .query_row(
+            "SELECT id, name, archived_at, category, emoji, note, sort_order, unit, version FROM items WHERE id = ?1",
+            params![item_id],
+            item_from_row,
+        )
+        .optional()?)
+}
+
+/// Archives (`Some`) or restores (`None`) the item. Archiving an archived item is a no-op.
+fn set_archived_at(
+    connection: &mut Connection,
+    item_id: String,
+    archived_at: Option<String>,
+) -> Result<(), Error> {
+    let transaction = connection.transaction()?;
+    let item = find_item(&transaction, &item_id)?.ok_or(Error::ItemNotFound(item_id))?;
+    if item.archived_at.is_some() == archived_at.is_some() {
+        return Ok(());
+    }
+    write_item(
+        &transaction,
+        model::Item {
+            archived_at,
+            ..item
+        },
+    )?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Upserts the check in the caller's transaction and returns it as stored.
+/// Storing the same details again keeps the version.
+fn write_check(transaction: &Transaction<'_>, check: model::Check) -> Result<model::Check, Error> {
+    let check_list_exists = transaction
+        .query_row(
+            "SELECT 1 FROM check_lists WHERE id = ?1",
+            params![check.check_list_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !check_list_exists {
+        return Err(Error::CheckListNotFound(check.check_list_id));
+    }
+    let item_exists = transaction
+        .query_row(
+            "SELECT 1 FROM items WHERE id = ?1",
+            params![check.item_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !item_exists {
+        return Err(Error::ItemNotFound(check.item_id));
+    }
+    let kind = match find_check(transaction, &check.check_list_id, &check.item_id)? {
+        Some(previous)
+            if model::Check {
+                version: previous.version,
+                ..check.clone()
+            } == previous =>
+        {
+            return Ok(previous)
+        }
+        Some(_) => model::ChangeKind::Updated,
+        None => model::ChangeKind::Created,
+    };
+    let version = record_change(transaction, kind, model::Entity::Check(check.clone()))?;
+    let check = model::Check { version, ..check };
+    let (price_amount, price_currency) = match &check.price {
+        Some(price) => (Some(price.amount), Some(price.currency.as_str())),
+        None => (None, None),
+    };
+    transaction.execute(
+        "INSERT INTO checks (check_list_id, item_id, checked_at, note, price_amount, price_currency, quantity, shop, version) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (check_list_id, item_id) DO UPDATE SET checked_at = excluded.checked_at, note = excluded.note, price_amount = excluded.price_amount, price_currency = excluded.price_currency, quantity = excluded.quantity, shop = excluded.shop, version = excluded.version",
+        params![
+            check.check_list_id,
+            check.item_id,
+            check.checked_at,
+            check.note,
+            price_amount,
+            price_currency,
+            check.quantity,
+            check.shop,
+            check.version
+        ],
+    )?;
+    Ok(check)
+}
+
+/// Inserts the check list in the caller's transaction.
+fn write_check_list(
+    transaction: &Transaction<'_>,
+    check_list: model::CheckList,
+) -> Result<(), Error> {
+    let exists = transaction
+        .query_row(
+            "SELECT 1 FROM check_lists WHERE id = ?1 OR date = ?2",
+            params![check_list.id, check_list.date],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if exists {
+        return Err(Error::CheckListAlreadyExists(check_list.id));
+    }
+    let version = record_change(
+        transaction,
+        model::ChangeKind::Created,
+        model::Entity::CheckList(check_list.clone()),
+    )?;
+    transaction.execute(
+        "INSERT INTO check_lists (id, date, version) VALUES (?1, ?2, ?3)",
+        params![check_list.id, check_list.date, version],
+    )?;
+    Ok(())
+}
+
+/// Upserts the item in the caller's transaction and returns it as stored.
+fn write_item(transaction: &Transaction<'_>, item: model::Item) -> Result<model::Item, Error> {
+    let exists = transaction
+        .query_row(
+            "SELECT 1 FROM items WHERE id = ?1",
+            params![item.id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    let version = record_change(
+        transaction,
+        if exists {
+            model::ChangeKind::Updated
+        } else {
+            model::ChangeKind::Created
+        },
+        model::Entity::Item(item.clone()),
+    )?;
+    let item = model::Item { version, ..item };
+    transaction.execute(
+        "INSERT INTO items (id, name, archived_at, category, emoji, note, sort_order, unit, version) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (id) DO UPDATE SET name = excluded.name, archived_at = excluded.archived_at, category = excluded.category, emoji = excluded.emoji, note = excluded.note, sort_order = excluded.sort_order, unit = excluded.unit, version = excluded.version",
+        params![
+            item.id,
+            item.name,
+            item.archived_at,
+            item.category,
+            item.emoji,
+            item.note,
+            item.sort_order,
+            item.unit,
+            item.version
+        ],
+    )?;
+    Ok(item)
+}
+
+fn check_from_row(row: &Row<'_>) -> rusqlite::Result<model::Check> {
+    let price_amount: Option<i64> = row.get("price_amount")?;
+    let price_currency: Option<String> = row.get("price_currency")?;
+    Ok(model::Check {
+        check_list_id: row.get("check_list_id")?,
+        item_id: row.get("item_id")?,
+        checked_at: row.get("checked_at")?,
+        note: row.get("note")?,
+        price: price_amount
+            .zip(price_currency)
+            .map(|(amount, currency)| model::Price { amount, currency }),
+        quantity: row.get("quantity")?,
+        shop: row.get("shop")?,
+        version: row.get("version")?,
+    })
+}
+
+fn check_list_from_row(row: &Row<'_>) -> rusqlite::Result<model::CheckList> {
+    Ok(model::CheckList {
+        id: row.get("id")?,
+        date: row.get("date")?,
+        version: row.get("version")?,
+    })
+}
+
+fn item_from_row(row: &Row<'_>) -> rusqlite::Result<model::Item> {
+    Ok(model::Item {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        archived_at: row.get("archived_at")?,
+        category: row.get("category")?,
+        emoji: row.get("emoji")?,
+        note: row.get("note")?,
+        sort_order: row.get("sort_order")?,
+        unit: row.get("unit")?,
+        version: row.get("version")?,
+    })
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn archive_item(
+        &self,
+        item_id: String,
+        archived_at: String,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                set_archived_at(connection, item_id, Some(archived_at))
+            })
+            .await?)
+    }
+
+    async fn changes_since(
+        &self,
+        revision: u64,
+        limit: usize,
+    ) -> Result<Vec<model::Change>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let mut statement = connection.prepare(
+                    "SELECT revision, kind, entity FROM changes WHERE revision > ?1 ORDER BY revision LIMIT ?2",
+                )?;
+                let rows = statement
+                    .query_map(params![revision, limit], |row| {
+                        Ok((
+                            row.get::<_, u64>("revision")?,
+                            row.get::<_, String>("kind")?,
+                            row.get::<_, String>("entity")?,
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                rows.into_iter()
+                    .map(|(revision, kind, entity)| {
+                        Ok(model::Change {
+                            entity: model::Entity::from(serde_json::from_str::<EntityData>(
+                                &entity,
+                            )?),
+                            kind: model::ChangeKind::from(serde_json::from_value::<
+                                ChangeKindData,
+                            >(
+                                serde_json::Value::String(kind)
+                            )?),
+                            revision,
+                        })
+                    })
+                    .collect()
+            })
+            .await?)
+    }
+
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                let current = find_check(&transaction, &check_list_id, &item_id)?;
+                compare_version(current.clone().map(model::Entity::Check), expected_version)?;
+                let Some(check) = current else {
+                    return Ok(());
+                };
+                transaction.execute(
+                    "DELETE FROM checks WHERE check_list_id = ?1 AND item_id = ?2",
+                    params![check_list_id, item_id],
+                )?;
+                record_change(
+                    &transaction,
+                    model::ChangeKind::Deleted,
+                    model::Entity::Check(check),
+                )?;
+                transaction.commit()?;
+                Ok(())
+            })
+            .await?)
+    }
+
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                let current = find_item(&transaction, &item_id)?;
+                compare_version(current.clone().map(model::Entity::Item), expected_version)?;
+                let Some(item) = current else {
+                    return Ok(());
+                };
+                let checks = {
+                    let mut statement = transaction.prepare(
+                        "SELECT check_list_id, item_id, checked_at, note, price_amount, price_currency, quantity, shop, version FROM checks WHERE item_id = ?1 ORDER BY check_list_id",
+                    )?;
+                    let checks = statement
+                        .query_map(params![item_id], check_from_row)?
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+                    checks
+                };
+                transaction.execute("DELETE FROM checks WHERE item_id = ?1", params![item_id])?;
+                for check in checks {
+                    record_change(
+                        &transaction,
+                        model::ChangeKind::Deleted,
+                        model::Entity::Check(check),
+                    )?;
+                }
+                transaction.execute("DELETE FROM items WHERE id = ?1", params![item_id])?;
+                record_change(
+                    &transaction,
+                    model::ChangeKind::Deleted,
+                    model::Entity::Item(item),
+                )?;
+                transaction.commit()?;
+                Ok(())
+            })
+            .await?)
+    }
+
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                let current = find_check(&transaction, &check.check_list_id, &check.item_id)?;
+                compare_version(current.map(model::Entity::Check), expected_version)?;
+                let check = write_check(&transaction, check)?;
+                transaction.commit()?;
+                Ok(check)
+            })
+            .await?)
+    }
+
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<u64>,
+        sequence: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                let current = transaction
+                    .query_row(
+                        "SELECT sequence FROM client_sequences WHERE client_id = ?1",
+                        params![client_id],
+                        |row| row.get::<_, u64>(0),
+                    )
+                    .optional()?;
+                if current != expected {
+                    return Err(Error::ClientSequenceConflict(client_id));
+                }
+                match sequence {
+                    Some(sequence) => transaction.execute(
+                        "INSERT INTO client_sequences (client_id, sequence) VALUES (?1, ?2) ON CONFLICT (client_id) DO UPDATE SET sequence = excluded.sequence",
+                        params![client_id, sequence],
+                    )?,
+                    None => transaction.execute(
+                        "DELETE FROM client_sequences WHERE client_id = ?1",
+                        params![client_id],
+                    )?,
+                };
+                transaction.commit()?;
+                Ok(())
+            })
+            .await?)
+    }
+
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                let current = find_item(&transaction, &item.id)?;
+                compare_version(current.map(model::Entity::Item), expected_version)?;
+                let item = write_item(&transaction, item)?;
+                transaction.commit()?;
+                Ok(item)
+            })
+            .await?)
+    }
+
+    async fn delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+    ) -> Result<(), use_case::Error> {
+        self.compare_and_delete_check(check_list_id, item_id, None)
+            .await
+    }
+
+    async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, use_case::Error> {
+        Ok(self
+            .with_connection(|connection| {
+                let mut statement =
+                    connection.prepare("SELECT id, date, version FROM check_lists ORDER BY id")?;
+                let check_lists = statement
+                    .query_map([], check_list_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(check_lists)
+            })
+            .await?)
+    }
+
+    async fn find_all_checks(&self) -> Result<Vec<model::Check>, use_case::Error> {
+        Ok(self
+            .with_connection(|connection| {
+                let mut statement = connection.prepare(
+                    "SELECT check_list_id, item_id, checked_at, note, price_amount, price_currency, quantity, shop, version FROM checks ORDER BY check_list_id, item_id",
+                )?;
+                let checks = statement
+                    .query_map([], check_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(checks)
+            })
+            .await?)
+    }
+
+    async fn find_all_items(&self) -> Result<Vec<model::Item>, use_case::Error> {
+        Ok(self
+            .with_connection(|connection| {
+                let mut statement =
+                    connection.prepare("SELECT id, name, archived_at, category, emoji, note, sort_order, unit, version FROM items ORDER BY id")?;
+                let items = statement
+                    .query_map([], item_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(items)
+            })
+            .await?)
+    }
+
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let mut statement = connection.prepare(
+                    "SELECT id, date, version FROM check_lists WHERE ?1 IS NULL OR id > ?1 ORDER BY id LIMIT ?2",
+                )?;
+                let check_lists = statement
+                    .query_map(params![after, limit], check_list_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(check_lists)
+            })
+            .await?)
+    }
+
+    async fn find_checks_by_check_list_id(
+        &self,
+        check_list_id: String,
+    ) -> Result<Vec<model::Check>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let mut statement = connection.prepare(
+                    "SELECT check_list_id, item_id, checked_at, note, price_amount, price_currency, quantity, shop, version FROM checks WHERE check_list_id = ?1 ORDER BY item_id",
+                )?;
+                let checks = statement
+                    .query_map(params![check_list_id], check_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(checks)
+            })
+            .await?)
+    }
+
+    async fn find_checks_by_item_id(
+        &self,
+        item_id: String,
+    ) -> Result<Vec<model::Check>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let mut statement = connection.prepare(
+                    "SELECT check_list_id, item_id, checked_at, note, price_amount, price_currency, quantity, shop, version FROM checks WHERE item_id = ?1 ORDER BY check_list_id",
+                )?;
+                let checks = statement
+                    .query_map(params![item_id], check_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(checks)
+            })
+            .await?)
+    }
+
+    async fn find_client_sequence(
+        &self,
+        client_id: String,
+    ) -> Result<Option<u64>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                Ok(connection
+                    .query_row(
+                        "SELECT sequence FROM client_sequences WHERE client_id = ?1",
+                        params![client_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?)
+            })
+            .await?)
+    }
+
+    async fn find_current_revision(&self) -> Result<u64, use_case::Error> {
+        Ok(self
+            .with_connection(|connection| {
+                Ok(connection.query_row(
+                    "SELECT COALESCE(MAX(revision), 0) FROM changes",
+                    [],
+                    |row| row.get(0),
+                )?)
+            })
+            .await?)
+    }
+
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let mut statement = connection.prepare(
+                    "SELECT id, name, archived_at, category, emoji, note, sort_order, unit, version FROM items WHERE ?1 IS NULL OR id > ?1 ORDER BY id LIMIT ?2",
+                )?;
+                let items = statement
+                    .query_map(params![after, limit], item_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(items)
+            })
+            .await?)
+    }
+
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                Ok(connection
+                    .query_row(
+                        "SELECT id, password_hash FROM users WHERE id = ?1",
+                        params![user_id],
+                        |row| {
+                            Ok(model::User {
+                                id: row.get(0)?,
+                                password_hash: row.get(1)?,
+                            })
+                        },
+                    )
+                    .optional()?)
+            })
+            .await?)
+    }
+
+    /// Every write is committed before it returns.
+    async fn flush(&self) -> Result<(), use_case::Error> {
+        Ok(())
+    }
+
+    async fn purge_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        self.compare_and_purge_item(item_id, None).await
+    }
+
+    async fn restore_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| set_archived_at(connection, item_id, None))
+            .await?)
+    }
+
+    async fn store_batch(&self, batch: use_case::Batch) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                for item in batch.items {
+                    write_item(&transaction, item)?;
+                }
+                for check_list in batch.check_lists {
+                    write_check_list(&transaction, check_list)?;
+                }
+                for check in batch.checks {
+                    write_check(&transaction, check)?;
+                }
+                transaction.commit()?;
+                Ok(())
+            })
+            .await?)
+    }
+
+    async fn store_check(&self, check: model::Check) -> Result<(), use_case::Error> {
+        self.compare_and_store_check(check, None).await?;
+        Ok(())
+    }
+
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                let transaction = connection.transaction()?;
+                write_check_list(&transaction, check_list)?;
+                transaction.commit()?;
+                Ok(())
+            })
+            .await?)
+    }
+
+    async fn store_client_sequence(
+        &self,
+        client_id: String,
+        sequence: u64,
+    ) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                connection.execute(
+                    "INSERT INTO client_sequences (client_id, sequence) VALUES (?1, ?2) ON CONFLICT (client_id) DO UPDATE SET sequence = excluded.sequence",
+                    params![client_id, sequence],
+                )?;
+                Ok(())
+            })
+            .await?)
+    }
+
+    async fn store_item(&self, item: model::Item) -> Result<(), use_case::Error> {
+        self.compare_and_store_item(item, None).await?;
+        Ok(())
+    }
+
+    async fn store_user(&self, user: model::User) -> Result<(), use_case::Error> {
+        Ok(self
+            .with_connection(move |connection| {
+                connection.execute(
+                    "INSERT INTO users (id, password_hash) VALUES (?1, ?2) ON CONFLICT (id) DO UPDATE SET password_hash = excluded.password_hash",
+                    params![user.id, user.password_hash],
+                )?;
+                Ok(())
+            })
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::store_conformance;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_migrate() -> anyhow::Result<()> {
+        let mut connection = Connection::open_in_memory()?;
+        migrate(&mut connection)?;
+        // idempotent
+        migrate(&mut connection)?;
+        let user_version: usize =
+            connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        assert_eq!(user_version, MIGRATIONS.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_records_existing_rows() -> anyhow::Result<()> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(MIGRATIONS[0])?;
+        connection.pragma_update(None, "user_version", 1)?;
+        connection.execute_batch(
+            "INSERT INTO check_lists (id, date) VALUES ('1', '2020-01-02');
+             INSERT INTO items (id, name) VALUES ('2', 'item2');
+             INSERT INTO checks (check_list_id, item_id) VALUES ('1', '2');",
+        )?;
+        let store = SqliteStore::new(connection)?;
+        let changes = store.changes_since(0, 10).await?;
+        assert_eq!(
+            changes
+                .into_iter()
+                .map(|change| (change.revision, change.entity))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    1,
+                    model::Entity::CheckList(model::CheckList {
+                        id: "1".to_owned(),
+                        date: "2020-01-02".to_owned(),
+                        ..Default::default()
+                    })
+                ),
+                (
+                    2,
+                    model::Entity::Item(model::Item {
+                        id: "2".to_owned(),
+                        name: "item2".to_owned(),
+                        ..Default::default()
+                    })
+                ),
+                (
+                    3,
+                    model::Entity::Check(model::Check {
+                        check_list_id: "1".to_owned(),
+                        item_id: "2".to_owned(),
+                        ..Default::default()
+                    })
+                ),
+            ]
+        );
+        // the rows are at the version of their change, not 0
+        assert_eq!(
+            store
+                .find_all_check_lists()
+                .await?
+                .into_iter()
+                .map(|check_list| check_list.version)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(
+            store
+                .find_all_items()
+                .await?
+                .into_iter()
+                .map(|item| item.version)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(
+            store
+                .find_all_checks()
+                .await?
+                .into_iter()
+                .map(|check| check.version)
+                .collect::<Vec<_>>(),
+            vec![3]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conformance() -> anyhow::Result<()> {
+        store_conformance::run(|| async { Ok(SqliteStore::open_in_memory()?) }).await
+    }
+}
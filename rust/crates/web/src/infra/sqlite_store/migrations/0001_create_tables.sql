@@ -0,0 +1,18 @@
+CREATE TABLE check_lists (
+  id TEXT NOT NULL PRIMARY KEY,
+  date TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE items (
+  id TEXT NOT NULL PRIMARY KEY,
+  name TEXT NOT NULL
+);
+
+CREATE TABLE checks (
+  check_list_id TEXT NOT NULL REFERENCES check_lists (id),
+  item_id TEXT NOT NULL REFERENCES items (id),
+  PRIMARY KEY (check_list_id, item_id)
+);
+
+-- lookups by check_list_id use the primary key index
+CREATE INDEX checks_item_id ON checks (item_id, check_list_id);
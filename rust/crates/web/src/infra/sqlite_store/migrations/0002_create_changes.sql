@@ -0,0 +1,21 @@
+CREATE TABLE changes (
+  revision INTEGER PRIMARY KEY AUTOINCREMENT,
+  kind TEXT NOT NULL,
+  entity TEXT NOT NULL
+);
+
+CREATE TABLE client_sequences (
+  client_id TEXT PRIMARY KEY,
+  sequence INTEGER NOT NULL
+);
+
+-- existing rows become the first changes
+INSERT INTO changes (kind, entity)
+  SELECT 'created', json_object('type', 'checkList', 'payload', json_object('date', date, 'id', id))
+  FROM check_lists ORDER BY id;
+INSERT INTO changes (kind, entity)
+  SELECT 'created', json_object('type', 'item', 'payload', json_object('id', id, 'name', name))
+  FROM items ORDER BY id;
+INSERT INTO changes (kind, entity)
+  SELECT 'created', json_object('type', 'check', 'payload', json_object('checkListId', check_list_id, 'itemId', item_id))
+  FROM checks ORDER BY check_list_id, item_id;
@@ -0,0 +1 @@
+ALTER TABLE items ADD COLUMN archived_at TEXT;
@@ -0,0 +1,5 @@
+ALTER TABLE items ADD COLUMN category TEXT;
+ALTER TABLE items ADD COLUMN emoji TEXT;
+ALTER TABLE items ADD COLUMN note TEXT;
+ALTER TABLE items ADD COLUMN sort_order INTEGER;
+ALTER TABLE items ADD COLUMN unit TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE checks ADD COLUMN checked_at TEXT;
+ALTER TABLE checks ADD COLUMN note TEXT;
+ALTER TABLE checks ADD COLUMN price_amount INTEGER;
+ALTER TABLE checks ADD COLUMN price_currency TEXT;
+ALTER TABLE checks ADD COLUMN quantity REAL;
+ALTER TABLE checks ADD COLUMN shop TEXT;
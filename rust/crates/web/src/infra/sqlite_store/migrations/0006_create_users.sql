@@ -0,0 +1,4 @@
+CREATE TABLE users (
+  id TEXT PRIMARY KEY,
+  password_hash TEXT NOT NULL
+);
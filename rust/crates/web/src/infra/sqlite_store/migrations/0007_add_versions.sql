@@ -0,0 +1,3 @@
+ALTER TABLE check_lists ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE checks ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
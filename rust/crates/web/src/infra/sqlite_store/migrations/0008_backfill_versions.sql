@@ -0,0 +1,24 @@
+-- version 0 means "does not exist", so existing rows take the revision of
+-- their latest change
+UPDATE check_lists SET version = COALESCE(
+  (SELECT MAX(revision) FROM changes
+   WHERE json_extract(entity, '$.type') = 'checkList'
+     AND json_extract(entity, '$.payload.id') = check_lists.id),
+  (SELECT seq FROM sqlite_sequence WHERE name = 'changes'),
+  1
+) WHERE version = 0;
+UPDATE checks SET version = COALESCE(
+  (SELECT MAX(revision) FROM changes
+   WHERE json_extract(entity, '$.type') = 'check'
+     AND json_extract(entity, '$.payload.checkListId') = checks.check_list_id
+     AND json_extract(entity, '$.payload.itemId') = checks.item_id),
+  (SELECT seq FROM sqlite_sequence WHERE name = 'changes'),
+  1
+) WHERE version = 0;
+UPDATE items SET version = COALESCE(
+  (SELECT MAX(revision) FROM changes
+   WHERE json_extract(entity, '$.type') = 'item'
+     AND json_extract(entity, '$.payload.id') = items.id),
+  (SELECT seq FROM sqlite_sequence WHERE name = 'changes'),
+  1
+) WHERE version = 0;
@@ -1,3 +1,9 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    ops::Bound,
+    sync::{Arc, RwLock},
+};
+
 use axum::async_trait;
 
 use crate::{
@@ -5,85 +11,870 @@ use crate::{
     use_case::{self, Store},
 };
 
-#[derive(Clone, Debug)]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("check list already exists {0}")]
+    CheckListAlreadyExists(String),
+    #[error("check list not found {0}")]
+    CheckListNotFound(String),
+    #[error("client sequence conflict {0}")]
+    ClientSequenceConflict(String),
+    #[error("item not found {0}")]
+    ItemNotFound(String),
+    #[error("lock poisoned")]
+    LockPoisoned,
+    #[error("revision expired {0}")]
+    RevisionExpired(u64),
+    #[error("version conflict expected {expected}")]
+    VersionConflict {
+        current: Option<Box<model::Entity>>,
+        expected: u64,
+    },
+}
+
+impl From<Error> for use_case::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::CheckListAlreadyExists(id) => Self::AlreadyExists(format!("check list {}", id)),
+            Error::CheckListNotFound(id) => Self::NotFound(format!("check list {}", id)),
+            Error::ClientSequenceConflict(id) => Self::Conflict(format!("client sequence {}", id)),
+            Error::ItemNotFound(id) => Self::NotFound(format!("item {}", id)),
+            Error::LockPoisoned => Self::Unknown(e.to_string()),
+            Error::RevisionExpired(_) => Self::Conflict(e.to_string()),
+            Error::VersionConflict { current, expected } => {
+                Self::VersionConflict { current, expected }
+            }
+        }
+    }
+}
+
+/// The number of changes kept for `changes_since`. A client whose sync token
+/// is older gets a full snapshot.
+const MAX_CHANGES: usize = 10_000;
+
+/// by-id maps and the secondary indexes shared by the in-process stores
+#[derive(Clone, Debug, Default)]
+pub struct Indexes {
+    changes: VecDeque<model::Change>,
+    check_list_ids_by_date: BTreeMap<String, String>,
+    check_lists: BTreeMap<String, model::CheckList>,
+    /// check_list_id -> item_id -> check
+    checks_by_check_list_id: BTreeMap<String, BTreeMap<String, model::Check>>,
+    checks_by_item_id: BTreeMap<String, BTreeSet<String>>,
+    client_sequences: BTreeMap<String, u64>,
+    /// changes up to this revision have been discarded
+    compacted_revision: u64,
+    items: BTreeMap<String, model::Item>,
+    revision: u64,
+    users: BTreeMap<String, model::User>,
+}
+
+impl Indexes {
+    pub fn changes_since(&self, revision: u64, limit: usize) -> Result<Vec<model::Change>, Error> {
+        if revision < self.compacted_revision {
+            return Err(Error::RevisionExpired(revision));
+        }
+        let start = self
+            .changes
+            .partition_point(|change| change.revision <= revision);
+        Ok(self.changes.range(start..).take(limit).cloned().collect())
+    }
+
+    /// Discards the recorded changes. `changes_since` fails for older revisions afterwards.
+    pub fn compact(&mut self, revision: u64) {
+        self.changes.clear();
+        self.compacted_revision = revision;
+        self.revision = revision;
+    }
+
+    pub fn client_sequences(&self) -> &BTreeMap<String, u64> {
+        &self.client_sequences
+    }
+
+    pub fn find_client_sequence(&self, client_id: &str) -> Option<u64> {
+        self.client_sequences.get(client_id).copied()
+    }
+
+    pub fn find_current_revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn store_client_sequence(&mut self, client_id: String, sequence: u64) {
+        self.client_sequences.insert(client_id, sequence);
+    }
+
+    pub fn delete_client_sequence(&mut self, client_id: &str) {
+        self.client_sequences.remove(client_id);
+    }
+
+    pub fn compare_client_sequence(
+        &self,
+        client_id: &str,
+        expected: Option<u64>,
+    ) -> Result<(), Error> {
+        if self.find_client_sequence(client_id) != expected {
+            return Err(Error::ClientSequenceConflict(client_id.to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn find_user(&self, user_id: &str) -> Option<model::User> {
+        self.users.get(user_id).cloned()
+    }
+
+    pub fn store_user(&mut self, user: model::User) {
+        self.users.insert(user.id.clone(), user);
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = &model::User> {
+        self.users.values()
+    }
+
+    /// Records the change and returns its revision, which is the version of
+    /// the entity after the change.
+    fn record(&mut self, kind: model::ChangeKind, entity: model::Entity) -> u64 {
+        self.revision += 1;
+        self.changes.push_back(model::Change {
+            entity: entity.with_version(self.revision),
+            kind,
+            revision: self.revision,
+        });
+        if self.changes.len() > MAX_CHANGES {
+            if let Some(change) = self.changes.pop_front() {
+                self.compacted_revision = change.revision;
+            }
+        }
+        self.revision
+    }
+
+    pub fn archive_item(&mut self, item_id: &str, archived_at: &str) -> Result<(), Error> {
+        let item = self
+            .find_item(item_id)
+            .ok_or_else(|| Error::ItemNotFound(item_id.to_owned()))?;
+        if item.archived_at.is_some() {
+            return Ok(());
+        }
+        self.store_item(model::Item {
+            archived_at: Some(archived_at.to_owned()),
+            ..item
+        });
+        Ok(())
+    }
+
+    pub fn can_store_check(&self, check: &model::Check) -> Result<(), Error> {
+        if !self.check_lists.contains_key(&check.check_list_id) {
+            return Err(Error::CheckListNotFound(check.check_list_id.clone()));
+        }
+        if !self.items.contains_key(&check.item_id) {
+            return Err(Error::ItemNotFound(check.item_id.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn can_store_check_list(&self, check_list: &model::CheckList) -> Result<(), Error> {
+        if self.check_lists.contains_key(&check_list.id)
+            || self.check_list_ids_by_date.contains_key(&check_list.date)
+        {
+            return Err(Error::CheckListAlreadyExists(check_list.id.clone()));
+        }
+        Ok(())
+    }
+
+    /// Returns `Error::VersionConflict` unless `expected_version` is `None` or
+    /// the check's version (0 if it does not exist).
+    pub fn compare_check_version(
+        &self,
+        check_list_id: &str,
+        item_id: &str,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error> {
+        let current = self.find_check(check_list_id, item_id);
+        compare_version(current.map(model::Entity::Check), expected_version)
+    }
+
+    /// Returns `Error::VersionConflict` unless `expected_version` is `None` or
+    /// the item's version (0 if it does not exist).
+    pub fn compare_item_version(
+        &self,
+        item_id: &str,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error> {
+        let current = self.find_item(item_id);
+        compare_version(current.map(model::Entity::Item), expected_version)
+    }
+
+    pub fn contains_check(&self, check_list_id: &str, item_id: &str) -> bool {
+        self.checks_by_check_list_id
+            .get(check_list_id)
+            .is_some_and(|checks| checks.contains_key(item_id))
+    }
+
+    pub fn contains_item(&self, id: &str) -> bool {
+        self.items.contains_key(id)
+    }
+
+    pub fn delete_check(&mut self, check_list_id: &str, item_id: &str) {
+        let Some(check) = self
+            .checks_by_check_list_id
+            .get_mut(check_list_id)
+            .and_then(|checks| checks.remove(item_id))
+        else {
+            return;
+        };
+        if let Some(check_list_ids) = self.checks_by_item_id.get_mut(item_id) {
+            check_list_ids.remove(check_list_id);
+        }
+        self.record(model::ChangeKind::Deleted, model::Entity::Check(check));
+    }
+
+    pub fn find_all_check_lists(&self) -> Vec<model::CheckList> {
+        self.check_lists.values().cloned().collect()
+    }
+
+    pub fn find_all_checks(&self) -> Vec<model::Check> {
+        self.checks_by_check_list_id
+            .values()
+            .flat_map(|checks| checks.values().cloned())
+            .collect()
+    }
+
+    pub fn find_all_items(&self) -> Vec<model::Item> {
+        self.items.values().cloned().collect()
+    }
+
+    pub fn find_check_lists(&self, after: Option<&str>, limit: usize) -> Vec<model::CheckList> {
+        page(&self.check_lists, after, limit)
+    }
+
+    pub fn find_check(&self, check_list_id: &str, item_id: &str) -> Option<model::Check> {
+        self.checks_by_check_list_id
+            .get(check_list_id)
+            .and_then(|checks| checks.get(item_id))
+            .cloned()
+    }
+
+    pub fn find_check_list_by_date(&self, date: &str) -> Option<model::CheckList> {
+        self.check_list_ids_by_date
+            .get(date)
+            .and_then(|id| self.check_lists.get(id))
+            .cloned()
+    }
+
+    pub fn find_checks_by_check_list_id(&self, check_list_id: &str) -> Vec<model::Check> {
+        self.checks_by_check_list_id
+            .get(check_list_id)
+            .into_iter()
+            .flat_map(|checks| checks.values().cloned())
+            .collect()
+    }
+
+    pub fn find_checks_by_item_id(&self, item_id: &str) -> Vec<model::Check> {
+        self.checks_by_item_id
+            .get(item_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|check_list_id| {
+                self.checks_by_check_list_id
+                    .get(check_list_id)
+                    .and_then(|checks| checks.get(item_id))
+                    .cloned()
+            })
+            .collect()
+    }
+
+    pub fn find_item(&self, item_id: &str) -> Option<model::Item> {
+        self.items.get(item_id).cloned()
+    }
+
+    pub fn find_items(&self, after: Option<&str>, limit: usize) -> Vec<model::Item> {
+        page(&self.items, after, limit)
+    }
+
+    /// Inserts a snapshotted entity as is, keeping its version. No change is recorded.
+    pub fn load(&mut self, entity: model::Entity) {
+        match entity {
+            model::Entity::Check(check) => {
+                self.checks_by_item_id
+                    .entry(check.item_id.clone())
+                    .or_default()
+                    .insert(check.check_list_id.clone());
+                self.checks_by_check_list_id
+                    .entry(check.check_list_id.clone())
+                    .or_default()
+                    .insert(check.item_id.clone(), check);
+            }
+            model::Entity::CheckList(check_list) => {
+                self.check_list_ids_by_date
+                    .insert(check_list.date.clone(), check_list.id.clone());
+                self.check_lists.insert(check_list.id.clone(), check_list);
+            }
+            model::Entity::Item(item) => {
+                self.items.insert(item.id.clone(), item);
+            }
+        }
+    }
+
+    pub fn purge_item(&mut self, item_id: &str) {
+        let Some(item) = self.items.get(item_id).cloned() else {
+            return;
+        };
+        for check in self.find_checks_by_item_id(item_id) {
+            self.delete_check(&check.check_list_id, &check.item_id);
+        }
+        self.checks_by_item_id.remove(item_id);
+        self.items.remove(item_id);
+        self.record(model::ChangeKind::Deleted, model::Entity::Item(item));
+    }
+
+    pub fn restore_item(&mut self, item_id: &str) -> Result<(), Error> {
+        let item = self
+            .find_item(item_id)
+            .ok_or_else(|| Error::ItemNotFound(item_id.to_owned()))?;
+        if item.archived_at.is_none() {
+            return Ok(());
+        }
+        self.store_item(model::Item {
+            archived_at: None,
+            ..item
+        });
+        Ok(())
+    }
+
+    /// Checks the whole batch before storing any of it, so a failing entity
+    /// leaves `self` unchanged.
+    pub fn store_batch(&mut self, batch: use_case::Batch) -> Result<(), Error> {
+        self.can_store_batch(&batch)?;
+        for item in batch.items {
+            self.store_item(item);
+        }
+        for check_list in batch.check_lists {
+            self.store_check_list(check_list)?;
+        }
+        for check in batch.checks {
+            self.store_check(check)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the error that storing the entities of the batch in order
+    /// would, taking the earlier entities of the batch into account.
+    fn can_store_batch(&self, batch: &use_case::Batch) -> Result<(), Error> {
+        let item_ids = batch
+            .items
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<BTreeSet<&str>>();
+        let mut check_list_ids = BTreeSet::new();
+        let mut dates = BTreeSet::new();
+        for check_list in &batch.check_lists {
+            self.can_store_check_list(check_list)?;
+            if !check_list_ids.insert(check_list.id.as_str())
+                || !dates.insert(check_list.date.as_str())
+            {
+                return Err(Error::CheckListAlreadyExists(check_list.id.clone()));
+            }
+        }
+        for check in &batch.checks {
+            if !check_list_ids.contains(check.check_list_id.as_str())
+                && !self.check_lists.contains_key(&check.check_list_id)
+            {
+                return Err(Error::CheckListNotFound(check.check_list_id.clone()));
+            }
+            if !item_ids.contains(check.item_id.as_str())
+                && !self.items.contains_key(&check.item_id)
+            {
+                return Err(Error::ItemNotFound(check.item_id.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the stored check. Storing the same details again keeps the version.
+    pub fn store_check(&mut self, check: model::Check) -> Result<model::Check, Error> {
+        self.can_store_check(&check)?;
+        let kind = match self.find_check(&check.check_list_id, &check.item_id) {
+            Some(previous)
+                if model::Check {
+                    version: previous.version,
+                    ..check.clone()
+                } == previous =>
+            {
+                return Ok(previous)
+            }
+            Some(_) => model::ChangeKind::Updated,
+            None => model::ChangeKind::Created,
+        };
+        let version = self.record(kind, model::Entity::Check(check.clone()));
+        let check = model::Check { version, ..check };
+        self.load(model::Entity::Check(check.clone()));
+        Ok(check)
+    }
+
+    pub fn store_check_list(&mut self, check_list: model::CheckList) -> Result<(), Error> {
+        self.can_store_check_list(&check_list)?;
+        let version = self.record(
+            model::ChangeKind::Created,
+            model::Entity::CheckList(check_list.clone()),
+        );
+        self.load(model::Entity::CheckList(model::CheckList {
+            version,
+            ..check_list
+        }));
+        Ok(())
+    }
+
+    /// Returns the stored item.
+    pub fn store_item(&mut self, item: model::Item) -> model::Item {
+        let kind = if self.items.contains_key(&item.id) {
+            model::ChangeKind::Updated
+        } else {
+            model::ChangeKind::Created
+        };
+        let version = self.record(kind, model::Entity::Item(item.clone()));
+        let item = model::Item { version, ..item };
+        self.items.insert(item.id.clone(), item.clone());
+        item
+    }
+}
+
+fn compare_version(
+    current: Option<model::Entity>,
+    expected_version: Option<u64>,
+) -> Result<(), Error> {
+    let current_version = current.as_ref().map_or(0, model::Entity::version);
+    match expected_version {
+        Some(expected) if expected != current_version => Err(Error::VersionConflict {
+            current: current.map(Box::new),
+            expected,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn page<T: Clone>(map: &BTreeMap<String, T>, after: Option<&str>, limit: usize) -> Vec<T> {
+    let lower = after.map_or(Bound::Unbounded, Bound::Excluded);
+    map.range::<str, _>((lower, Bound::Unbounded))
+        .take(limit)
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct InMemoryStore {
-    check_lists: Vec<model::CheckList>,
-    checks: Vec<model::Check>,
-    items: Vec<model::Item>,
+    indexes: Arc<RwLock<Indexes>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn example() -> Self {
+        let mut indexes = Indexes::default();
+        for check_list in [
+            model::CheckList {
+                id: "1".to_owned(),
+                date: "2020-01-02".to_owned(),
+                ..Default::default()
+            },
+            model::CheckList {
+                id: "2".to_owned(),
+                date: "2020-01-03".to_owned(),
+                ..Default::default()
+            },
+        ] {
+            indexes
+                .store_check_list(check_list)
+                .expect("example check list");
+        }
+        for item in [
+            model::Item {
+                id: "1".to_owned(),
+                name: "item1".to_owned(),
+                ..Default::default()
+            },
+            model::Item {
+                id: "2".to_owned(),
+                name: "item2".to_owned(),
+                ..Default::default()
+            },
+        ] {
+            indexes.store_item(item);
+        }
+        for check in [
+            model::Check {
+                check_list_id: "1".to_owned(),
+                item_id: "1".to_owned(),
+                ..Default::default()
+            },
+            model::Check {
+                check_list_id: "2".to_owned(),
+                item_id: "2".to_owned(),
+                ..Default::default()
+            },
+        ] {
+            indexes.store_check(check).expect("example check");
+        }
+        Self {
+            indexes: Arc::new(RwLock::new(indexes)),
+        }
+    }
+
+    fn read<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Indexes) -> T,
+    {
+        let indexes = self.indexes.read().map_err(|_| Error::LockPoisoned)?;
+        Ok(f(&indexes))
+    }
+
+    fn write<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Indexes) -> Result<T, Error>,
+    {
+        let mut indexes = self.indexes.write().map_err(|_| Error::LockPoisoned)?;
+        f(&mut indexes)
+    }
 }
 
 #[async_trait]
 impl Store for InMemoryStore {
+    async fn archive_item(
+        &self,
+        item_id: String,
+        archived_at: String,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| indexes.archive_item(&item_id, &archived_at))?)
+    }
+
+    async fn changes_since(
+        &self,
+        revision: u64,
+        limit: usize,
+    ) -> Result<Vec<model::Change>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.changes_since(revision, limit))??)
+    }
+
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.compare_check_version(&check_list_id, &item_id, expected_version)?;
+            indexes.delete_check(&check_list_id, &item_id);
+            Ok(())
+        })?)
+    }
+
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.compare_item_version(&item_id, expected_version)?;
+            indexes.purge_item(&item_id);
+            Ok(())
+        })?)
+    }
+
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.compare_check_version(
+                &check.check_list_id,
+                &check.item_id,
+                expected_version,
+            )?;
+            indexes.store_check(check)
+        })?)
+    }
+
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<u64>,
+        sequence: Option<u64>,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.compare_client_sequence(&client_id, expected)?;
+            match sequence {
+                Some(sequence) => indexes.store_client_sequence(client_id, sequence),
+                None => indexes.delete_client_sequence(&client_id),
+            }
+            Ok(())
+        })?)
+    }
+
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.compare_item_version(&item.id, expected_version)?;
+            Ok(indexes.store_item(item))
+        })?)
+    }
+
+    async fn delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.delete_check(&check_list_id, &item_id);
+            Ok(())
+        })?)
+    }
+
     async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, use_case::Error> {
-        Ok(self.check_lists.clone())
+        Ok(self.read(Indexes::find_all_check_lists)?)
     }
 
     async fn find_all_checks(&self) -> Result<Vec<model::Check>, use_case::Error> {
-        Ok(self.checks.clone())
+        Ok(self.read(Indexes::find_all_checks)?)
     }
 
     async fn find_all_items(&self) -> Result<Vec<model::Item>, use_case::Error> {
-        Ok(self.items.clone())
+        Ok(self.read(Indexes::find_all_items)?)
+    }
+
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_check_lists(after.as_deref(), limit))?)
     }
 
     async fn find_checks_by_check_list_id(
         &self,
         check_list_id: String,
     ) -> Result<Vec<model::Check>, use_case::Error> {
-        Ok(self
-            .checks
-            .iter()
-            .filter(|check| check.check_list_id == check_list_id)
-            .cloned()
-            .collect())
+        Ok(self.read(|indexes| indexes.find_checks_by_check_list_id(&check_list_id))?)
     }
 
     async fn find_checks_by_item_id(
         &self,
         item_id: String,
     ) -> Result<Vec<model::Check>, use_case::Error> {
-        Ok(self
-            .checks
-            .iter()
-            .filter(|check| check.item_id == item_id)
-            .cloned()
-            .collect())
+        Ok(self.read(|indexes| indexes.find_checks_by_item_id(&item_id))?)
+    }
+
+    async fn find_client_sequence(
+        &self,
+        client_id: String,
+    ) -> Result<Option<u64>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_client_sequence(&client_id))?)
+    }
+
+    async fn find_current_revision(&self) -> Result<u64, use_case::Error> {
+        Ok(self.read(Indexes::find_current_revision)?)
+    }
+
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_items(after.as_deref(), limit))?)
+    }
+
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, use_case::Error> {
+        Ok(self.read(|indexes| indexes.find_user(&user_id))?)
+    }
+
+    async fn flush(&self) -> Result<(), use_case::Error> {
+        Ok(())
+    }
+
+    async fn purge_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        self.compare_and_purge_item(item_id, None).await
+    }
+
+    async fn restore_item(&self, item_id: String) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| indexes.restore_item(&item_id))?)
+    }
+
+    async fn store_batch(&self, batch: use_case::Batch) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| indexes.store_batch(batch))?)
+    }
+
+    async fn store_check(&self, check: model::Check) -> Result<(), use_case::Error> {
+        self.write(|indexes| indexes.store_check(check))?;
+        Ok(())
+    }
+
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| indexes.store_check_list(check_list))?)
+    }
+
+    async fn store_client_sequence(
+        &self,
+        client_id: String,
+        sequence: u64,
+    ) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.store_client_sequence(client_id, sequence);
+            Ok(())
+        })?)
+    }
+
+    async fn store_item(&self, item: model::Item) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.store_item(item);
+            Ok(())
+        })?)
+    }
+
+    async fn store_user(&self, user: model::User) -> Result<(), use_case::Error> {
+        Ok(self.write(|indexes| {
+            indexes.store_user(user);
+            Ok(())
+        })?)
     }
 }
 
-impl InMemoryStore {
-    pub fn example() -> Self {
-        Self {
-            check_lists: vec![
-                model::CheckList {
-                    id: "1".to_owned(),
-                    date: "2020-01-02".to_owned(),
-                },
-                model::CheckList {
-                    id: "2".to_owned(),
-                    date: "2020-01-03".to_owned(),
-                },
-            ],
-            checks: vec![
-                model::Check {
-                    check_list_id: "1".to_owned(),
-                    item_id: "1".to_owned(),
-                },
-                model::Check {
-                    check_list_id: "2".to_owned(),
-                    item_id: "2".to_owned(),
-                },
-            ],
-            items: vec![
-                model::Item {
-                    id: "1".to_owned(),
-                    name: "item1".to_owned(),
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::store_conformance;
+
+    use super::*;
+
+    #[test]
+    fn test_indexes() -> anyhow::Result<()> {
+        let mut indexes = Indexes::default();
+        indexes.store_check_list(model::CheckList {
+            id: "1".to_owned(),
+            date: "2020-01-02".to_owned(),
+            ..Default::default()
+        })?;
+        indexes.store_item(model::Item {
+            id: "2".to_owned(),
+            name: "item2".to_owned(),
+            ..Default::default()
+        });
+        indexes.store_check(model::Check {
+            check_list_id: "1".to_owned(),
+            item_id: "2".to_owned(),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            indexes.find_check_list_by_date("2020-01-02"),
+            Some(model::CheckList {
+                id: "1".to_owned(),
+                date: "2020-01-02".to_owned(),
+                version: 1,
+            })
+        );
+        assert!(indexes.contains_check("1", "2"));
+        assert_eq!(indexes.find_checks_by_check_list_id("1").len(), 1);
+        assert_eq!(indexes.find_checks_by_item_id("2").len(), 1);
+
+        indexes.delete_check("1", "2");
+        assert!(!indexes.contains_check("1", "2"));
+        assert_eq!(indexes.find_checks_by_check_list_id("1"), vec![]);
+        assert_eq!(indexes.find_checks_by_item_id("2"), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_batch() -> anyhow::Result<()> {
+        let check_list = |id: &str, date: &str| model::CheckList {
+            id: id.to_owned(),
+            date: date.to_owned(),
+            ..Default::default()
+        };
+        let check = |check_list_id: &str, item_id: &str| model::Check {
+            check_list_id: check_list_id.to_owned(),
+            item_id: item_id.to_owned(),
+            ..Default::default()
+        };
+        let mut indexes = Indexes::default();
+        // a check of an item and a check list of the same batch
+        indexes.store_batch(use_case::Batch {
+            items: vec![model::Item {
+                id: "1".to_owned(),
+                name: "item1".to_owned(),
+                ..Default::default()
+            }],
+            check_lists: vec![check_list("1", "2020-01-02")],
+            checks: vec![check("1", "1")],
+        })?;
+        assert!(indexes.contains_check("1", "1"));
+        let revision = indexes.revision;
+
+        for (batch, expected) in [
+            (
+                use_case::Batch {
+                    check_lists: vec![check_list("2", "2020-01-03"), check_list("3", "2020-01-03")],
+                    ..Default::default()
                 },
-                model::Item {
-                    id: "2".to_owned(),
-                    name: "item2".to_owned(),
+                "check list already exists 3",
+            ),
+            (
+                use_case::Batch {
+                    check_lists: vec![check_list("2", "2020-01-03")],
+                    checks: vec![check("2", "1"), check("2", "2")],
+                    ..Default::default()
                 },
-            ],
+                "item not found 2",
+            ),
+        ] {
+            assert_eq!(
+                indexes.store_batch(batch).map_err(|e| e.to_string()),
+                Err(expected.to_owned())
+            );
+            assert_eq!(indexes.revision, revision, "{}", expected);
+            assert_eq!(indexes.find_check_list_by_date("2020-01-03"), None);
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_retention() -> anyhow::Result<()> {
+        let mut indexes = Indexes::default();
+        for i in 0..=MAX_CHANGES {
+            indexes.store_item(model::Item {
+                id: "1".to_owned(),
+                name: format!("item{}", i),
+                ..Default::default()
+            });
+        }
+        assert!(matches!(
+            indexes.changes_since(0, 1),
+            Err(Error::RevisionExpired(0))
+        ));
+        let changes = indexes.changes_since(1, MAX_CHANGES + 1)?;
+        assert_eq!(changes.len(), MAX_CHANGES);
+        assert_eq!(changes[0].revision, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conformance() -> anyhow::Result<()> {
+        store_conformance::run(|| async { Ok(InMemoryStore::new()) }).await
+    }
+
+    #[tokio::test]
+    async fn test_clone() -> anyhow::Result<()> {
+        let store = InMemoryStore::new();
+        store
+            .store_item(model::Item {
+                id: "1".to_owned(),
+                name: "item1".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        // clones share the same data
+        let cloned = store.clone();
+        assert_eq!(cloned.find_all_items().await?.len(), 1);
+        Ok(())
     }
 }
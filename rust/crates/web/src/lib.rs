@@ -0,0 +1,8 @@
+pub mod app;
+pub mod handler;
+pub mod infra;
+pub mod model;
+pub mod shutdown;
+#[cfg(test)]
+mod test_utils;
+pub mod use_case;
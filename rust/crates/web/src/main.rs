@@ -1,22 +1,386 @@
-mod app;
-mod handler;
-mod infra;
-mod model;
 #[cfg(test)]
+#[path = "test_utils/http.rs"]
 mod test_utils;
-mod use_case;
 
-use app::App;
+use std::{net::SocketAddr, str::FromStr as _, sync::Arc, time::Duration};
+
 use axum::Server;
-use handler::route;
+use firestore_path::{DatabaseId, DatabaseName, ProjectId};
+use rand::{rngs::StdRng, SeedableRng as _};
+use time::OffsetDateTime;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use web::{
+    app::App,
+    handler::{
+        api::openapi,
+        graphql::{GraphQLLimits, GraphQLSchema, IdempotencyKeys, PersistedQueries},
+        route_with, HttpOptions,
+    },
+    infra::{
+        file_store::{FileStore, FileStoreOptions},
+        firestore::client::Client,
+        firestore_idempotency_key_store::FirestoreIdempotencyKeyStore,
+        firestore_store::FirestoreStore,
+        idempotency_key_store::InMemoryIdempotencyKeyStore,
+        logging,
+        sqlite_store::SqliteStore,
+        store::InMemoryStore,
+    },
+    shutdown::HasShutdown as _,
+    use_case::{
+        export::{Format, WriteSink},
+        Store,
+    },
+};
+
+const USAGE: &str = "usage:
+  web [serve]
+  web export [csv|json] > FILE
+  web import csv|json [--dry-run] < FILE
+  web seed [--days N]
+  web schema
+  web openapi
+  web users add USER_ID < PASSWORD
+  web users reset-password USER_ID < PASSWORD
+
+The store is configured by STORE (memory, file, sqlite or firestore) and its
+variables, as for the server. The server limits GraphQL requests by
+GRAPHQL_MAX_DEPTH, GRAPHQL_MAX_COMPLEXITY and GRAPHQL_RATE_LIMIT (requests per
+minute per bearer token, otherwise per client address; 600 by default, 0 for no
+limit). Behind GRAPHQL_TRUSTED_PROXIES proxies (0 by default, 1 on Cloud Run),
+the client address is read from X-Forwarded-For. With GRAPHQL_ALLOW_LIST set
+to a manifest file (a JSON object of queries by their sha256 hash), only those
+queries can be executed; otherwise any query can be persisted automatically.
+
+A GraphQL mutation with an Idempotency-Key header (or idempotencyKey argument)
+runs once; a retry within IDEMPOTENCY_KEY_TTL seconds (86400 by default) gets
+the original response. A running mutation holds its key for
+IDEMPOTENCY_KEY_LEASE seconds (60 by default), which must be longer than
+HTTP_REQUEST_TIMEOUT. The keys are stored in Firestore with STORE=firestore, in
+memory otherwise.
+
+CORS_ALLOWED_ORIGINS is a comma-separated list of the origins (or *) allowed to
+call the server from a browser. Request bodies are limited to
+HTTP_MAX_BODY_BYTES (2 MiB by default) and requests to HTTP_REQUEST_TIMEOUT
+seconds (30 by default).
+
+Logs are written to stderr as JSON lines, up to LOG_LEVEL (error, warn, info,
+debug or trace; info by default). Store calls and Firestore RPCs are debug.
+
+On SIGTERM or SIGINT the server fails /readyz for SHUTDOWN_READINESS_DELAY
+seconds (5 by default), then drains the open requests for up to
+SHUTDOWN_DRAIN_TIMEOUT seconds (30 by default), then flushes the store.";
+
+const SEED_DAYS: u32 = 30;
+
+const GRAPHQL_RATE_LIMIT: u32 = 600;
+
+/// Connects to the database of PROJECT_ID.
+async fn firestore_client() -> anyhow::Result<Client> {
+    let env = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+    let project_id = env("PROJECT_ID").ok_or_else(|| anyhow::anyhow!("PROJECT_ID"))?;
+    let endpoint =
+        env("FIRESTORE_ENDPOINT").unwrap_or_else(|| "https://firestore.googleapis.com".to_owned());
+    Ok(Client::new(
+        DatabaseName::new(
+            ProjectId::from_str(&project_id)?,
+            DatabaseId::from_str("(default)")?,
+        ),
+        endpoint,
+    )
+    .await?)
+}
+
+/// The store configured by STORE, with its Firestore client if any.
+struct Backend {
+    store: Arc<dyn Store + Send + Sync>,
+    firestore_client: Option<Client>,
+}
+
+/// Opens the store configured by the environment. Every subcommand uses it.
+async fn store() -> anyhow::Result<Arc<dyn Store + Send + Sync>> {
+    Ok(backend().await?.store)
+}
+
+async fn backend() -> anyhow::Result<Backend> {
+    let env = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+    let store: Arc<dyn Store + Send + Sync> = match env("STORE").as_deref().unwrap_or("memory") {
+        "file" => Arc::new(FileStore::open(
+            env("FILE_STORE_PATH").unwrap_or_else(|| "kireta.jsonl".to_owned()),
+            FileStoreOptions {
+                fsync_policy: env("FILE_STORE_FSYNC")
+                    .map(|s| s.parse())
+                    .transpose()?
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+        )?),
+        "firestore" => {
+            let client = firestore_client().await?;
+            return Ok(Backend {
+                store: Arc::new(FirestoreStore::new(client.clone())),
+                firestore_client: Some(client),
+            });
+        }
+        "memory" => Arc::new(InMemoryStore::example()),
+        "sqlite" => Arc::new(SqliteStore::open(
+            env("SQLITE_PATH").unwrap_or_else(|| "kireta.sqlite3".to_owned()),
+        )?),
+        store => anyhow::bail!("unknown STORE {}", store),
+    };
+    Ok(Backend {
+        store,
+        firestore_client: None,
+    })
+}
+
+fn graphql_limits() -> anyhow::Result<GraphQLLimits> {
+    let env = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+    let default = GraphQLLimits::default();
+    Ok(GraphQLLimits {
+        max_depth: env("GRAPHQL_MAX_DEPTH")
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or(default.max_depth),
+        max_complexity: env("GRAPHQL_MAX_COMPLEXITY")
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or(default.max_complexity),
+        rate_limit: match env("GRAPHQL_RATE_LIMIT") {
+            Some(s) => Some(s.parse::<u32>()?).filter(|rate_limit| *rate_limit > 0),
+            None => Some(GRAPHQL_RATE_LIMIT),
+        },
+        trusted_proxies: env("GRAPHQL_TRUSTED_PROXIES")
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or(default.trusted_proxies),
+    })
+}
+
+fn persisted_queries() -> anyhow::Result<PersistedQueries> {
+    Ok(match std::env::var("GRAPHQL_ALLOW_LIST") {
+        Ok(path) if !path.is_empty() => {
+            PersistedQueries::allow_list(&std::fs::read_to_string(path)?)?
+        }
+        _ => PersistedQueries::default(),
+    })
+}
+
+/// Shares the keys between instances if the store does, through the client of
+/// the store.
+fn idempotency_keys(firestore_client: Option<Client>) -> anyhow::Result<IdempotencyKeys> {
+    let ttl = seconds("IDEMPOTENCY_KEY_TTL", 24 * 60 * 60)?;
+    let lease = seconds("IDEMPOTENCY_KEY_LEASE", 60)?;
+    Ok(match firestore_client {
+        Some(client) => {
+            IdempotencyKeys::new(Arc::new(FirestoreIdempotencyKeyStore::new(client)), ttl)
+        }
+        None => IdempotencyKeys::new(Arc::new(InMemoryIdempotencyKeyStore::new()), ttl),
+    }
+    .with_lease(lease))
+}
+
+fn http_options() -> anyhow::Result<HttpOptions> {
+    let env = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+    let default = HttpOptions::default();
+    Ok(HttpOptions {
+        allowed_origins: env("CORS_ALLOWED_ORIGINS")
+            .map(|origins| {
+                origins
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|origin| !origin.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            })
+            .unwrap_or(default.allowed_origins),
+        max_body_bytes: env("HTTP_MAX_BODY_BYTES")
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or(default.max_body_bytes),
+        request_timeout: seconds("HTTP_REQUEST_TIMEOUT", default.request_timeout.as_secs())?,
+    })
+}
+
+/// Reads a number of seconds, e.g. `SHUTDOWN_DRAIN_TIMEOUT=30`.
+fn seconds(key: &str, default: u64) -> anyhow::Result<Duration> {
+    Ok(Duration::from_secs(match std::env::var(key) {
+        Ok(value) if !value.is_empty() => value.parse()?,
+        _ => default,
+    }))
+}
+
+async fn serve() -> anyhow::Result<()> {
+    let readiness_delay = seconds("SHUTDOWN_READINESS_DELAY", 5)?;
+    let drain_timeout = seconds("SHUTDOWN_DRAIN_TIMEOUT", 30)?;
+    let Backend {
+        store,
+        firestore_client,
+    } = backend().await?;
+    let app = App::new(store.clone())
+        .with_graphql_limits(graphql_limits()?)
+        .with_persisted_queries(persisted_queries()?)
+        .with_idempotency_keys(idempotency_keys(firestore_client)?);
+    let shutdown = app.shutdown().clone();
+    let address = "0.0.0.0:3000".parse()?;
+    tracing::info!(%address, "listening");
+    let (draining_sender, draining) = tokio::sync::oneshot::channel();
+    let server = Server::bind(&address)
+        .serve(
+            route_with(http_options()?)
+                .with_state(app)
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            if let Err(e) = web::shutdown::signal().await {
+                tracing::error!(error = %e, "cannot listen for shutdown signals");
+                return std::future::pending().await;
+            }
+            tracing::info!(?readiness_delay, "shutting down, readiness is failing");
+            shutdown.begin();
+            tokio::time::sleep(readiness_delay).await;
+            tracing::info!(?drain_timeout, "draining connections");
+            let _ = draining_sender.send(());
+        });
+    let drain_timed_out = async {
+        match draining.await {
+            Ok(()) => tokio::time::sleep(drain_timeout).await,
+            // the server has stopped without a signal
+            Err(_) => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = server => result?,
+        _ = drain_timed_out => tracing::warn!("drain timed out, closing the remaining connections"),
+    }
+    store.flush().await?;
+    tracing::info!("shut down");
+    Ok(())
+}
+
+/// Writes the export to stdout.
+async fn export(format: Format) -> anyhow::Result<()> {
+    let store = store().await?;
+    let mut sink = WriteSink(tokio::io::stdout());
+    web::use_case::export::export(store.as_ref(), format, &mut sink).await?;
+    sink.0.flush().await?;
+    Ok(())
+}
+
+/// Imports stdin and prints the summary. Fails if any row is invalid.
+async fn import(format: Format, dry_run: bool) -> anyhow::Result<()> {
+    let store = store().await?;
+    let mut input = String::new();
+    tokio::io::stdin().read_to_string(&mut input).await?;
+    let summary = web::use_case::import::import(store.as_ref(), format, &input, dry_run).await?;
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    if !summary.errors.is_empty() {
+        anyhow::bail!("{} errors, nothing imported", summary.errors.len());
+    }
+    Ok(())
+}
+
+/// Stores a check list with random checks for each of the last `days` days.
+async fn seed(days: u32) -> anyhow::Result<()> {
+    let store = store().await?;
+    let batch = web::use_case::seed::seed(
+        store.as_ref(),
+        &mut StdRng::from_entropy(),
+        days,
+        OffsetDateTime::now_utc().date(),
+    )
+    .await?;
+    println!(
+        "seeded {} items, {} check lists, {} checks",
+        batch.items.len(),
+        batch.check_lists.len(),
+        batch.checks.len()
+    );
+    Ok(())
+}
+
+/// Prints the GraphQL SDL.
+fn schema() {
+    print!("{}", GraphQLSchema::new().sdl());
+}
+
+/// Prints the OpenAPI document of the REST API.
+fn print_openapi() -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&openapi())?);
+    Ok(())
+}
+
+/// Reads the password from the first line of stdin, so it does not show up in
+/// the process list or the shell history.
+async fn read_password() -> anyhow::Result<String> {
+    let mut input = String::new();
+    tokio::io::stdin().read_to_string(&mut input).await?;
+    Ok(input.lines().next().unwrap_or_default().to_owned())
+}
+
+async fn add_user(user_id: &str) -> anyhow::Result<()> {
+    let store = store().await?;
+    let password = read_password().await?;
+    web::use_case::user::add_user(
+        store.as_ref(),
+        user_id.to_owned(),
+        &password,
+        argon2::Params::default(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn reset_password(user_id: &str) -> anyhow::Result<()> {
+    let store = store().await?;
+    let password = read_password().await?;
+    web::use_case::user::reset_password(
+        store.as_ref(),
+        user_id.to_owned(),
+        &password,
+        argon2::Params::default(),
+    )
+    .await?;
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let app = route().with_state(App::example());
-    Server::bind(&"0.0.0.0:3000".parse()?)
-        .serve(app.into_make_service())
-        .await?;
-    Ok(())
+    let log_level = match std::env::var("LOG_LEVEL") {
+        Ok(level) if !level.is_empty() => level
+            .parse::<tracing::Level>()
+            .map_err(|_| anyhow::anyhow!("unknown LOG_LEVEL {}", level))?,
+        _ => tracing::Level::INFO,
+    };
+    tracing::subscriber::set_global_default(logging::subscriber(std::io::stderr, log_level))?;
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] | ["serve"] => serve().await,
+        ["export"] => export(Format::default()).await,
+        ["export", format] => export(format.parse()?).await,
+        ["import", format] => import(format.parse()?, false).await,
+        ["import", format, "--dry-run"] => import(format.parse()?, true).await,
+        ["seed"] => seed(SEED_DAYS).await,
+        ["seed", "--days", days] => seed(days.parse()?).await,
+        ["schema"] => {
+            schema();
+            Ok(())
+        }
+        ["openapi"] => print_openapi(),
+        ["users", "add", user_id] => add_user(user_id).await,
+        ["users", "reset-password", user_id] => reset_password(user_id).await,
+        ["help" | "--help" | "-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => anyhow::bail!("{}", USAGE),
+    }
 }
 
 #[cfg(test)]
@@ -33,9 +397,9 @@ mod tests {
     };
     use serde_json::json;
 
-    use crate::test_utils::{request, send_request, ResponseExt, StatusCode};
+    use web::{app::App, handler::route};
 
-    use super::*;
+    use crate::test_utils::{request, send_request, ResponseExt, StatusCode};
 
     macro_rules! test_query3 {
         ($q:tt, $e:tt) => {
@@ -231,6 +595,18 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_bearer_unauthorized() -> anyhow::Result<()> {
+        let app = route().with_state(App::example());
+        let request = request("POST", "/graphql", r#"{"query":"query { bearer }"}"#)?;
+        let response = send_request(app, request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&response.into_body_as_string().await?)?;
+        assert_eq!(body["errors"][0]["message"], "unauthorized bearer");
+        assert_eq!(body["errors"][0]["extensions"]["code"], "UNAUTHORIZED");
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_hello() -> anyhow::Result<()> {
         test!(
@@ -308,7 +684,9 @@ mod tests {
                         "fields": [
                             { "name": "id" },
                             { "name": "date" },
-                            { "name": "checkedItems" }
+                            { "name": "version" },
+                            { "name": "checkedItems" },
+                            { "name": "checks" }
                         ]
                     }
                 }
@@ -355,6 +733,40 @@ mod tests {
         .await
     }
 
+    #[tokio::test]
+    async fn test_changes() -> anyhow::Result<()> {
+        // dummy data
+        test_query3!(
+            {
+                "query": r#"query {
+                    changes(since: 3, limit: 1) {
+                        entity {
+                            __typename
+                            ... on Item { id, name }
+                        }
+                        kind
+                        revision
+                    }
+                }"#
+            },
+            {
+                "data": {
+                    "changes": [
+                        {
+                            "entity": {
+                                "__typename": "Item",
+                                "id": "2",
+                                "name": "item2"
+                            },
+                            "kind": "CREATED",
+                            "revision": 4
+                        }
+                    ]
+                }
+            }
+        )
+    }
+
     #[tokio::test]
     async fn test_items() -> anyhow::Result<()> {
         // dummy data
@@ -365,6 +777,374 @@ mod tests {
         .await
     }
 
+    #[tokio::test]
+    async fn test_archive_item() -> anyhow::Result<()> {
+        let app = App::example();
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { archiveItem(id: "1") }"#
+            },
+            {
+                "data": {
+                    "archiveItem": "1"
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": "query { items { id } }"
+            },
+            {
+                "data": {
+                    "items": [{ "id": "2" }]
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": "query { checkLists { checkedItems { id } } }"
+            },
+            {
+                "data": {
+                    "checkLists": [
+                        { "checkedItems": [{ "id": "1" }] },
+                        { "checkedItems": [{ "id": "2" }] }
+                    ]
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { restoreItem(id: "1") }"#
+            },
+            {
+                "data": {
+                    "restoreItem": "1"
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": "query { items(includeArchived: true) { archivedAt, id } }"
+            },
+            {
+                "data": {
+                    "items": [
+                        { "archivedAt": null, "id": "1" },
+                        { "archivedAt": null, "id": "2" }
+                    ]
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { purgeItem(id: "1") }"#
+            },
+            {
+                "data": {
+                    "purgeItem": "1"
+                }
+            }
+        )?;
+        test!(
+            app,
+            {
+                "query": "query { checkLists { checkedItems { id } } }"
+            },
+            {
+                "data": {
+                    "checkLists": [
+                        { "checkedItems": [] },
+                        { "checkedItems": [{ "id": "2" }] }
+                    ]
+                }
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn test_update_item() -> anyhow::Result<()> {
+        let app = App::example();
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation {
+                    updateItem(input: { id: "2", category: "bath", emoji: "🧻", note: "2 packs", sortOrder: 1, unit: "roll" }) {
+                        category, emoji, id, name, note, sortOrder, unit
+                    }
+                }"#
+            },
+            {
+                "data": {
+                    "updateItem": {
+                        "category": "bath",
+                        "emoji": "🧻",
+                        "id": "2",
+                        "name": "item2",
+                        "note": "2 packs",
+                        "sortOrder": 1,
+                        "unit": "roll"
+                    }
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { updateItem(input: { id: "2", name: "paper", note: null }) { name, note, unit } }"#
+            },
+            {
+                "data": {
+                    "updateItem": {
+                        "name": "paper",
+                        "note": null,
+                        "unit": "roll"
+                    }
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"query { items(category: "bath") { id } }"#
+            },
+            {
+                "data": {
+                    "items": [{ "id": "2" }]
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": "query { items(orderBy: SORT_ORDER) { id } }"
+            },
+            {
+                "data": {
+                    "items": [{ "id": "2" }, { "id": "1" }]
+                }
+            }
+        )?;
+        let request = request(
+            "POST",
+            "/graphql",
+            json!({ "query": r#"mutation { updateItem(input: { id: "3" }) { id } }"# }).to_string(),
+        )?;
+        let response = send_request(route().with_state(app), request).await?;
+        let body: serde_json::Value = serde_json::from_str(&response.into_body_as_string().await?)?;
+        assert_eq!(body["errors"][0]["message"], "not found item 3");
+        assert_eq!(body["errors"][0]["extensions"]["code"], "NOT_FOUND");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_item() -> anyhow::Result<()> {
+        let app = App::example();
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation {
+                    checkItem(input: { checkListId: "2", itemId: "1", checkedAt: "2020-01-03T04:05:06Z", note: "on sale", price: { amount: 298, currency: "JPY" }, quantity: 1.5, shop: "shop1" }) {
+                        checkListId, checkedAt, itemId, note, price { amount, currency }, quantity, shop
+                    }
+                }"#
+            },
+            {
+                "data": {
+                    "checkItem": {
+                        "checkListId": "2",
+                        "checkedAt": "2020-01-03T04:05:06Z",
+                        "itemId": "1",
+                        "note": "on sale",
+                        "price": { "amount": 298, "currency": "JPY" },
+                        "quantity": 1.5,
+                        "shop": "shop1"
+                    }
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"query { items { checks { checkListId, quantity }, id } }"#
+            },
+            {
+                "data": {
+                    "items": [
+                        {
+                            "checks": [
+                                { "checkListId": "1", "quantity": null },
+                                { "checkListId": "2", "quantity": 1.5 }
+                            ],
+                            "id": "1"
+                        },
+                        {
+                            "checks": [{ "checkListId": "2", "quantity": null }],
+                            "id": "2"
+                        }
+                    ]
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { uncheckItem(checkListId: "2", itemId: "1") }"#
+            },
+            {
+                "data": {
+                    "uncheckItem": "1"
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"query { checkLists { checks { itemId }, id } }"#
+            },
+            {
+                "data": {
+                    "checkLists": [
+                        { "checks": [{ "itemId": "1" }], "id": "1" },
+                        { "checks": [{ "itemId": "2" }], "id": "2" }
+                    ]
+                }
+            }
+        )?;
+        let request = request(
+            "POST",
+            "/graphql",
+            json!({ "query": r#"mutation { checkItem(input: { checkListId: "1", itemId: "1", price: { amount: 1, currency: "yen" } }) { itemId } }"# }).to_string(),
+        )?;
+        let response = send_request(route().with_state(app), request).await?;
+        let body: serde_json::Value = serde_json::from_str(&response.into_body_as_string().await?)?;
+        assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID_INPUT");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expected_version() -> anyhow::Result<()> {
+        let app = App::example();
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { updateItem(input: { id: "1", name: "milk", expectedVersion: 3 }) { name, version } }"#
+            },
+            {
+                "data": {
+                    "updateItem": { "name": "milk", "version": 7 }
+                }
+            }
+        )?;
+        let conflict = request(
+            "POST",
+            "/graphql",
+            json!({ "query": r#"mutation { updateItem(input: { id: "1", name: "eggs", expectedVersion: 3 }) { name } }"# }).to_string(),
+        )?;
+        let response = send_request(route().with_state(app.clone()), conflict).await?;
+        let body: serde_json::Value = serde_json::from_str(&response.into_body_as_string().await?)?;
+        assert_eq!(
+            body["errors"][0]["extensions"],
+            json!({
+                "code": "CONFLICT",
+                "current": {
+                    "type": "item",
+                    "payload": { "id": "1", "name": "milk", "version": 7 }
+                },
+                "expectedVersion": 3
+            })
+        );
+
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation { checkItem(input: { checkListId: "2", itemId: "1", checkedAt: "2020-01-03T04:05:06Z", expectedVersion: 0 }) { version } }"#
+            },
+            {
+                "data": {
+                    "checkItem": { "version": 8 }
+                }
+            }
+        )?;
+        let conflict = request(
+            "POST",
+            "/graphql",
+            json!({ "query": r#"mutation { uncheckItem(checkListId: "2", itemId: "1", expectedVersion: 7) }"# }).to_string(),
+        )?;
+        let response = send_request(route().with_state(app.clone()), conflict).await?;
+        let body: serde_json::Value = serde_json::from_str(&response.into_body_as_string().await?)?;
+        assert_eq!(body["errors"][0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(
+            body["errors"][0]["extensions"]["current"]["payload"]["version"],
+            8
+        );
+        test!(
+            app,
+            {
+                "query": r#"mutation { uncheckItem(checkListId: "2", itemId: "1", expectedVersion: 8) }"#
+            },
+            {
+                "data": {
+                    "uncheckItem": "1"
+                }
+            }
+        )?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_report() -> anyhow::Result<()> {
+        let app = App::example();
+        test!(
+            app.clone(),
+            {
+                "query": r#"mutation {
+                    checkItem(input: { checkListId: "1", itemId: "1", checkedAt: "2020-01-02T09:00:00Z", price: { amount: 298, currency: "JPY" }, quantity: 3, shop: "shop1" }) { itemId }
+                }"#
+            },
+            {
+                "data": {
+                    "checkItem": { "itemId": "1" }
+                }
+            }
+        )?;
+        test!(
+            app.clone(),
+            {
+                "query": r#"query {
+                    report(from: "2020-01-01", to: "2020-01-30", groupBy: SHOP) {
+                        consumption { item { id }, quantity, unitsPer30Days },
+                        spend { amount, checks, currency, key },
+                        yearOverYear { amount, currency, key, previousAmount }
+                    }
+                }"#
+            },
+            {
+                "data": {
+                    "report": {
+                        "consumption": [
+                            { "item": { "id": "1" }, "quantity": 3.0, "unitsPer30Days": 3.0 },
+                            { "item": { "id": "2" }, "quantity": 1.0, "unitsPer30Days": 1.0 }
+                        ],
+                        "spend": [
+                            { "amount": 298, "checks": 1, "currency": "JPY", "key": "shop1" }
+                        ],
+                        "yearOverYear": [
+                            { "amount": 298, "currency": "JPY", "key": "shop1", "previousAmount": 0 }
+                        ]
+                    }
+                }
+            }
+        )?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_items_checked_check_lists() -> anyhow::Result<()> {
         // dummy data
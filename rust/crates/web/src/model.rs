@@ -2,28 +2,149 @@ use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
-use time::Date;
+use time::{
+    format_description::well_known::{Iso8601, Rfc3339},
+    Date, OffsetDateTime,
+};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// A value that breaks the rules below. Stores assume validated values.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("currency {0}")]
+    Currency(String),
+    #[error("date {0}")]
+    Date(String),
+    #[error("id {0:?}")]
+    Id(String),
+    #[error("name is empty")]
+    Name,
+    #[error("price is negative")]
+    Price,
+    #[error("quantity {0}")]
+    Quantity(String),
+    #[error("timestamp {0}")]
+    Timestamp(String),
+}
+
+/// 1 to 128 characters of ASCII letters, digits, `-` and `_`, e.g. a UUID.
+pub fn validate_id(id: &str) -> Result<(), ValidationError> {
+    if id.is_empty()
+        || id.len() > 128
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ValidationError::Id(id.to_owned()));
+    }
+    Ok(())
+}
+
+/// YYYY-MM-DD
+pub fn validate_date(date: &str) -> Result<(), ValidationError> {
+    Date::parse(date, &Iso8601::DATE)
+        .ok()
+        .filter(|_| date.len() == 10)
+        .map(|_| ())
+        .ok_or_else(|| ValidationError::Date(date.to_owned()))
+}
+
+/// RFC 3339
+pub fn validate_timestamp(timestamp: &str) -> Result<(), ValidationError> {
+    OffsetDateTime::parse(timestamp, &Rfc3339)
+        .map(|_| ())
+        .map_err(|_| ValidationError::Timestamp(timestamp.to_owned()))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub entity: Entity,
+    pub kind: ChangeKind,
+    pub revision: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Check {
     pub check_list_id: String,
     pub item_id: String,
+    /// RFC 3339
+    pub checked_at: Option<String>,
+    pub note: Option<String>,
+    pub price: Option<Price>,
+    /// in the item's unit
+    pub quantity: Option<f64>,
+    pub shop: Option<String>,
+    /// See [`Item::version`].
+    pub version: u64,
+}
+
+impl Check {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_id(&self.check_list_id)?;
+        validate_id(&self.item_id)?;
+        if let Some(checked_at) = &self.checked_at {
+            validate_timestamp(checked_at)?;
+        }
+        if let Some(price) = &self.price {
+            price.validate()?;
+        }
+        if let Some(quantity) = self.quantity {
+            if !quantity.is_finite() || quantity < 0_f64 {
+                return Err(ValidationError::Quantity(quantity.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn with_version(self, version: u64) -> Self {
+        Self { version, ..self }
+    }
 }
 
 impl Distribution<Check> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Check {
+        const SHOPS: [&str; 4] = ["supermarket", "drugstore", "convenience store", "market"];
+        let quantity = rng.gen_range(1..=3);
         Check {
             check_list_id: Uuid::from_bytes(rng.gen()).to_string(),
             item_id: Uuid::from_bytes(rng.gen()).to_string(),
+            price: rng.gen_bool(0.8).then(|| Price {
+                amount: rng.gen_range(1..=50) * 20 * quantity,
+                currency: "JPY".to_owned(),
+            }),
+            quantity: Some(quantity as f64),
+            shop: rng
+                .gen_bool(0.8)
+                .then(|| SHOPS[rng.gen_range(0..SHOPS.len())].to_owned()),
+            ..Default::default()
         }
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct CheckList {
     pub id: String,
     pub date: String,
+    /// See [`Item::version`].
+    pub version: u64,
+}
+
+impl CheckList {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_id(&self.id)?;
+        validate_date(&self.date)
+    }
+
+    pub fn with_version(self, version: u64) -> Self {
+        Self { version, ..self }
+    }
 }
 
 impl Distribution<CheckList> for Standard {
@@ -31,23 +152,134 @@ impl Distribution<CheckList> for Standard {
         CheckList {
             id: Uuid::from_bytes(rng.gen()).to_string(),
             date: rng.gen::<Date>().to_string(),
+            ..Default::default()
         }
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entity {
+    Check(Check),
+    CheckList(CheckList),
+    Item(Item),
+}
+
+impl Entity {
+    pub fn version(&self) -> u64 {
+        match self {
+            Self::Check(check) => check.version,
+            Self::CheckList(check_list) => check_list.version,
+            Self::Item(item) => item.version,
+        }
+    }
+
+    pub fn with_version(self, version: u64) -> Self {
+        match self {
+            Self::Check(check) => Self::Check(check.with_version(version)),
+            Self::CheckList(check_list) => Self::CheckList(check_list.with_version(version)),
+            Self::Item(item) => Self::Item(item.with_version(version)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Item {
     pub id: String,
     pub name: String,
+    /// RFC 3339. Archived items are hidden from `items` but kept in history.
+    pub archived_at: Option<String>,
+    pub category: Option<String>,
+    /// an emoji or an icon name
+    pub emoji: Option<String>,
+    pub note: Option<String>,
+    /// user-defined display order, ascending
+    pub sort_order: Option<i64>,
+    /// e.g. "roll", "bottle", "kg"
+    pub unit: Option<String>,
+    /// The revision of the last write to the entity, for optimistic
+    /// concurrency. Stores assign it on every write and ignore the version of
+    /// the entity being written. 0 only for an entity that does not exist; an
+    /// entity last written before versions were recorded has a version too.
+    pub version: u64,
+}
+
+impl Item {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_id(&self.id)?;
+        if self.name.trim().is_empty() {
+            return Err(ValidationError::Name);
+        }
+        if let Some(archived_at) = &self.archived_at {
+            validate_timestamp(archived_at)?;
+        }
+        Ok(())
+    }
+
+    pub fn with_version(self, version: u64) -> Self {
+        Self { version, ..self }
+    }
 }
 
 impl Distribution<Item> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Item {
+        // (name, category, emoji, unit)
+        const ITEMS: [(&str, &str, &str, &str); 12] = [
+            ("milk", "dairy", "🥛", "bottle"),
+            ("eggs", "dairy", "🥚", "pack"),
+            ("bread", "bakery", "🍞", "loaf"),
+            ("rice", "grains", "🍚", "kg"),
+            ("bananas", "produce", "🍌", "bunch"),
+            ("onions", "produce", "🧅", "kg"),
+            ("coffee", "drinks", "☕", "bag"),
+            ("toilet paper", "household", "🧻", "roll"),
+            ("dish soap", "household", "🧽", "bottle"),
+            ("shampoo", "personal care", "🧴", "bottle"),
+            ("toothpaste", "personal care", "🪥", "tube"),
+            ("trash bags", "household", "🗑️", "pack"),
+        ];
+        let (name, category, emoji, unit) = ITEMS[rng.gen_range(0..ITEMS.len())];
         Item {
             id: Uuid::from_bytes(rng.gen()).to_string(),
-            // TODO: generate random name
-            name: Uuid::from_bytes(rng.gen()).to_string(),
+            name: name.to_owned(),
+            category: Some(category.to_owned()),
+            emoji: Some(emoji.to_owned()),
+            unit: Some(unit.to_owned()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Price {
+    /// in the currency's minor unit, e.g. cents
+    pub amount: i64,
+    /// ISO 4217, e.g. "JPY"
+    pub currency: String,
+}
+
+impl Price {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.amount < 0 {
+            return Err(ValidationError::Price);
+        }
+        if self.currency.len() != 3 || !self.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(ValidationError::Currency(self.currency.clone()));
         }
+        Ok(())
+    }
+}
+
+/// A user that can sign in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct User {
+    pub id: String,
+    /// An Argon2id hash in the PHC string format
+    pub password_hash: String,
+}
+
+impl User {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_id(&self.id)
     }
 }
 
@@ -57,6 +289,102 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_validate() {
+        let check_list = CheckList {
+            id: "1".to_owned(),
+            date: "2020-01-02".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(check_list.validate(), Ok(()));
+        for date in ["2020-1-2", "2020-02-30", "20200102", ""] {
+            assert_eq!(
+                CheckList {
+                    date: date.to_owned(),
+                    ..check_list.clone()
+                }
+                .validate(),
+                Err(ValidationError::Date(date.to_owned()))
+            );
+        }
+        for id in ["", "a/b", "a b", &"a".repeat(129)] {
+            assert_eq!(
+                CheckList {
+                    id: id.to_owned(),
+                    ..check_list.clone()
+                }
+                .validate(),
+                Err(ValidationError::Id(id.to_owned()))
+            );
+        }
+
+        let item = Item {
+            id: "1".to_owned(),
+            name: "item1".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(item.validate(), Ok(()));
+        assert_eq!(
+            Item {
+                name: " ".to_owned(),
+                ..item.clone()
+            }
+            .validate(),
+            Err(ValidationError::Name)
+        );
+
+        let check = Check {
+            check_list_id: "1".to_owned(),
+            item_id: "1".to_owned(),
+            checked_at: Some("2020-01-02T03:04:05Z".to_owned()),
+            price: Some(Price {
+                amount: 298,
+                currency: "JPY".to_owned(),
+            }),
+            quantity: Some(1.5),
+            ..Default::default()
+        };
+        assert_eq!(check.validate(), Ok(()));
+        assert_eq!(
+            Check {
+                checked_at: Some("2020-01-02".to_owned()),
+                ..check.clone()
+            }
+            .validate(),
+            Err(ValidationError::Timestamp("2020-01-02".to_owned()))
+        );
+        assert_eq!(
+            Check {
+                quantity: Some(f64::NAN),
+                ..check.clone()
+            }
+            .validate(),
+            Err(ValidationError::Quantity("NaN".to_owned()))
+        );
+        assert_eq!(
+            Check {
+                price: Some(Price {
+                    amount: -1,
+                    currency: "JPY".to_owned(),
+                }),
+                ..check.clone()
+            }
+            .validate(),
+            Err(ValidationError::Price)
+        );
+        assert_eq!(
+            Check {
+                price: Some(Price {
+                    amount: 1,
+                    currency: "jpy".to_owned(),
+                }),
+                ..check
+            }
+            .validate(),
+            Err(ValidationError::Currency("jpy".to_owned()))
+        );
+    }
+
     #[test]
     fn test_impl_distribution_check_for_standard() {
         let mut rng = thread_rng();
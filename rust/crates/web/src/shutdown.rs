@@ -0,0 +1,85 @@
+//! Graceful shutdown. On SIGTERM or SIGINT, `/readyz` starts failing so that a
+//! load balancer stops sending requests, then the server stops accepting
+//! connections and drains the in-flight requests, then the store is flushed.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Tells the handlers that the shutdown has begun. Long-lived connections
+/// (e.g. GraphQL subscriptions, of which the schema has none yet) should
+/// close when `begun` resolves.
+#[derive(Clone, Debug)]
+pub struct Shutdown(Arc<watch::Sender<bool>>);
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+
+    pub fn begin(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn has_begun(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves when `begin` is called.
+    pub async fn begun(&self) {
+        let mut receiver = self.0.subscribe();
+        // the sender lives as long as self
+        let _ = receiver.wait_for(|begun| *begun).await;
+    }
+}
+
+pub trait HasShutdown {
+    fn shutdown(&self) -> &Shutdown;
+}
+
+/// Resolves on SIGTERM (e.g. from a container runtime) or SIGINT (Ctrl-C).
+pub async fn signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown() -> anyhow::Result<()> {
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.has_begun());
+        let begun = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.begun().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!begun.is_finished());
+
+        shutdown.begin();
+        tokio::time::timeout(Duration::from_secs(1), begun).await??;
+        assert!(shutdown.has_begun());
+        // resolves at once after the shutdown has begun
+        tokio::time::timeout(Duration::from_secs(1), shutdown.begun()).await?;
+        Ok(())
+    }
+}
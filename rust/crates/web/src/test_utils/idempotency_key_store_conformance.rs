@@ -0,0 +1,122 @@
+//! Behavior every `IdempotencyKeyStore` implementation must share.
+//!
+//! Each backend calls [`run`] with a function that returns an empty store.
+
+use std::{future::Future, time::Duration};
+
+use crate::use_case::idempotency::{IdempotencyKeyStore, IdempotencyRecord};
+
+const LEASE: Duration = Duration::from_secs(60);
+const TTL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn run<F, Fut, S>(new_store: F) -> anyhow::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<S>>,
+    S: IdempotencyKeyStore + Send + Sync,
+{
+    claims(&new_store().await?).await?;
+    release(&new_store().await?).await?;
+    expiry(&new_store().await?).await?;
+    Ok(())
+}
+
+async fn claims<S: IdempotencyKeyStore + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    assert_eq!(
+        store
+            .claim("key1".to_owned(), "request1".to_owned(), LEASE)
+            .await?,
+        None
+    );
+    assert_eq!(
+        store
+            .claim("key1".to_owned(), "request2".to_owned(), LEASE)
+            .await?,
+        Some(IdempotencyRecord {
+            fingerprint: "request1".to_owned(),
+            response: None,
+        }),
+        "a running request holds its key"
+    );
+    store
+        .complete("key1".to_owned(), "response1".to_owned(), TTL)
+        .await?;
+    assert_eq!(
+        store
+            .claim("key1".to_owned(), "request1".to_owned(), LEASE)
+            .await?,
+        Some(IdempotencyRecord {
+            fingerprint: "request1".to_owned(),
+            response: Some("response1".to_owned()),
+        })
+    );
+    assert_eq!(
+        store
+            .claim("key2".to_owned(), "request1".to_owned(), LEASE)
+            .await?,
+        None,
+        "keys are independent"
+    );
+    Ok(())
+}
+
+async fn release<S: IdempotencyKeyStore + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    store
+        .claim("key1".to_owned(), "request1".to_owned(), LEASE)
+        .await?;
+    store.release("key1".to_owned()).await?;
+    assert_eq!(
+        store
+            .claim("key1".to_owned(), "request2".to_owned(), LEASE)
+            .await?,
+        None
+    );
+    // releasing a missing key is a no-op
+    store.release("key2".to_owned()).await?;
+    Ok(())
+}
+
+async fn expiry<S: IdempotencyKeyStore + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    store
+        .claim("key1".to_owned(), "request1".to_owned(), Duration::ZERO)
+        .await?;
+    assert_eq!(
+        store
+            .claim("key1".to_owned(), "request2".to_owned(), LEASE)
+            .await?,
+        None,
+        "a claim that was not completed within its lease must not hold the key"
+    );
+
+    store
+        .claim("key2".to_owned(), "request1".to_owned(), Duration::ZERO)
+        .await?;
+    store
+        .complete("key2".to_owned(), "response1".to_owned(), TTL)
+        .await?;
+    assert_eq!(
+        store
+            .claim("key2".to_owned(), "request1".to_owned(), LEASE)
+            .await?,
+        Some(IdempotencyRecord {
+            fingerprint: "request1".to_owned(),
+            response: Some("response1".to_owned()),
+        }),
+        "a completed claim is kept for the TTL"
+    );
+
+    store
+        .claim("key3".to_owned(), "request1".to_owned(), LEASE)
+        .await?;
+    store
+        .complete("key3".to_owned(), "response1".to_owned(), Duration::ZERO)
+        .await?;
+    assert_eq!(
+        store
+            .claim("key3".to_owned(), "request2".to_owned(), LEASE)
+            .await?,
+        None,
+        "an expired claim must not hold the key"
+    );
+    Ok(())
+}
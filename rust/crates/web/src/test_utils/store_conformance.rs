@@ -0,0 +1,732 @@
+//! Behavior every `Store` implementation must share.
+//!
+//! Each backend calls [`run`] with a function that returns an empty store.
+
+use std::future::Future;
+
+use crate::{
+    model,
+    use_case::{Batch, Error, Store},
+};
+
+pub async fn run<F, Fut, S>(new_store: F) -> anyhow::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<S>>,
+    S: Store + Send + Sync,
+{
+    check_lists(&new_store().await?).await?;
+    items(&new_store().await?).await?;
+    checks(&new_store().await?).await?;
+    check_details(&new_store().await?).await?;
+    pagination(&new_store().await?).await?;
+    client_sequences(&new_store().await?).await?;
+    changes(&new_store().await?).await?;
+    archive(&new_store().await?).await?;
+    purge(&new_store().await?).await?;
+    batch(&new_store().await?).await?;
+    versions(&new_store().await?).await?;
+    users(&new_store().await?).await?;
+    Ok(())
+}
+
+fn check_list(id: &str, date: &str) -> model::CheckList {
+    model::CheckList {
+        id: id.to_owned(),
+        date: date.to_owned(),
+        ..Default::default()
+    }
+}
+
+fn item(id: &str, name: &str) -> model::Item {
+    model::Item {
+        id: id.to_owned(),
+        name: name.to_owned(),
+        ..Default::default()
+    }
+}
+
+fn check(check_list_id: &str, item_id: &str) -> model::Check {
+    model::Check {
+        check_list_id: check_list_id.to_owned(),
+        item_id: item_id.to_owned(),
+        ..Default::default()
+    }
+}
+
+async fn check_lists<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    assert_eq!(store.find_all_check_lists().await?, vec![]);
+
+    store
+        .store_check_list(check_list("2", "2020-01-03"))
+        .await?;
+    store
+        .store_check_list(check_list("1", "2020-01-02"))
+        .await?;
+    assert_eq!(
+        store.find_all_check_lists().await?,
+        vec![
+            check_list("1", "2020-01-02").with_version(2),
+            check_list("2", "2020-01-03").with_version(1)
+        ],
+        "check lists are ordered by id and versioned by revision"
+    );
+
+    assert!(
+        matches!(
+            store.store_check_list(check_list("1", "2020-01-04")).await,
+            Err(Error::AlreadyExists(_))
+        ),
+        "duplicate check list id must be rejected"
+    );
+    assert!(
+        matches!(
+            store.store_check_list(check_list("3", "2020-01-02")).await,
+            Err(Error::AlreadyExists(_))
+        ),
+        "duplicate check list date must be rejected"
+    );
+    assert_eq!(store.find_all_check_lists().await?.len(), 2);
+    Ok(())
+}
+
+async fn items<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    assert_eq!(store.find_all_items().await?, vec![]);
+
+    store.store_item(item("2", "item2")).await?;
+    store.store_item(item("1", "item1")).await?;
+    assert_eq!(
+        store.find_all_items().await?,
+        vec![
+            item("1", "item1").with_version(2),
+            item("2", "item2").with_version(1)
+        ],
+        "items are ordered by id"
+    );
+
+    // store_item updates an existing item
+    store.store_item(item("1", "item1 updated")).await?;
+    assert_eq!(
+        store.find_all_items().await?,
+        vec![
+            item("1", "item1 updated").with_version(3),
+            item("2", "item2").with_version(1)
+        ]
+    );
+
+    // metadata is persisted and cleared
+    let with_metadata = model::Item {
+        category: Some("bath".to_owned()),
+        emoji: Some("🧻".to_owned()),
+        note: Some("2 packs".to_owned()),
+        sort_order: Some(-1),
+        unit: Some("roll".to_owned()),
+        ..item("2", "item2")
+    };
+    store.store_item(with_metadata.clone()).await?;
+    assert_eq!(
+        store.find_items(Some("1".to_owned()), 1).await?,
+        vec![with_metadata.with_version(4)]
+    );
+    store.store_item(item("2", "item2")).await?;
+    assert_eq!(
+        store.find_items(Some("1".to_owned()), 1).await?,
+        vec![item("2", "item2").with_version(5)]
+    );
+    Ok(())
+}
+
+async fn checks<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    store
+        .store_check_list(check_list("1", "2020-01-02"))
+        .await?;
+    store
+        .store_check_list(check_list("2", "2020-01-03"))
+        .await?;
+    store.store_item(item("1", "item1")).await?;
+    store.store_item(item("2", "item2")).await?;
+
+    store.store_check(check("2", "1")).await?;
+    store.store_check(check("1", "2")).await?;
+    store.store_check(check("1", "1")).await?;
+    // storing the same check twice is a no-op
+    store.store_check(check("1", "1")).await?;
+    assert_eq!(
+        store.find_all_checks().await?,
+        vec![
+            check("1", "1").with_version(7),
+            check("1", "2").with_version(6),
+            check("2", "1").with_version(5)
+        ],
+        "checks are ordered by (check_list_id, item_id)"
+    );
+
+    assert_eq!(
+        store.find_checks_by_check_list_id("1".to_owned()).await?,
+        vec![
+            check("1", "1").with_version(7),
+            check("1", "2").with_version(6)
+        ]
+    );
+    assert_eq!(
+        store.find_checks_by_item_id("1".to_owned()).await?,
+        vec![
+            check("1", "1").with_version(7),
+            check("2", "1").with_version(5)
+        ]
+    );
+    assert_eq!(
+        store.find_checks_by_check_list_id("3".to_owned()).await?,
+        vec![]
+    );
+
+    assert!(
+        matches!(
+            store.store_check(check("3", "1")).await,
+            Err(Error::NotFound(_))
+        ),
+        "check for an unknown check list must be rejected"
+    );
+    assert!(
+        matches!(
+            store.store_check(check("1", "3")).await,
+            Err(Error::NotFound(_))
+        ),
+        "check for an unknown item must be rejected"
+    );
+
+    store.delete_check("1".to_owned(), "1".to_owned()).await?;
+    // deleting a missing check is a no-op
+    store.delete_check("1".to_owned(), "1".to_owned()).await?;
+    assert_eq!(
+        store.find_all_checks().await?,
+        vec![
+            check("1", "2").with_version(6),
+            check("2", "1").with_version(5)
+        ]
+    );
+    assert_eq!(
+        store.find_checks_by_item_id("1".to_owned()).await?,
+        vec![check("2", "1").with_version(5)]
+    );
+    Ok(())
+}
+
+async fn check_details<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    store
+        .store_check_list(check_list("1", "2020-01-02"))
+        .await?;
+    store.store_item(item("1", "item1")).await?;
+    let revision = store.find_current_revision().await?;
+
+    let detailed = model::Check {
+        checked_at: Some("2020-01-02T03:04:05Z".to_owned()),
+        note: Some("on sale".to_owned()),
+        price: Some(model::Price {
+            amount: 298,
+            currency: "JPY".to_owned(),
+        }),
+        quantity: Some(1.5),
+        shop: Some("shop1".to_owned()),
+        ..check("1", "1")
+    };
+    store.store_check(check("1", "1")).await?;
+    // storing a check again replaces its details
+    store.store_check(detailed.clone()).await?;
+    // storing the same details again keeps the version
+    store.store_check(detailed.clone()).await?;
+    let detailed = detailed.with_version(revision + 2);
+    assert_eq!(store.find_all_checks().await?, vec![detailed.clone()]);
+    assert_eq!(
+        store.find_checks_by_item_id("1".to_owned()).await?,
+        vec![detailed.clone()]
+    );
+
+    store.delete_check("1".to_owned(), "1".to_owned()).await?;
+    assert_eq!(
+        store
+            .changes_since(revision, 10)
+            .await?
+            .into_iter()
+            .map(|change| (change.kind, change.entity))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                model::ChangeKind::Created,
+                model::Entity::Check(check("1", "1").with_version(revision + 1))
+            ),
+            (
+                model::ChangeKind::Updated,
+                model::Entity::Check(detailed.clone())
+            ),
+            (
+                model::ChangeKind::Deleted,
+                model::Entity::Check(detailed.with_version(revision + 3))
+            ),
+        ]
+    );
+    Ok(())
+}
+
+async fn pagination<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    for i in 1..=5 {
+        store
+            .store_check_list(check_list(&i.to_string(), &format!("2020-01-0{}", i)))
+            .await?;
+        store
+            .store_item(item(&i.to_string(), &format!("item{}", i)))
+            .await?;
+    }
+
+    let page1 = store.find_check_lists(None, 2).await?;
+    assert_eq!(
+        page1,
+        vec![
+            check_list("1", "2020-01-01").with_version(1),
+            check_list("2", "2020-01-02").with_version(3)
+        ]
+    );
+    let page2 = store.find_check_lists(Some("2".to_owned()), 2).await?;
+    assert_eq!(
+        page2,
+        vec![
+            check_list("3", "2020-01-03").with_version(5),
+            check_list("4", "2020-01-04").with_version(7)
+        ]
+    );
+    let page3 = store.find_check_lists(Some("4".to_owned()), 2).await?;
+    assert_eq!(page3, vec![check_list("5", "2020-01-05").with_version(9)]);
+    assert_eq!(
+        store.find_check_lists(Some("5".to_owned()), 2).await?,
+        vec![]
+    );
+
+    let mut items = vec![];
+    let mut after = None;
+    loop {
+        let page = store.find_items(after, 2).await?;
+        assert!(page.len() <= 2, "page must not exceed the limit");
+        match page.last() {
+            Some(last) => after = Some(last.id.clone()),
+            None => break,
+        }
+        items.extend(page);
+    }
+    assert_eq!(items, store.find_all_items().await?);
+    Ok(())
+}
+
+async fn client_sequences<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    assert_eq!(store.find_client_sequence("a".to_owned()).await?, None);
+    store.store_client_sequence("a".to_owned(), 1).await?;
+    store.store_client_sequence("a".to_owned(), 3).await?;
+    store.store_client_sequence("b".to_owned(), 2).await?;
+    assert_eq!(store.find_client_sequence("a".to_owned()).await?, Some(3));
+    assert_eq!(store.find_client_sequence("b".to_owned()).await?, Some(2));
+
+    store
+        .compare_and_store_client_sequence("a".to_owned(), Some(3), Some(4))
+        .await?;
+    assert!(
+        matches!(
+            store
+                .compare_and_store_client_sequence("a".to_owned(), Some(3), Some(5))
+                .await,
+            Err(Error::Conflict(_))
+        ),
+        "the sequence has advanced"
+    );
+    assert!(matches!(
+        store
+            .compare_and_store_client_sequence("c".to_owned(), Some(1), Some(2))
+            .await,
+        Err(Error::Conflict(_))
+    ));
+    store
+        .compare_and_store_client_sequence("c".to_owned(), None, Some(1))
+        .await?;
+    assert_eq!(store.find_client_sequence("c".to_owned()).await?, Some(1));
+    store
+        .compare_and_store_client_sequence("c".to_owned(), Some(1), None)
+        .await?;
+    assert_eq!(store.find_client_sequence("c".to_owned()).await?, None);
+    assert_eq!(store.find_client_sequence("a".to_owned()).await?, Some(4));
+    Ok(())
+}
+
+async fn changes<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    assert_eq!(store.find_current_revision().await?, 0);
+    assert_eq!(store.changes_since(0, 10).await?, vec![]);
+
+    store
+        .store_check_list(check_list("1", "2020-01-02"))
+        .await?;
+    store.store_item(item("1", "item1")).await?;
+    store.store_item(item("1", "item1 renamed")).await?;
+    store.store_check(check("1", "1")).await?;
+    // no-ops are not recorded
+    store.store_check(check("1", "1")).await?;
+    store.delete_check("1".to_owned(), "2".to_owned()).await?;
+    // rejected writes are not recorded
+    assert!(store
+        .store_check_list(check_list("2", "2020-01-02"))
+        .await
+        .is_err());
+    store.delete_check("1".to_owned(), "1".to_owned()).await?;
+
+    // a changed entity is at the version of its change
+    let change = |revision: u64, kind: model::ChangeKind, entity: model::Entity| model::Change {
+        entity: entity.with_version(revision),
+        kind,
+        revision,
+    };
+    let expected = vec![
+        change(
+            1,
+            model::ChangeKind::Created,
+            model::Entity::CheckList(check_list("1", "2020-01-02")),
+        ),
+        change(
+            2,
+            model::ChangeKind::Created,
+            model::Entity::Item(item("1", "item1")),
+        ),
+        change(
+            3,
+            model::ChangeKind::Updated,
+            model::Entity::Item(item("1", "item1 renamed")),
+        ),
+        change(
+            4,
+            model::ChangeKind::Created,
+            model::Entity::Check(check("1", "1")),
+        ),
+        change(
+            5,
+            model::ChangeKind::Deleted,
+            model::Entity::Check(check("1", "1")),
+        ),
+    ];
+    assert_eq!(store.find_current_revision().await?, 5);
+    assert_eq!(store.changes_since(0, 10).await?, expected);
+    assert_eq!(store.changes_since(1, 2).await?, expected[1..3].to_vec());
+    assert_eq!(store.changes_since(5, 10).await?, vec![]);
+    Ok(())
+}
+
+async fn archive<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    assert!(matches!(
+        store
+            .archive_item("1".to_owned(), "2020-01-02T03:04:05Z".to_owned())
+            .await,
+        Err(Error::NotFound(_))
+    ));
+    assert!(matches!(
+        store.restore_item("1".to_owned()).await,
+        Err(Error::NotFound(_))
+    ));
+
+    store.store_item(item("1", "item1")).await?;
+    store
+        .archive_item("1".to_owned(), "2020-01-02T03:04:05Z".to_owned())
+        .await?;
+    // archiving again keeps the first timestamp
+    store
+        .archive_item("1".to_owned(), "2020-01-03T00:00:00Z".to_owned())
+        .await?;
+    let archived = model::Item {
+        archived_at: Some("2020-01-02T03:04:05Z".to_owned()),
+        ..item("1", "item1").with_version(2)
+    };
+    assert_eq!(store.find_all_items().await?, vec![archived.clone()]);
+    assert_eq!(
+        store.changes_since(1, 10).await?,
+        vec![model::Change {
+            entity: model::Entity::Item(archived),
+            kind: model::ChangeKind::Updated,
+            revision: 2,
+        }]
+    );
+
+    store.restore_item("1".to_owned()).await?;
+    store.restore_item("1".to_owned()).await?;
+    assert_eq!(
+        store.find_all_items().await?,
+        vec![item("1", "item1").with_version(3)]
+    );
+    assert_eq!(store.find_current_revision().await?, 3);
+    Ok(())
+}
+
+async fn purge<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    // purging a missing item is a no-op
+    store.purge_item("1".to_owned()).await?;
+
+    store
+        .store_check_list(check_list("1", "2020-01-02"))
+        .await?;
+    store
+        .store_check_list(check_list("2", "2020-01-03"))
+        .await?;
+    store.store_item(item("1", "item1")).await?;
+    store.store_item(item("2", "item2")).await?;
+    store.store_check(check("1", "1")).await?;
+    store.store_check(check("2", "1")).await?;
+    store.store_check(check("1", "2")).await?;
+    let revision = store.find_current_revision().await?;
+
+    store.purge_item("1".to_owned()).await?;
+    assert_eq!(
+        store.find_all_items().await?,
+        vec![item("2", "item2").with_version(4)]
+    );
+    assert_eq!(
+        store.find_all_checks().await?,
+        vec![check("1", "2").with_version(7)]
+    );
+    assert_eq!(store.find_checks_by_item_id("1".to_owned()).await?, vec![]);
+    assert_eq!(
+        store
+            .changes_since(revision, 10)
+            .await?
+            .into_iter()
+            .map(|change| (change.kind, change.entity))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                model::ChangeKind::Deleted,
+                model::Entity::Check(check("1", "1").with_version(revision + 1))
+            ),
+            (
+                model::ChangeKind::Deleted,
+                model::Entity::Check(check("2", "1").with_version(revision + 2))
+            ),
+            (
+                model::ChangeKind::Deleted,
+                model::Entity::Item(item("1", "item1").with_version(revision + 3))
+            ),
+        ]
+    );
+    // the item id can be reused
+    store.store_item(item("1", "item1")).await?;
+    assert_eq!(store.find_checks_by_item_id("1".to_owned()).await?, vec![]);
+    Ok(())
+}
+
+async fn batch<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    store.store_batch(Batch::default()).await?;
+    assert_eq!(store.find_current_revision().await?, 0);
+
+    store.store_item(item("1", "item1")).await?;
+    // checks may refer to entities in the same batch
+    store
+        .store_batch(Batch {
+            items: vec![item("1", "item1b"), item("2", "item2")],
+            check_lists: vec![check_list("1", "2020-01-02")],
+            checks: vec![check("1", "1"), check("1", "2")],
+        })
+        .await?;
+    assert_eq!(
+        store.find_all_items().await?,
+        vec![
+            item("1", "item1b").with_version(2),
+            item("2", "item2").with_version(3)
+        ]
+    );
+    assert_eq!(
+        store.find_all_checks().await?,
+        vec![
+            check("1", "1").with_version(5),
+            check("1", "2").with_version(6)
+        ]
+    );
+    assert_eq!(
+        store
+            .changes_since(1, 10)
+            .await?
+            .into_iter()
+            .map(|change| change.kind)
+            .collect::<Vec<_>>(),
+        vec![
+            model::ChangeKind::Updated,
+            model::ChangeKind::Created,
+            model::ChangeKind::Created,
+            model::ChangeKind::Created,
+            model::ChangeKind::Created,
+        ]
+    );
+
+    let revision = store.find_current_revision().await?;
+    assert!(
+        matches!(
+            store
+                .store_batch(Batch {
+                    items: vec![item("3", "item3")],
+                    check_lists: vec![check_list("2", "2020-01-03")],
+                    checks: vec![check("2", "3"), check("2", "4")],
+                })
+                .await,
+            Err(Error::NotFound(_))
+        ),
+        "a check of a missing item must be rejected"
+    );
+    assert!(
+        matches!(
+            store
+                .store_batch(Batch {
+                    check_lists: vec![check_list("2", "2020-01-02")],
+                    ..Default::default()
+                })
+                .await,
+            Err(Error::AlreadyExists(_))
+        ),
+        "a duplicate check list date must be rejected"
+    );
+    assert_eq!(
+        store.find_current_revision().await?,
+        revision,
+        "a rejected batch must not write anything"
+    );
+    assert_eq!(store.find_all_items().await?.len(), 2);
+    assert_eq!(store.find_all_check_lists().await?.len(), 1);
+    Ok(())
+}
+
+async fn versions<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    store
+        .store_check_list(check_list("1", "2020-01-02"))
+        .await?;
+    // version 0 expects the item not to exist
+    let stored = store
+        .compare_and_store_item(item("1", "item1"), Some(0))
+        .await?;
+    assert_eq!(stored, item("1", "item1").with_version(2));
+    assert_eq!(store.find_all_items().await?, vec![stored.clone()]);
+    for expected in [0, 1] {
+        assert_eq!(
+            store
+                .compare_and_store_item(item("1", "item1 renamed"), Some(expected))
+                .await,
+            Err(Error::VersionConflict {
+                current: Some(Box::new(model::Entity::Item(stored.clone()))),
+                expected,
+            }),
+            "a stale version must be rejected with the current item"
+        );
+    }
+    assert_eq!(
+        store
+            .compare_and_store_item(item("1", "item1 renamed"), Some(2))
+            .await?,
+        item("1", "item1 renamed").with_version(3)
+    );
+    // no expected version skips the comparison
+    assert_eq!(
+        store
+            .compare_and_store_item(item("1", "item1"), None)
+            .await?,
+        item("1", "item1").with_version(4)
+    );
+
+    let checked = store
+        .compare_and_store_check(check("1", "1"), Some(0))
+        .await?;
+    assert_eq!(checked, check("1", "1").with_version(5));
+    // storing the same details keeps the version
+    assert_eq!(
+        store
+            .compare_and_store_check(check("1", "1"), Some(5))
+            .await?,
+        checked
+    );
+    assert_eq!(
+        store
+            .compare_and_delete_check("1".to_owned(), "1".to_owned(), Some(4))
+            .await,
+        Err(Error::VersionConflict {
+            current: Some(Box::new(model::Entity::Check(checked.clone()))),
+            expected: 4,
+        })
+    );
+    store
+        .compare_and_delete_check("1".to_owned(), "1".to_owned(), Some(5))
+        .await?;
+    assert_eq!(store.find_all_checks().await?, vec![]);
+    // a missing check is at version 0
+    store
+        .compare_and_delete_check("1".to_owned(), "1".to_owned(), Some(0))
+        .await?;
+    assert_eq!(
+        store
+            .compare_and_delete_check("1".to_owned(), "1".to_owned(), Some(5))
+            .await,
+        Err(Error::VersionConflict {
+            current: None,
+            expected: 5,
+        })
+    );
+    assert_eq!(
+        store
+            .compare_and_store_check(check("1", "1"), Some(5))
+            .await,
+        Err(Error::VersionConflict {
+            current: None,
+            expected: 5,
+        })
+    );
+    assert_eq!(
+        store.find_current_revision().await?,
+        6,
+        "rejected writes must not write anything"
+    );
+
+    assert_eq!(
+        store.compare_and_purge_item("1".to_owned(), Some(3)).await,
+        Err(Error::VersionConflict {
+            current: Some(Box::new(model::Entity::Item(
+                item("1", "item1").with_version(4)
+            ))),
+            expected: 3,
+        })
+    );
+    store
+        .compare_and_purge_item("1".to_owned(), Some(4))
+        .await?;
+    assert_eq!(store.find_all_items().await?, vec![]);
+    // a missing item is at version 0
+    store
+        .compare_and_purge_item("1".to_owned(), Some(0))
+        .await?;
+    assert_eq!(
+        store.compare_and_purge_item("1".to_owned(), Some(4)).await,
+        Err(Error::VersionConflict {
+            current: None,
+            expected: 4,
+        })
+    );
+    Ok(())
+}
+
+async fn users<S: Store + Send + Sync>(store: &S) -> anyhow::Result<()> {
+    let user = |password_hash: &str| model::User {
+        id: "user1".to_owned(),
+        password_hash: password_hash.to_owned(),
+    };
+    assert_eq!(store.find_user("user1".to_owned()).await?, None);
+    store.store_user(user("hash1")).await?;
+    assert_eq!(
+        store.find_user("user1".to_owned()).await?,
+        Some(user("hash1"))
+    );
+    store.store_user(user("hash2")).await?;
+    assert_eq!(
+        store.find_user("user1".to_owned()).await?,
+        Some(user("hash2"))
+    );
+    assert_eq!(
+        store.find_current_revision().await?,
+        0,
+        "users must not be recorded as changes"
+    );
+    Ok(())
+}
@@ -1,25 +1,202 @@
+pub mod data;
+pub mod export;
+pub mod idempotency;
+pub mod import;
+pub mod report;
+pub mod seed;
+pub mod sync;
+pub mod user;
+
 use std::sync::Arc;
 
 use axum::async_trait;
 
 use crate::model;
 
-#[derive(Clone, Debug, thiserror::Error)]
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum Error {
+    #[error("already exists {0}")]
+    AlreadyExists(String),
+    #[error("conflict {0}")]
+    Conflict(String),
+    #[error("invalid input {0}")]
+    InvalidInput(String),
+    #[error("not found {0}")]
+    NotFound(String),
+    #[error("unauthorized {0}")]
+    Unauthorized(String),
+    #[error("unavailable {0}")]
+    Unavailable(String),
     #[error("unknown {0}")]
     Unknown(String),
+    /// The entity is not at the expected version. `current` is the entity as
+    /// stored, `None` if it does not exist.
+    #[error("version conflict expected {expected}")]
+    VersionConflict {
+        current: Option<Box<model::Entity>>,
+        expected: u64,
+    },
+}
+
+impl Error {
+    /// Returns a stable code that clients can branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::AlreadyExists(_) => "ALREADY_EXISTS",
+            Error::Conflict(_) => "CONFLICT",
+            Error::InvalidInput(_) => "INVALID_INPUT",
+            Error::NotFound(_) => "NOT_FOUND",
+            Error::Unauthorized(_) => "UNAUTHORIZED",
+            Error::Unavailable(_) => "UNAVAILABLE",
+            Error::Unknown(_) => "UNKNOWN",
+            Error::VersionConflict { .. } => "CONFLICT",
+        }
+    }
+}
+
+impl From<model::ValidationError> for Error {
+    fn from(e: model::ValidationError) -> Self {
+        Self::InvalidInput(e.to_string())
+    }
+}
+
+/// Entities written together by `Store::store_batch`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Batch {
+    pub items: Vec<model::Item>,
+    pub check_lists: Vec<model::CheckList>,
+    pub checks: Vec<model::Check>,
+}
+
+impl Batch {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.check_lists.is_empty() && self.checks.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len() + self.check_lists.len() + self.checks.len()
+    }
+
+    /// Splits the batch into batches of up to `size` entities, keeping the
+    /// order items, check lists, checks.
+    pub fn chunks(self, size: usize) -> Vec<Batch> {
+        let mut chunks = vec![];
+        let mut chunk = Batch::default();
+        for item in self.items {
+            chunk.items.push(item);
+            if chunk.len() == size {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+        }
+        for check_list in self.check_lists {
+            chunk.check_lists.push(check_list);
+            if chunk.len() == size {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+        }
+        for check in self.checks {
+            chunk.checks.push(check);
+            if chunk.len() == size {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
 }
 
 #[async_trait]
 pub trait Store {
+    /// Hides the item from `items` until it is restored. Archiving an archived item keeps `archived_at`.
+    async fn archive_item(&self, item_id: String, archived_at: String) -> Result<(), Error>;
+    /// Returns up to `limit` changes recorded after `revision`, oldest first.
+    /// Returns `Error::Conflict` if changes after `revision` are no longer retained.
+    async fn changes_since(&self, revision: u64, limit: usize)
+        -> Result<Vec<model::Change>, Error>;
+    /// Deletes the check as `delete_check` would if `expected_version` is
+    /// `None` or the check's version (0 if it does not exist). Returns
+    /// `Error::VersionConflict` otherwise.
+    async fn compare_and_delete_check(
+        &self,
+        check_list_id: String,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error>;
+    /// Purges the item as `purge_item` would if `expected_version` is `None`
+    /// or the item's version (0 if it does not exist). Returns
+    /// `Error::VersionConflict` otherwise.
+    async fn compare_and_purge_item(
+        &self,
+        item_id: String,
+        expected_version: Option<u64>,
+    ) -> Result<(), Error>;
+    /// Stores the check as `store_check` would if `expected_version` is `None`
+    /// or the check's version (0 if it does not exist), and returns it with
+    /// its new version. Returns `Error::VersionConflict` otherwise.
+    async fn compare_and_store_check(
+        &self,
+        check: model::Check,
+        expected_version: Option<u64>,
+    ) -> Result<model::Check, Error>;
+    /// Replaces the client's sequence with `sequence`, or deletes it if
+    /// `sequence` is `None`, if the stored one is `expected` (`None` if there
+    /// is none). Returns `Error::Conflict` otherwise.
+    async fn compare_and_store_client_sequence(
+        &self,
+        client_id: String,
+        expected: Option<u64>,
+        sequence: Option<u64>,
+    ) -> Result<(), Error>;
+    /// Stores the item as `store_item` would if `expected_version` is `None`
+    /// or the item's version (0 if it does not exist), and returns it with its
+    /// new version. Returns `Error::VersionConflict` otherwise.
+    async fn compare_and_store_item(
+        &self,
+        item: model::Item,
+        expected_version: Option<u64>,
+    ) -> Result<model::Item, Error>;
+    async fn delete_check(&self, check_list_id: String, item_id: String) -> Result<(), Error>;
     async fn find_all_check_lists(&self) -> Result<Vec<model::CheckList>, Error>;
     async fn find_all_checks(&self) -> Result<Vec<model::Check>, Error>;
     async fn find_all_items(&self) -> Result<Vec<model::Item>, Error>;
+    /// Returns up to `limit` check lists ordered by id, starting after the id `after`.
+    async fn find_check_lists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::CheckList>, Error>;
     async fn find_checks_by_check_list_id(
         &self,
         check_list_id: String,
     ) -> Result<Vec<model::Check>, Error>;
     async fn find_checks_by_item_id(&self, item_id: String) -> Result<Vec<model::Check>, Error>;
+    async fn find_client_sequence(&self, client_id: String) -> Result<Option<u64>, Error>;
+    /// Returns the revision of the latest write (0 if nothing has been written).
+    async fn find_current_revision(&self) -> Result<u64, Error>;
+    /// Returns up to `limit` items ordered by id, starting after the id `after`.
+    async fn find_items(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<model::Item>, Error>;
+    async fn find_user(&self, user_id: String) -> Result<Option<model::User>, Error>;
+    /// Makes the writes so far durable, e.g. before the process exits. Stores
+    /// that commit every write before returning do nothing.
+    async fn flush(&self) -> Result<(), Error>;
+    /// Deletes the item and its checks permanently.
+    async fn purge_item(&self, item_id: String) -> Result<(), Error>;
+    async fn restore_item(&self, item_id: String) -> Result<(), Error>;
+    /// Stores the items, then the check lists, then the checks, all or nothing.
+    /// Each entity is stored as `store_item`, `store_check_list` and `store_check` would.
+    async fn store_batch(&self, batch: Batch) -> Result<(), Error>;
+    async fn store_check(&self, check: model::Check) -> Result<(), Error>;
+    async fn store_check_list(&self, check_list: model::CheckList) -> Result<(), Error>;
+    async fn store_client_sequence(&self, client_id: String, sequence: u64) -> Result<(), Error>;
+    async fn store_item(&self, item: model::Item) -> Result<(), Error>;
+    /// Creates or replaces the user. Users are not recorded as changes.
+    async fn store_user(&self, user: model::User) -> Result<(), Error>;
 }
 
 pub trait HasStore {
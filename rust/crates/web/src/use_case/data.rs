@@ -0,0 +1,472 @@
+//! Serializable representations shared by the command log, the change log and the sync protocol.
+//! The formats mirror the Android client. Fields added later are optional.
+
+use crate::model;
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeData {
+    pub entity: EntityData,
+    pub kind: ChangeKindData,
+    pub revision: u64,
+}
+
+impl From<ChangeData> for model::Change {
+    fn from(
+        ChangeData {
+            entity,
+            kind,
+            revision,
+        }: ChangeData,
+    ) -> Self {
+        Self {
+            entity: model::Entity::from(entity),
+            kind: model::ChangeKind::from(kind),
+            revision,
+        }
+    }
+}
+
+impl From<model::Change> for ChangeData {
+    fn from(
+        model::Change {
+            entity,
+            kind,
+            revision,
+        }: model::Change,
+    ) -> Self {
+        Self {
+            entity: EntityData::from(entity),
+            kind: ChangeKindData::from(kind),
+            revision,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKindData {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl From<ChangeKindData> for model::ChangeKind {
+    fn from(kind: ChangeKindData) -> Self {
+        match kind {
+            ChangeKindData::Created => Self::Created,
+            ChangeKindData::Updated => Self::Updated,
+            ChangeKindData::Deleted => Self::Deleted,
+        }
+    }
+}
+
+impl From<model::ChangeKind> for ChangeKindData {
+    fn from(kind: model::ChangeKind) -> Self {
+        match kind {
+            model::ChangeKind::Created => Self::Created,
+            model::ChangeKind::Updated => Self::Updated,
+            model::ChangeKind::Deleted => Self::Deleted,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckData {
+    pub check_list_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<String>,
+    pub item_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<PriceData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shop: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_version",
+        skip_serializing_if = "is_zero"
+    )]
+    pub version: u64,
+}
+
+impl From<CheckData> for model::Check {
+    fn from(
+        CheckData {
+            check_list_id,
+            checked_at,
+            item_id,
+            note,
+            price,
+            quantity,
+            shop,
+            version,
+        }: CheckData,
+    ) -> Self {
+        Self {
+            check_list_id,
+            item_id,
+            checked_at,
+            note,
+            price: price.map(model::Price::from),
+            quantity,
+            shop,
+            version,
+        }
+    }
+}
+
+impl From<model::Check> for CheckData {
+    fn from(
+        model::Check {
+            check_list_id,
+            item_id,
+            checked_at,
+            note,
+            price,
+            quantity,
+            shop,
+            version,
+        }: model::Check,
+    ) -> Self {
+        Self {
+            check_list_id,
+            checked_at,
+            item_id,
+            note,
+            price: price.map(PriceData::from),
+            quantity,
+            shop,
+            version,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct CheckListData {
+    pub date: String,
+    pub id: String,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_version",
+        skip_serializing_if = "is_zero"
+    )]
+    pub version: u64,
+}
+
+impl From<CheckListData> for model::CheckList {
+    fn from(CheckListData { date, id, version }: CheckListData) -> Self {
+        Self { date, id, version }
+    }
+}
+
+impl From<model::CheckList> for CheckListData {
+    fn from(model::CheckList { date, id, version }: model::CheckList) -> Self {
+        Self { date, id, version }
+    }
+}
+
+/// The Android `Command` union.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "payload")]
+pub enum Command {
+    #[serde(rename_all = "camelCase")]
+    AddCheckList {
+        check_list: CheckListData,
+    },
+    AddItem {
+        item: ItemData,
+    },
+    #[serde(rename_all = "camelCase")]
+    ArchiveItem {
+        archived_at: String,
+        item_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    PurgeItem {
+        item_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    RestoreItem {
+        item_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetChecked {
+        check_list_id: String,
+        checked: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        checked_at: Option<String>,
+        item_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        price: Option<PriceData>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        quantity: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shop: Option<String>,
+    },
+    /// Replaces the whole item; an omitted optional field is cleared.
+    SetItem {
+        item: ItemData,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "payload")]
+pub enum EntityData {
+    Check(CheckData),
+    CheckList(CheckListData),
+    Item(ItemData),
+}
+
+impl From<EntityData> for model::Entity {
+    fn from(entity: EntityData) -> Self {
+        match entity {
+            EntityData::Check(check) => Self::Check(model::Check::from(check)),
+            EntityData::CheckList(check_list) => {
+                Self::CheckList(model::CheckList::from(check_list))
+            }
+            EntityData::Item(item) => Self::Item(model::Item::from(item)),
+        }
+    }
+}
+
+impl From<model::Entity> for EntityData {
+    fn from(entity: model::Entity) -> Self {
+        match entity {
+            model::Entity::Check(check) => Self::Check(CheckData::from(check)),
+            model::Entity::CheckList(check_list) => {
+                Self::CheckList(CheckListData::from(check_list))
+            }
+            model::Entity::Item(item) => Self::Item(ItemData::from(item)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_version",
+        skip_serializing_if = "is_zero"
+    )]
+    pub version: u64,
+}
+
+impl From<ItemData> for model::Item {
+    fn from(
+        ItemData {
+            archived_at,
+            category,
+            emoji,
+            id,
+            name,
+            note,
+            sort_order,
+            unit,
+            version,
+        }: ItemData,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            archived_at,
+            category,
+            emoji,
+            note,
+            sort_order,
+            unit,
+            version,
+        }
+    }
+}
+
+impl From<model::Item> for ItemData {
+    fn from(
+        model::Item {
+            id,
+            name,
+            archived_at,
+            category,
+            emoji,
+            note,
+            sort_order,
+            unit,
+            version,
+        }: model::Item,
+    ) -> Self {
+        Self {
+            archived_at,
+            category,
+            emoji,
+            id,
+            name,
+            note,
+            sort_order,
+            unit,
+            version,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct PriceData {
+    pub amount: i64,
+    pub currency: String,
+}
+
+impl From<PriceData> for model::Price {
+    fn from(PriceData { amount, currency }: PriceData) -> Self {
+        Self { amount, currency }
+    }
+}
+
+impl From<model::Price> for PriceData {
+    fn from(model::Price { amount, currency }: model::Price) -> Self {
+        Self { amount, currency }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserData {
+    pub id: String,
+    pub password_hash: String,
+}
+
+impl From<UserData> for model::User {
+    fn from(UserData { id, password_hash }: UserData) -> Self {
+        Self { id, password_hash }
+    }
+}
+
+impl From<model::User> for UserData {
+    fn from(model::User { id, password_hash }: model::User) -> Self {
+        Self { id, password_hash }
+    }
+}
+
+/// Reads a missing version as 0, also from deserializers that present a
+/// missing field as unit, as `serde_firestore_value` does.
+fn deserialize_version<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Ok(<Option<u64> as serde::Deserialize>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn is_zero(version: &u64) -> bool {
+    *version == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() -> anyhow::Result<()> {
+        let command = Command::SetChecked {
+            check_list_id: "1".to_owned(),
+            checked: true,
+            checked_at: None,
+            item_id: "2".to_owned(),
+            note: None,
+            price: None,
+            quantity: None,
+            shop: None,
+        };
+        let json = serde_json::to_string(&command)?;
+        assert_eq!(
+            json,
+            r#"{"type":"setChecked","payload":{"checkListId":"1","checked":true,"itemId":"2"}}"#
+        );
+        assert_eq!(serde_json::from_str::<Command>(&json)?, command);
+
+        let command = Command::SetChecked {
+            check_list_id: "1".to_owned(),
+            checked: true,
+            checked_at: Some("2020-01-02T03:04:05Z".to_owned()),
+            item_id: "2".to_owned(),
+            note: Some("on sale".to_owned()),
+            price: Some(PriceData {
+                amount: 298,
+                currency: "JPY".to_owned(),
+            }),
+            quantity: Some(1.5),
+            shop: Some("shop1".to_owned()),
+        };
+        let json = serde_json::to_string(&command)?;
+        assert_eq!(
+            json,
+            r#"{"type":"setChecked","payload":{"checkListId":"1","checked":true,"checkedAt":"2020-01-02T03:04:05Z","itemId":"2","note":"on sale","price":{"amount":298,"currency":"JPY"},"quantity":1.5,"shop":"shop1"}}"#
+        );
+        assert_eq!(serde_json::from_str::<Command>(&json)?, command);
+
+        let command = Command::AddCheckList {
+            check_list: CheckListData {
+                date: "2020-01-02".to_owned(),
+                id: "1".to_owned(),
+                version: 0,
+            },
+        };
+        let json = serde_json::to_string(&command)?;
+        assert_eq!(
+            json,
+            r#"{"type":"addCheckList","payload":{"checkList":{"date":"2020-01-02","id":"1"}}}"#
+        );
+        assert_eq!(serde_json::from_str::<Command>(&json)?, command);
+
+        // older clients omit `archivedAt`
+        let json = r#"{"type":"setItem","payload":{"item":{"id":"1","name":"item1"}}}"#;
+        let command = Command::SetItem {
+            item: ItemData {
+                id: "1".to_owned(),
+                name: "item1".to_owned(),
+                ..Default::default()
+            },
+        };
+        assert_eq!(serde_json::from_str::<Command>(json)?, command);
+        assert_eq!(serde_json::to_string(&command)?, json);
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_data() -> anyhow::Result<()> {
+        let change = model::Change {
+            entity: model::Entity::Check(model::Check {
+                check_list_id: "1".to_owned(),
+                item_id: "2".to_owned(),
+                ..Default::default()
+            }),
+            kind: model::ChangeKind::Deleted,
+            revision: 3,
+        };
+        let json = serde_json::to_string(&ChangeData::from(change.clone()))?;
+        assert_eq!(
+            json,
+            r#"{"entity":{"type":"check","payload":{"checkListId":"1","itemId":"2"}},"kind":"deleted","revision":3}"#
+        );
+        assert_eq!(
+            model::Change::from(serde_json::from_str::<ChangeData>(&json)?),
+            change
+        );
+        Ok(())
+    }
+}
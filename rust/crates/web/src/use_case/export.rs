@@ -0,0 +1,366 @@
+//! Export of all data as a versioned JSON document or as CSV.
+//!
+//! The output is written page by page, so a large store is never held in
+//! memory, except for the item names that the CSV rows are joined with.
+//!
+//! CSV layout, one row per check, ordered by (check_list_id, item_id):
+//!
+//! ```text
+//! date,check_list_id,item_id,item_name,quantity,price_amount,price_currency,shop,note,checked_at
+//! 2020-01-02,1,1,item1,1.5,298,JPY,shop1,,2020-01-02T03:04:05Z
+//! ```
+//!
+//! Empty columns are `None`. `price_amount` is in the currency's minor unit.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use axum::async_trait;
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+
+use super::{
+    data::{CheckData, CheckListData, ItemData},
+    Error, Store,
+};
+
+/// The version of the JSON document. Changes when a field changes incompatibly.
+pub const VERSION: u32 = 1;
+
+const PAGE_SIZE: usize = 100;
+
+pub const CSV_HEADER: [&str; 10] = [
+    "date",
+    "check_list_id",
+    "item_id",
+    "item_name",
+    "quantity",
+    "price_amount",
+    "price_currency",
+    "shop",
+    "note",
+    "checked_at",
+];
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    Csv,
+    #[default]
+    Json,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(Error::InvalidInput(format!("format {}", s))),
+        }
+    }
+}
+
+/// The JSON document. Items come before check lists, and check lists before
+/// checks, so that each entity can be imported in order.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportData {
+    pub version: u32,
+    pub items: Vec<ItemData>,
+    pub check_lists: Vec<CheckListData>,
+    pub checks: Vec<CheckData>,
+}
+
+/// Receives the export chunk by chunk.
+#[async_trait]
+pub trait Sink {
+    async fn send(&mut self, chunk: String) -> Result<(), Error>;
+}
+
+/// A `Sink` that writes to an `AsyncWrite`, e.g. stdout or a file.
+pub struct WriteSink<W>(pub W);
+
+#[async_trait]
+impl<W: AsyncWrite + Send + Unpin> Sink for WriteSink<W> {
+    async fn send(&mut self, chunk: String) -> Result<(), Error> {
+        self.0
+            .write_all(chunk.as_bytes())
+            .await
+            .map_err(|e| Error::Unknown(e.to_string()))
+    }
+}
+
+pub async fn export(
+    store: &(dyn Store + Send + Sync),
+    format: Format,
+    sink: &mut (dyn Sink + Send),
+) -> Result<(), Error> {
+    match format {
+        Format::Csv => export_csv(store, PAGE_SIZE, sink).await,
+        Format::Json => export_json(store, PAGE_SIZE, sink).await,
+    }
+}
+
+async fn export_csv(
+    store: &(dyn Store + Send + Sync),
+    page_size: usize,
+    sink: &mut (dyn Sink + Send),
+) -> Result<(), Error> {
+    let mut item_names = BTreeMap::new();
+    let mut after = None;
+    loop {
+        let items = store.find_items(after.take(), page_size).await?;
+        let Some(last) = items.last() else {
+            break;
+        };
+        after = Some(last.id.clone());
+        item_names.extend(items.into_iter().map(|item| (item.id, item.name)));
+    }
+
+    sink.send(csv_row(CSV_HEADER.iter().map(|s| s.to_string())))
+        .await?;
+    let mut after = None;
+    loop {
+        let check_lists = store.find_check_lists(after.take(), page_size).await?;
+        let Some(last) = check_lists.last() else {
+            break;
+        };
+        after = Some(last.id.clone());
+        let mut chunk = String::new();
+        for check_list in check_lists {
+            for check in store
+                .find_checks_by_check_list_id(check_list.id.clone())
+                .await?
+            {
+                let item_name = item_names.get(&check.item_id).cloned().unwrap_or_default();
+                let (price_amount, price_currency) = match check.price {
+                    Some(price) => (price.amount.to_string(), price.currency),
+                    None => (String::new(), String::new()),
+                };
+                chunk.push_str(&csv_row([
+                    check_list.date.clone(),
+                    check.check_list_id,
+                    check.item_id,
+                    item_name,
+                    check
+                        .quantity
+                        .map(|quantity| quantity.to_string())
+                        .unwrap_or_default(),
+                    price_amount,
+                    price_currency,
+                    check.shop.unwrap_or_default(),
+                    check.note.unwrap_or_default(),
+                    check.checked_at.unwrap_or_default(),
+                ]));
+            }
+        }
+        sink.send(chunk).await?;
+    }
+    Ok(())
+}
+
+async fn export_json(
+    store: &(dyn Store + Send + Sync),
+    page_size: usize,
+    sink: &mut (dyn Sink + Send),
+) -> Result<(), Error> {
+    sink.send(format!(r#"{{"version":{},"items":["#, VERSION))
+        .await?;
+    let mut first = true;
+    let mut after = None;
+    loop {
+        let items = store.find_items(after.take(), page_size).await?;
+        let Some(last) = items.last() else {
+            break;
+        };
+        after = Some(last.id.clone());
+        let mut chunk = String::new();
+        for item in items {
+            push_json(&mut chunk, &mut first, &ItemData::from(item))?;
+        }
+        sink.send(chunk).await?;
+    }
+
+    sink.send(r#"],"checkLists":["#.to_owned()).await?;
+    let mut first = true;
+    let mut after = None;
+    loop {
+        let check_lists = store.find_check_lists(after.take(), page_size).await?;
+        let Some(last) = check_lists.last() else {
+            break;
+        };
+        after = Some(last.id.clone());
+        let mut chunk = String::new();
+        for check_list in check_lists {
+            push_json(&mut chunk, &mut first, &CheckListData::from(check_list))?;
+        }
+        sink.send(chunk).await?;
+    }
+
+    sink.send(r#"],"checks":["#.to_owned()).await?;
+    let mut first = true;
+    let mut after = None;
+    loop {
+        let check_lists = store.find_check_lists(after.take(), page_size).await?;
+        let Some(last) = check_lists.last() else {
+            break;
+        };
+        after = Some(last.id.clone());
+        let mut chunk = String::new();
+        for check_list in check_lists {
+            for check in store.find_checks_by_check_list_id(check_list.id).await? {
+                push_json(&mut chunk, &mut first, &CheckData::from(check))?;
+            }
+        }
+        sink.send(chunk).await?;
+    }
+    sink.send("]}".to_owned()).await
+}
+
+fn push_json<T: serde::Serialize>(
+    chunk: &mut String,
+    first: &mut bool,
+    value: &T,
+) -> Result<(), Error> {
+    if !*first {
+        chunk.push(',');
+    }
+    *first = false;
+    chunk.push_str(&serde_json::to_string(value).map_err(|e| Error::Unknown(e.to_string()))?);
+    Ok(())
+}
+
+/// Quotes the fields that contain a comma, a quote or a line break (RFC 4180).
+fn csv_row<I: IntoIterator<Item = String>>(fields: I) -> String {
+    let mut row = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{infra::store::InMemoryStore, model};
+
+    use super::*;
+
+    async fn example() -> anyhow::Result<InMemoryStore> {
+        let store = InMemoryStore::example();
+        store
+            .store_item(model::Item {
+                id: "3".to_owned(),
+                name: "paper, \"soft\"".to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        store
+            .store_check(model::Check {
+                check_list_id: "1".to_owned(),
+                item_id: "3".to_owned(),
+                checked_at: Some("2020-01-02T03:04:05Z".to_owned()),
+                price: Some(model::Price {
+                    amount: 298,
+                    currency: "JPY".to_owned(),
+                }),
+                quantity: Some(1.5),
+                shop: Some("shop1".to_owned()),
+                ..Default::default()
+            })
+            .await?;
+        Ok(store)
+    }
+
+    #[tokio::test]
+    async fn test_export_json() -> anyhow::Result<()> {
+        let store = example().await?;
+        for page_size in [1, 2, PAGE_SIZE] {
+            let mut sink = WriteSink(Vec::new());
+            export_json(&store, page_size, &mut sink).await?;
+            let data = serde_json::from_slice::<ExportData>(&sink.0)?;
+            assert_eq!(
+                data,
+                ExportData {
+                    version: VERSION,
+                    items: store
+                        .find_all_items()
+                        .await?
+                        .into_iter()
+                        .map(ItemData::from)
+                        .collect(),
+                    check_lists: store
+                        .find_all_check_lists()
+                        .await?
+                        .into_iter()
+                        .map(CheckListData::from)
+                        .collect(),
+                    checks: store
+                        .find_all_checks()
+                        .await?
+                        .into_iter()
+                        .map(CheckData::from)
+                        .collect(),
+                }
+            );
+        }
+
+        let mut sink = WriteSink(Vec::new());
+        export_json(&InMemoryStore::new(), 1, &mut sink).await?;
+        assert_eq!(
+            String::from_utf8(sink.0)?,
+            r#"{"version":1,"items":[],"checkLists":[],"checks":[]}"#
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_csv() -> anyhow::Result<()> {
+        let store = example().await?;
+        let mut sink = WriteSink(Vec::new());
+        export_csv(&store, 1, &mut sink).await?;
+        assert_eq!(
+            String::from_utf8(sink.0)?,
+            [
+                "date,check_list_id,item_id,item_name,quantity,price_amount,price_currency,shop,note,checked_at\r\n",
+                "2020-01-02,1,1,item1,,,,,,\r\n",
+                "2020-01-02,1,3,\"paper, \"\"soft\"\"\",1.5,298,JPY,shop1,,2020-01-02T03:04:05Z\r\n",
+                "2020-01-03,2,2,item2,,,,,,\r\n",
+            ]
+            .concat()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(Format::from_str("csv"), Ok(Format::Csv));
+        assert_eq!(Format::from_str("json"), Ok(Format::Json));
+        assert!(matches!(
+            Format::from_str("xml"),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
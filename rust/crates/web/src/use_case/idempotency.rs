@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use axum::async_trait;
+
+use super::Error;
+
+/// The claim that holds a key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IdempotencyRecord {
+    /// Identifies the request that claimed the key.
+    pub fingerprint: String,
+    /// The response to replay, `None` while the request is running.
+    pub response: Option<String>,
+}
+
+/// Stores the responses of requests by their idempotency key, so that a
+/// retried request is answered with the original response instead of being
+/// run again. Keys are opaque to the store.
+#[async_trait]
+pub trait IdempotencyKeyStore {
+    /// Claims the key for the request with `fingerprint` for `lease` and
+    /// returns `None`. Returns the claim that holds the key instead if it has
+    /// not expired.
+    async fn claim(
+        &self,
+        key: String,
+        fingerprint: String,
+        lease: Duration,
+    ) -> Result<Option<IdempotencyRecord>, Error>;
+    /// Stores the response of the claimed key and keeps it for `ttl`.
+    async fn complete(&self, key: String, response: String, ttl: Duration) -> Result<(), Error>;
+    /// Drops the claim, so that a retry runs the request again.
+    async fn release(&self, key: String) -> Result<(), Error>;
+}
@@ -0,0 +1,741 @@
+//! Import of the export formats, e.g. for migrating from the Android local
+//! store or from a spreadsheet.
+//!
+//! Every entity is validated against the model rules and against the store
+//! before anything is written. If any entity is invalid, nothing is written and
+//! the errors are reported per entry (JSON) or per row (CSV). A dry run stops
+//! after the validation and reports what would be written.
+//!
+//! CSV rows use the export layout (see [`super::export`]), with the header as
+//! row 1. An empty `check_list_id` selects the check list of the date, which is
+//! created if it does not exist. An empty `item_id` selects the item named
+//! `item_name`, which is created if it does not exist. Existing items are not
+//! renamed by `item_name`.
+//!
+//! Writes go through `Store::store_batch` in chunks of [`BATCH_SIZE`] entities,
+//! items first, so each chunk only refers to stored entities or to itself.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use uuid::Uuid;
+
+use crate::model;
+
+use super::{
+    export::{ExportData, Format, CSV_HEADER, VERSION},
+    Batch, Error, Store,
+};
+
+/// Entities per `Store::store_batch` call. Keeps a chunk well within a
+/// Firestore transaction.
+pub const BATCH_SIZE: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize)]
+pub struct Counts {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// An invalid entry, e.g. `row 3` (CSV) or `checks[2]` (JSON).
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize)]
+pub struct RowError {
+    pub location: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub dry_run: bool,
+    pub items: Counts,
+    pub check_lists: Counts,
+    pub checks: Counts,
+    /// Nothing has been written if this is not empty.
+    pub errors: Vec<RowError>,
+}
+
+/// Entities read from the input, each with its location.
+#[derive(Debug, Default)]
+struct Entries {
+    items: Vec<(String, model::Item)>,
+    check_lists: Vec<(String, model::CheckList)>,
+    checks: Vec<(String, model::Check)>,
+    errors: Vec<RowError>,
+}
+
+impl Entries {
+    fn error<S: Into<String>, T: ToString>(&mut self, location: S, message: T) {
+        self.errors.push(RowError {
+            location: location.into(),
+            message: message.to_string(),
+        });
+    }
+}
+
+/// The stored entities that the input is compared with.
+struct Existing {
+    check_lists: BTreeMap<String, model::CheckList>,
+    check_list_ids_by_date: BTreeMap<String, String>,
+    items: BTreeMap<String, model::Item>,
+}
+
+impl Existing {
+    async fn load(store: &(dyn Store + Send + Sync)) -> Result<Self, Error> {
+        let check_lists = store
+            .find_all_check_lists()
+            .await?
+            .into_iter()
+            .map(|check_list| (check_list.id.clone(), check_list))
+            .collect::<BTreeMap<_, _>>();
+        let check_list_ids_by_date = check_lists
+            .values()
+            .map(|check_list| (check_list.date.clone(), check_list.id.clone()))
+            .collect();
+        let items = store
+            .find_all_items()
+            .await?
+            .into_iter()
+            .map(|item| (item.id.clone(), item))
+            .collect();
+        Ok(Self {
+            check_lists,
+            check_list_ids_by_date,
+            items,
+        })
+    }
+}
+
+/// Returns `Err` if the input as a whole is unreadable, and `Ok` with the row
+/// errors otherwise.
+pub async fn import(
+    store: &(dyn Store + Send + Sync),
+    format: Format,
+    input: &str,
+    dry_run: bool,
+) -> Result<ImportSummary, Error> {
+    let existing = Existing::load(store).await?;
+    let entries = match format {
+        Format::Csv => parse_csv(&existing, input)?,
+        Format::Json => parse_json(input)?,
+    };
+    let (batch, mut summary) = validate(store, &existing, entries).await?;
+    summary.dry_run = dry_run;
+    if dry_run || !summary.errors.is_empty() {
+        return Ok(summary);
+    }
+    for chunk in batch.chunks(BATCH_SIZE) {
+        store.store_batch(chunk).await?;
+    }
+    Ok(summary)
+}
+
+fn parse_json(input: &str) -> Result<Entries, Error> {
+    let data = serde_json::from_str::<ExportData>(input)
+        .map_err(|e| Error::InvalidInput(format!("json {}", e)))?;
+    if data.version != VERSION {
+        return Err(Error::InvalidInput(format!("version {}", data.version)));
+    }
+    Ok(Entries {
+        items: data
+            .items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| (format!("items[{}]", index), model::Item::from(item)))
+            .collect(),
+        check_lists: data
+            .check_lists
+            .into_iter()
+            .enumerate()
+            .map(|(index, check_list)| {
+                (
+                    format!("checkLists[{}]", index),
+                    model::CheckList::from(check_list),
+                )
+            })
+            .collect(),
+        checks: data
+            .checks
+            .into_iter()
+            .enumerate()
+            .map(|(index, check)| (format!("checks[{}]", index), model::Check::from(check)))
+            .collect(),
+        errors: vec![],
+    })
+}
+
+fn parse_csv(existing: &Existing, input: &str) -> Result<Entries, Error> {
+    let mut records = csv_records(input)?.into_iter();
+    if records.next().as_deref() != Some(&CSV_HEADER.map(str::to_owned)[..]) {
+        return Err(Error::InvalidInput(format!(
+            "csv header must be {}",
+            CSV_HEADER.join(",")
+        )));
+    }
+
+    let mut entries = Entries::default();
+    // check list id -> date and item id -> name of the rows so far
+    let mut check_list_dates = BTreeMap::<String, String>::new();
+    let mut check_list_ids_by_date = existing.check_list_ids_by_date.clone();
+    let mut item_names = BTreeMap::<String, String>::new();
+    let mut item_ids_by_name = existing
+        .items
+        .values()
+        .map(|item| (item.name.clone(), item.id.clone()))
+        .collect::<BTreeMap<_, _>>();
+    for (index, record) in records.enumerate() {
+        let location = format!("row {}", index + 2);
+        let [date, check_list_id, item_id, item_name, quantity, price_amount, price_currency, shop, note, checked_at] =
+            match <[String; 10]>::try_from(record) {
+                Ok(record) => record,
+                Err(record) => {
+                    entries.error(
+                        location,
+                        format!("{} columns, expected {}", record.len(), CSV_HEADER.len()),
+                    );
+                    continue;
+                }
+            };
+
+        let check_list_id = if check_list_id.is_empty() {
+            check_list_ids_by_date
+                .get(&date)
+                .cloned()
+                .unwrap_or_else(|| Uuid::new_v4().to_string())
+        } else {
+            check_list_id
+        };
+        match check_list_dates.get(&check_list_id) {
+            Some(previous) if previous != &date => {
+                entries.error(
+                    location,
+                    format!("check list {} has date {}", check_list_id, previous),
+                );
+                continue;
+            }
+            Some(_) => {}
+            None => {
+                check_list_dates.insert(check_list_id.clone(), date.clone());
+                check_list_ids_by_date
+                    .entry(date.clone())
+                    .or_insert_with(|| check_list_id.clone());
+                entries.check_lists.push((
+                    location.clone(),
+                    model::CheckList {
+                        id: check_list_id.clone(),
+                        date,
+                        ..Default::default()
+                    },
+                ));
+            }
+        }
+
+        let item_id = if item_id.is_empty() {
+            item_ids_by_name
+                .get(&item_name)
+                .cloned()
+                .unwrap_or_else(|| Uuid::new_v4().to_string())
+        } else {
+            item_id
+        };
+        if !item_names.contains_key(&item_id) {
+            let item = match existing.items.get(&item_id) {
+                Some(item) => item.clone(),
+                None => model::Item {
+                    id: item_id.clone(),
+                    name: item_name.clone(),
+                    ..Default::default()
+                },
+            };
+            item_names.insert(item_id.clone(), item.name.clone());
+            item_ids_by_name
+                .entry(item.name.clone())
+                .or_insert_with(|| item_id.clone());
+            entries.items.push((location.clone(), item));
+        }
+
+        let quantity = match optional(quantity).map(|s| s.parse::<f64>()).transpose() {
+            Ok(quantity) => quantity,
+            Err(e) => {
+                entries.error(location, format!("quantity {}", e));
+                continue;
+            }
+        };
+        let price = match (optional(price_amount), optional(price_currency)) {
+            (None, None) => None,
+            (Some(amount), Some(currency)) => match amount.parse::<i64>() {
+                Ok(amount) => Some(model::Price { amount, currency }),
+                Err(e) => {
+                    entries.error(location, format!("price_amount {}", e));
+                    continue;
+                }
+            },
+            (_, _) => {
+                entries.error(
+                    location,
+                    "price_amount and price_currency must be set together",
+                );
+                continue;
+            }
+        };
+        entries.checks.push((
+            location,
+            model::Check {
+                check_list_id,
+                item_id,
+                checked_at: optional(checked_at),
+                note: optional(note),
+                price,
+                quantity,
+                shop: optional(shop),
+                ..Default::default()
+            },
+        ));
+    }
+    Ok(entries)
+}
+
+fn optional(s: String) -> Option<String> {
+    Some(s).filter(|s| !s.is_empty())
+}
+
+/// Splits the input into records of fields (RFC 4180). Accepts LF as well as
+/// CRLF line breaks and skips empty lines.
+fn csv_records(input: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                if record != [""] {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::InvalidInput("csv unterminated quote".to_owned()));
+    }
+    record.push(field);
+    if record != [""] {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Checks the entries and returns the entities to write with the summary.
+async fn validate(
+    store: &(dyn Store + Send + Sync),
+    existing: &Existing,
+    mut entries: Entries,
+) -> Result<(Batch, ImportSummary), Error> {
+    let mut batch = Batch::default();
+    let mut summary = ImportSummary::default();
+
+    // checks of rejected entities are skipped, as the entity has been reported
+    let mut rejected_ids = BTreeSet::new();
+    let mut item_ids = BTreeSet::new();
+    for (location, item) in std::mem::take(&mut entries.items) {
+        if let Err(e) = item.validate() {
+            entries.error(location, e);
+            rejected_ids.insert(item.id);
+            continue;
+        }
+        if !item_ids.insert(item.id.clone()) {
+            entries.error(location, format!("duplicate item {}", item.id));
+            continue;
+        }
+        // the store assigns versions, so an entity differing only in its
+        // version is unchanged
+        match existing.items.get(&item.id) {
+            Some(previous) if previous == &item.clone().with_version(previous.version) => {
+                summary.items.unchanged += 1
+            }
+            Some(_) => {
+                summary.items.updated += 1;
+                batch.items.push(item);
+            }
+            None => {
+                summary.items.created += 1;
+                batch.items.push(item);
+            }
+        }
+    }
+
+    let mut check_list_ids = BTreeSet::new();
+    let mut dates = BTreeSet::new();
+    for (location, check_list) in std::mem::take(&mut entries.check_lists) {
+        if let Err(e) = check_list.validate() {
+            entries.error(location, e);
+            rejected_ids.insert(check_list.id);
+            continue;
+        }
+        if !check_list_ids.insert(check_list.id.clone()) {
+            entries.error(location, format!("duplicate check list {}", check_list.id));
+            continue;
+        }
+        if !dates.insert(check_list.date.clone()) {
+            entries.error(location, format!("duplicate date {}", check_list.date));
+            continue;
+        }
+        match (
+            existing.check_lists.get(&check_list.id),
+            existing.check_list_ids_by_date.get(&check_list.date),
+        ) {
+            (Some(previous), _)
+                if previous == &check_list.clone().with_version(previous.version) =>
+            {
+                summary.check_lists.unchanged += 1
+            }
+            (Some(previous), _) => {
+                entries.error(
+                    location,
+                    format!("check list {} has date {}", previous.id, previous.date),
+                );
+                rejected_ids.insert(check_list.id);
+            }
+            (None, Some(id)) => {
+                entries.error(
+                    location,
+                    format!("date {} is used by check list {}", check_list.date, id),
+                );
+                rejected_ids.insert(check_list.id);
+            }
+            (None, None) => {
+                summary.check_lists.created += 1;
+                batch.check_lists.push(check_list);
+            }
+        }
+    }
+
+    let mut existing_checks = BTreeMap::<String, BTreeMap<String, model::Check>>::new();
+    let mut check_ids = BTreeSet::new();
+    for (location, check) in std::mem::take(&mut entries.checks) {
+        if let Err(e) = check.validate() {
+            entries.error(location, e);
+            continue;
+        }
+        if rejected_ids.contains(&check.check_list_id) || rejected_ids.contains(&check.item_id) {
+            continue;
+        }
+        if !check_list_ids.contains(&check.check_list_id)
+            && !existing.check_lists.contains_key(&check.check_list_id)
+        {
+            entries.error(
+                location,
+                format!("check list {} not found", check.check_list_id),
+            );
+            continue;
+        }
+        if !item_ids.contains(&check.item_id) && !existing.items.contains_key(&check.item_id) {
+            entries.error(location, format!("item {} not found", check.item_id));
+            continue;
+        }
+        if !check_ids.insert((check.check_list_id.clone(), check.item_id.clone())) {
+            entries.error(
+                location,
+                format!(
+                    "duplicate check of item {} in check list {}",
+                    check.item_id, check.check_list_id
+                ),
+            );
+            continue;
+        }
+        if !existing_checks.contains_key(&check.check_list_id) {
+            let checks = if existing.check_lists.contains_key(&check.check_list_id) {
+                store
+                    .find_checks_by_check_list_id(check.check_list_id.clone())
+                    .await?
+            } else {
+                vec![]
+            };
+            existing_checks.insert(
+                check.check_list_id.clone(),
+                checks
+                    .into_iter()
+                    .map(|check| (check.item_id.clone(), check))
+                    .collect(),
+            );
+        }
+        match existing_checks
+            .get(&check.check_list_id)
+            .and_then(|checks| checks.get(&check.item_id))
+        {
+            Some(previous) if previous == &check.clone().with_version(previous.version) => {
+                summary.checks.unchanged += 1
+            }
+            Some(_) => {
+                summary.checks.updated += 1;
+                batch.checks.push(check);
+            }
+            None => {
+                summary.checks.created += 1;
+                batch.checks.push(check);
+            }
+        }
+    }
+
+    summary.errors = entries.errors;
+    Ok((batch, summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infra::store::InMemoryStore;
+
+    use super::*;
+
+    const CSV: &str = concat!(
+        "date,check_list_id,item_id,item_name,quantity,price_amount,price_currency,shop,note,checked_at\r\n",
+        "2020-01-02,1,1,item1,,,,,,\r\n",
+        "2020-01-02,1,,\"paper, \"\"soft\"\"\",1.5,298,JPY,shop1,,2020-01-02T03:04:05Z\r\n",
+        "2020-01-04,,,item1,2,,,,\"multi\nline\",\r\n",
+    );
+
+    #[tokio::test]
+    async fn test_import_csv() -> anyhow::Result<()> {
+        let store = InMemoryStore::example();
+        let revision = store.find_current_revision().await?;
+        let summary = import(&store, Format::Csv, CSV, true).await?;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                dry_run: true,
+                items: Counts {
+                    created: 1,
+                    updated: 0,
+                    unchanged: 1,
+                },
+                check_lists: Counts {
+                    created: 1,
+                    updated: 0,
+                    unchanged: 1,
+                },
+                checks: Counts {
+                    created: 2,
+                    updated: 0,
+                    unchanged: 1,
+                },
+                errors: vec![],
+            }
+        );
+        assert_eq!(store.find_current_revision().await?, revision);
+
+        let summary = import(&store, Format::Csv, CSV, false).await?;
+        assert!(!summary.dry_run);
+        let items = store.find_all_items().await?;
+        assert_eq!(items.len(), 3);
+        let paper = items
+            .iter()
+            .find(|item| item.name == "paper, \"soft\"")
+            .expect("paper");
+        assert_eq!(
+            store.find_checks_by_item_id(paper.id.clone()).await?,
+            vec![model::Check {
+                check_list_id: "1".to_owned(),
+                item_id: paper.id.clone(),
+                checked_at: Some("2020-01-02T03:04:05Z".to_owned()),
+                price: Some(model::Price {
+                    amount: 298,
+                    currency: "JPY".to_owned(),
+                }),
+                quantity: Some(1.5),
+                shop: Some("shop1".to_owned()),
+                version: revision + 3,
+                ..Default::default()
+            }]
+        );
+        let check_list = store
+            .find_all_check_lists()
+            .await?
+            .into_iter()
+            .find(|check_list| check_list.date == "2020-01-04")
+            .expect("check list");
+        assert_eq!(
+            store.find_checks_by_check_list_id(check_list.id).await?[0].note,
+            Some("multi\nline".to_owned())
+        );
+
+        // the same rows resolve to the stored entities
+        let summary = import(&store, Format::Csv, CSV, false).await?;
+        assert_eq!(summary.items.unchanged, 2);
+        assert_eq!(summary.check_lists.unchanged, 2);
+        assert_eq!(summary.checks.unchanged, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_errors() -> anyhow::Result<()> {
+        let store = InMemoryStore::example();
+        let revision = store.find_current_revision().await?;
+        let input = concat!(
+            "date,check_list_id,item_id,item_name,quantity,price_amount,price_currency,shop,note,checked_at\n",
+            "2020-01-05,3,,item3,,,,,,\n",
+            "2020-02-30,,,item3,,,,,,\n",
+            "2020-01-06,3,,item3,,,,,,\n",
+            "2020-01-05,3,,item3,x,,,,,\n",
+            "2020-01-05,3,,item3,,100,,,,\n",
+            "2020-01-05,3,9,,,,,,,\n",
+            "2020-01-05,3\n",
+        );
+        let summary = import(&store, Format::Csv, input, false).await?;
+        assert_eq!(
+            summary
+                .errors
+                .iter()
+                .map(|e| e.location.as_str())
+                .collect::<Vec<_>>(),
+            vec!["row 4", "row 5", "row 6", "row 8", "row 7", "row 3"]
+        );
+        assert_eq!(
+            summary.errors[0].message,
+            "check list 3 has date 2020-01-05"
+        );
+        assert_eq!(summary.errors[4].message, "name is empty");
+        assert_eq!(summary.errors[5].message, "date 2020-02-30");
+        assert_eq!(store.find_current_revision().await?, revision);
+
+        assert!(matches!(
+            import(&store, Format::Csv, "date,item_id\n", false).await,
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            import(&store, Format::Csv, "\"date", false).await,
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_json() -> anyhow::Result<()> {
+        let store = InMemoryStore::example();
+        let input = r#"{
+            "version": 1,
+            "items": [
+                {"id": "1", "name": "item1 renamed"},
+                {"id": "3", "name": "item3"}
+            ],
+            "checkLists": [
+                {"id": "1", "date": "2020-01-02"},
+                {"id": "3", "date": "2020-01-04"}
+            ],
+            "checks": [
+                {"checkListId": "3", "itemId": "3", "quantity": 2.0},
+                {"checkListId": "1", "itemId": "1", "shop": "shop1"}
+            ]
+        }"#;
+        let summary = import(&store, Format::Json, input, false).await?;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                dry_run: false,
+                items: Counts {
+                    created: 1,
+                    updated: 1,
+                    unchanged: 0,
+                },
+                check_lists: Counts {
+                    created: 1,
+                    updated: 0,
+                    unchanged: 1,
+                },
+                checks: Counts {
+                    created: 1,
+                    updated: 1,
+                    unchanged: 0,
+                },
+                errors: vec![],
+            }
+        );
+        assert_eq!(store.find_all_items().await?[0].name, "item1 renamed");
+        assert_eq!(store.find_all_checks().await?.len(), 3);
+
+        let input = r#"{
+            "version": 1,
+            "items": [{"id": "a/b", "name": "item"}],
+            "checkLists": [
+                {"id": "2", "date": "2020-01-05"},
+                {"id": "4", "date": "2020-01-03"}
+            ],
+            "checks": [
+                {"checkListId": "4", "itemId": "1"},
+                {"checkListId": "5", "itemId": "1"},
+                {"checkListId": "1", "itemId": "1", "price": {"amount": -1, "currency": "JPY"}}
+            ]
+        }"#;
+        let summary = import(&store, Format::Json, input, false).await?;
+        assert_eq!(
+            summary.errors,
+            vec![
+                RowError {
+                    location: "items[0]".to_owned(),
+                    message: r#"id "a/b""#.to_owned(),
+                },
+                RowError {
+                    location: "checkLists[0]".to_owned(),
+                    message: "check list 2 has date 2020-01-03".to_owned(),
+                },
+                RowError {
+                    location: "checkLists[1]".to_owned(),
+                    message: "date 2020-01-03 is used by check list 2".to_owned(),
+                },
+                RowError {
+                    location: "checks[1]".to_owned(),
+                    message: "check list 5 not found".to_owned(),
+                },
+                RowError {
+                    location: "checks[2]".to_owned(),
+                    message: "price is negative".to_owned(),
+                },
+            ]
+        );
+
+        assert!(matches!(
+            import(
+                &store,
+                Format::Json,
+                r#"{"version":2,"items":[],"checkLists":[],"checks":[]}"#,
+                false
+            )
+            .await,
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_chunks() {
+        let batch = Batch {
+            items: vec![model::Item::default(); 3],
+            check_lists: vec![],
+            checks: vec![model::Check::default(); 2],
+        };
+        assert_eq!(
+            batch
+                .chunks(2)
+                .iter()
+                .map(|chunk| (
+                    chunk.items.len(),
+                    chunk.check_lists.len(),
+                    chunk.checks.len()
+                ))
+                .collect::<Vec<_>>(),
+            vec![(2, 0, 0), (1, 0, 1), (0, 0, 1)]
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Spending and consumption reports.
+//!
+//! A check is dated by its `checked_at`, or by its check list's date if it has
+//! none. Checks without a price are left out of the spend. Checks without a
+//! quantity count as one unit.
+
+use std::collections::BTreeMap;
+
+use time::{
+    format_description::well_known::{Iso8601, Rfc3339},
+    Date, Month, OffsetDateTime,
+};
+
+use crate::model;
+
+use super::{Error, Store};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GroupBy {
+    Category,
+    #[default]
+    Month,
+    Shop,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// ordered by item id
+    pub consumption: Vec<Consumption>,
+    /// ordered by (key, currency)
+    pub spend: Vec<Spend>,
+    /// ordered by (key, currency)
+    pub year_over_year: Vec<YearOverYear>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Consumption {
+    pub checks: usize,
+    pub item: model::Item,
+    pub quantity: f64,
+    /// `quantity` per 30 days over the report period
+    pub units_per_30_days: f64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Spend {
+    pub amount: i64,
+    pub checks: usize,
+    pub currency: String,
+    /// "YYYY-MM", the category or the shop. `None` if the check has no category or shop.
+    pub key: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YearOverYear {
+    pub amount: i64,
+    pub currency: String,
+    /// the same as `Spend::key`. Months are keyed by the current period's month.
+    pub key: Option<String>,
+    /// the spend in the same period a year earlier
+    pub previous_amount: i64,
+}
+
+/// A check joined with its item and date.
+struct Entry<'a> {
+    check: &'a model::Check,
+    date: Date,
+    item: &'a model::Item,
+}
+
+/// Returns the report for the checks dated from `from` to `to`, both inclusive.
+pub async fn report(
+    store: &(dyn Store + Send + Sync),
+    from: Date,
+    to: Date,
+    group_by: GroupBy,
+) -> Result<Report, Error> {
+    if from > to {
+        return Err(Error::InvalidInput(format!(
+            "from {} is after to {}",
+            from, to
+        )));
+    }
+    let check_lists = store.find_all_check_lists().await?;
+    let items = store.find_all_items().await?;
+    let checks = store.find_all_checks().await?;
+
+    let check_list_dates = check_lists
+        .iter()
+        .map(|check_list| Ok((check_list.id.as_str(), parse_date(&check_list.date)?)))
+        .collect::<Result<BTreeMap<_, _>, Error>>()?;
+    let items = items
+        .iter()
+        .map(|item| (item.id.as_str(), item))
+        .collect::<BTreeMap<_, _>>();
+    let entries = checks
+        .iter()
+        .filter_map(|check| {
+            let item = items.get(check.item_id.as_str())?;
+            let date = match check.checked_at.as_deref() {
+                Some(checked_at) => OffsetDateTime::parse(checked_at, &Rfc3339)
+                    .map(|checked_at| checked_at.date())
+                    .map_err(|_| Error::Unknown(format!("checked_at {}", checked_at))),
+                None => check_list_dates
+                    .get(check.check_list_id.as_str())
+                    .copied()
+                    .ok_or_else(|| Error::Unknown(format!("check list {}", check.check_list_id))),
+            };
+            Some(date.map(|date| Entry { check, date, item }))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let current = entries
+        .iter()
+        .filter(|entry| from <= entry.date && entry.date <= to)
+        .collect::<Vec<_>>();
+    let (previous_from, previous_to) = (years_ago(from, 1), years_ago(to, 1));
+    let previous = entries
+        .iter()
+        .filter(|entry| previous_from <= entry.date && entry.date <= previous_to)
+        .collect::<Vec<_>>();
+
+    let days = (to - from).whole_days() + 1;
+    let current_spend = spend(&current, group_by, |date| date);
+    // shifts the previous period onto the current one so that the months match
+    let previous_spend = spend(&previous, group_by, |date| years_ago(date, -1));
+    Ok(Report {
+        consumption: consumption(&current, days),
+        year_over_year: year_over_year(&current_spend, &previous_spend),
+        spend: current_spend
+            .into_iter()
+            .map(|((key, currency), (amount, checks))| Spend {
+                amount,
+                checks,
+                currency,
+                key,
+            })
+            .collect(),
+    })
+}
+
+fn consumption(entries: &[&Entry<'_>], days: i64) -> Vec<Consumption> {
+    let mut consumption = BTreeMap::<&str, Consumption>::new();
+    for entry in entries {
+        let c = consumption
+            .entry(entry.item.id.as_str())
+            .or_insert_with(|| Consumption {
+                checks: 0,
+                item: entry.item.clone(),
+                quantity: 0_f64,
+                units_per_30_days: 0_f64,
+            });
+        c.checks += 1;
+        c.quantity += entry.check.quantity.unwrap_or(1_f64);
+    }
+    consumption
+        .into_values()
+        .map(|c| Consumption {
+            units_per_30_days: c.quantity * 30_f64 / days as f64,
+            ..c
+        })
+        .collect()
+}
+
+/// Returns (amount, checks) by (key, currency).
+fn spend<F>(
+    entries: &[&Entry<'_>],
+    group_by: GroupBy,
+    date: F,
+) -> BTreeMap<(Option<String>, String), (i64, usize)>
+where
+    F: Fn(Date) -> Date,
+{
+    let mut spend = BTreeMap::<(Option<String>, String), (i64, usize)>::new();
+    for entry in entries {
+        let Some(price) = entry.check.price.as_ref() else {
+            continue;
+        };
+        let key = match group_by {
+            GroupBy::Category => entry.item.category.clone(),
+            GroupBy::Month => {
+                let date = date(entry.date);
+                Some(format!("{:04}-{:02}", date.year(), u8::from(date.month())))
+            }
+            GroupBy::Shop => entry.check.shop.clone(),
+        };
+        let (amount, checks) = spend.entry((key, price.currency.clone())).or_default();
+        *amount += price.amount;
+        *checks += 1;
+    }
+    spend
+}
+
+fn year_over_year(
+    current: &BTreeMap<(Option<String>, String), (i64, usize)>,
+    previous: &BTreeMap<(Option<String>, String), (i64, usize)>,
+) -> Vec<YearOverYear> {
+    let mut keys = current.keys().chain(previous.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .map(|(key, currency)| YearOverYear {
+            amount: current
+                .get(&(key.clone(), currency.clone()))
+                .map_or(0, |(amount, _)| *amount),
+            currency: currency.clone(),
+            key: key.clone(),
+            previous_amount: previous
+                .get(&(key.clone(), currency.clone()))
+                .map_or(0, |(amount, _)| *amount),
+        })
+        .collect()
+}
+
+pub fn parse_date(s: &str) -> Result<Date, Error> {
+    Date::parse(s, &Iso8601::DATE).map_err(|_| Error::InvalidInput(format!("date {}", s)))
+}
+
+/// Feb 29 becomes Feb 28 in a common year.
+fn years_ago(date: Date, years: i32) -> Date {
+    let year = date.year() - years;
+    date.replace_year(year)
+        .or_else(|_| Date::from_calendar_date(year, Month::February, 28))
+        .unwrap_or(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infra::store::InMemoryStore;
+
+    use super::*;
+
+    async fn fixture() -> anyhow::Result<InMemoryStore> {
+        let store = InMemoryStore::new();
+        for (id, date) in [
+            ("1", "2019-01-05"),
+            ("2", "2020-01-05"),
+            ("3", "2020-01-20"),
+            ("4", "2020-02-10"),
+        ] {
+            store
+                .store_check_list(model::CheckList {
+                    id: id.to_owned(),
+                    date: date.to_owned(),
+                    ..Default::default()
+                })
+                .await?;
+        }
+        for (id, category) in [("1", Some("food")), ("2", None)] {
+            store
+                .store_item(model::Item {
+                    id: id.to_owned(),
+                    name: format!("item{}", id),
+                    category: category.map(str::to_owned),
+                    ..Default::default()
+                })
+                .await?;
+        }
+        for (check_list_id, item_id, amount, quantity, shop) in [
+            ("1", "1", Some(100), Some(2_f64), Some("shop1")),
+            ("2", "1", Some(150), Some(3_f64), Some("shop1")),
+            ("3", "1", Some(200), None, Some("shop2")),
+            ("4", "2", Some(500), Some(1_f64), None),
+            ("4", "1", None, Some(1_f64), None),
+        ] {
+            store
+                .store_check(model::Check {
+                    check_list_id: check_list_id.to_owned(),
+                    item_id: item_id.to_owned(),
+                    price: amount.map(|amount| model::Price {
+                        amount,
+                        currency: "JPY".to_owned(),
+                    }),
+                    quantity,
+                    shop: shop.map(str::to_owned),
+                    ..Default::default()
+                })
+                .await?;
+        }
+        Ok(store)
+    }
+
+    fn spend(key: Option<&str>, amount: i64, checks: usize) -> Spend {
+        Spend {
+            amount,
+            checks,
+            currency: "JPY".to_owned(),
+            key: key.map(str::to_owned),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_group_by_month() -> anyhow::Result<()> {
+        let store = fixture().await?;
+        let report = report(
+            &store,
+            parse_date("2020-01-01")?,
+            parse_date("2020-02-29")?,
+            GroupBy::Month,
+        )
+        .await?;
+        assert_eq!(
+            report.spend,
+            vec![
+                spend(Some("2020-01"), 350, 2),
+                spend(Some("2020-02"), 500, 1)
+            ]
+        );
+        assert_eq!(
+            report.year_over_year,
+            vec![
+                YearOverYear {
+                    amount: 350,
+                    currency: "JPY".to_owned(),
+                    key: Some("2020-01".to_owned()),
+                    previous_amount: 100,
+                },
+                YearOverYear {
+                    amount: 500,
+                    currency: "JPY".to_owned(),
+                    key: Some("2020-02".to_owned()),
+                    previous_amount: 0,
+                },
+            ]
+        );
+        assert_eq!(
+            report
+                .consumption
+                .iter()
+                .map(|c| (
+                    c.item.id.as_str(),
+                    c.checks,
+                    c.quantity,
+                    c.units_per_30_days
+                ))
+                .collect::<Vec<_>>(),
+            // 60 days
+            vec![("1", 3, 5_f64, 2.5_f64), ("2", 1, 1_f64, 0.5_f64)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_group_by_category_and_shop() -> anyhow::Result<()> {
+        let store = fixture().await?;
+        let (from, to) = (parse_date("2020-01-01")?, parse_date("2020-12-31")?);
+        let report = report(&store, from, to, GroupBy::Category).await?;
+        assert_eq!(
+            report.spend,
+            vec![spend(None, 500, 1), spend(Some("food"), 350, 2)]
+        );
+        let report = self::report(&store, from, to, GroupBy::Shop).await?;
+        assert_eq!(
+            report.spend,
+            vec![
+                spend(None, 500, 1),
+                spend(Some("shop1"), 150, 1),
+                spend(Some("shop2"), 200, 1)
+            ]
+        );
+        assert_eq!(
+            report
+                .year_over_year
+                .iter()
+                .map(|y| (y.key.as_deref(), y.amount, y.previous_amount))
+                .collect::<Vec<_>>(),
+            vec![
+                (None, 500, 0),
+                (Some("shop1"), 150, 100),
+                (Some("shop2"), 200, 0)
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checked_at() -> anyhow::Result<()> {
+        let store = fixture().await?;
+        // checked after midnight, filed in the previous day's check list
+        store
+            .store_check(model::Check {
+                check_list_id: "4".to_owned(),
+                item_id: "2".to_owned(),
+                checked_at: Some("2020-03-01T00:10:00+09:00".to_owned()),
+                price: Some(model::Price {
+                    amount: 500,
+                    currency: "JPY".to_owned(),
+                }),
+                ..Default::default()
+            })
+            .await?;
+        let report = report(
+            &store,
+            parse_date("2020-03-01")?,
+            parse_date("2020-03-31")?,
+            GroupBy::Month,
+        )
+        .await?;
+        assert_eq!(report.spend, vec![spend(Some("2020-03"), 500, 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input() -> anyhow::Result<()> {
+        let store = fixture().await?;
+        assert!(matches!(
+            report(
+                &store,
+                parse_date("2020-02-01")?,
+                parse_date("2020-01-01")?,
+                GroupBy::Month
+            )
+            .await,
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            parse_date("2020-13-01"),
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_years_ago() -> anyhow::Result<()> {
+        assert_eq!(
+            years_ago(parse_date("2020-02-29")?, 1),
+            parse_date("2019-02-28")?
+        );
+        assert_eq!(
+            years_ago(parse_date("2019-03-01")?, -1),
+            parse_date("2020-03-01")?
+        );
+        Ok(())
+    }
+}
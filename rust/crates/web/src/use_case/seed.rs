@@ -0,0 +1,144 @@
+//! Fake data for development, generated with the `Distribution` impls in `model`.
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use time::{format_description::well_known::Rfc3339, Date, Duration, Time};
+
+use crate::model;
+
+use super::{import::BATCH_SIZE, Batch, Error, Store};
+
+/// Items to sample. Items with the same name are sampled once.
+const ITEM_SAMPLES: usize = 30;
+
+/// The chance that an item is checked in a check list.
+const CHECK_PROBABILITY: f64 = 0.3;
+
+/// Stores a check list with random checks for each of the `days` days up to
+/// `today`, skipping the days that already have one. Items are reused by name.
+/// Returns the stored entities.
+pub async fn seed<R: Rng + Send + ?Sized>(
+    store: &(dyn Store + Send + Sync),
+    rng: &mut R,
+    days: u32,
+    today: Date,
+) -> Result<Batch, Error> {
+    let check_lists = store.find_all_check_lists().await?;
+    let items = store.find_all_items().await?;
+    let batch = generate(rng, days, today, &check_lists, &items)?;
+    for chunk in batch.clone().chunks(BATCH_SIZE) {
+        store.store_batch(chunk).await?;
+    }
+    Ok(batch)
+}
+
+fn generate<R: Rng + ?Sized>(
+    rng: &mut R,
+    days: u32,
+    today: Date,
+    check_lists: &[model::CheckList],
+    items: &[model::Item],
+) -> Result<Batch, Error> {
+    let mut batch = Batch::default();
+    let mut items_by_name = items
+        .iter()
+        .map(|item| (item.name.clone(), item.id.clone()))
+        .collect::<BTreeMap<_, _>>();
+    for _ in 0..ITEM_SAMPLES {
+        let item = rng.gen::<model::Item>();
+        if items_by_name.contains_key(&item.name) {
+            continue;
+        }
+        items_by_name.insert(item.name.clone(), item.id.clone());
+        batch.items.push(item);
+    }
+    let item_ids = items_by_name.into_values().collect::<Vec<_>>();
+
+    for days_ago in (0..days).rev() {
+        let date = today
+            .checked_sub(Duration::days(i64::from(days_ago)))
+            .ok_or_else(|| Error::InvalidInput(format!("days {}", days)))?;
+        let date_string = date.to_string();
+        if check_lists
+            .iter()
+            .any(|check_list| check_list.date == date_string)
+        {
+            continue;
+        }
+        let check_list = model::CheckList {
+            date: date_string,
+            ..rng.gen()
+        };
+        for item_id in item_ids.iter() {
+            if !rng.gen_bool(CHECK_PROBABILITY) {
+                continue;
+            }
+            let time = Time::from_hms(rng.gen_range(8..21), rng.gen_range(0..60), 0)
+                .map_err(|e| Error::Unknown(e.to_string()))?;
+            let checked_at = date
+                .with_time(time)
+                .assume_utc()
+                .format(&Rfc3339)
+                .map_err(|e| Error::Unknown(e.to_string()))?;
+            batch.checks.push(model::Check {
+                check_list_id: check_list.id.clone(),
+                item_id: item_id.clone(),
+                checked_at: Some(checked_at),
+                ..rng.gen()
+            });
+        }
+        batch.check_lists.push(check_list);
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng as _};
+    use time::Month;
+
+    use crate::infra::store::InMemoryStore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_seed() -> anyhow::Result<()> {
+        let store = InMemoryStore::example();
+        let mut rng = StdRng::seed_from_u64(1);
+        let today = Date::from_calendar_date(2020, Month::January, 4)?;
+        let batch = seed(&store, &mut rng, 3, today).await?;
+        // 2020-01-02 and 2020-01-03 have check lists
+        assert_eq!(
+            batch
+                .check_lists
+                .iter()
+                .map(|check_list| check_list.date.as_str())
+                .collect::<Vec<_>>(),
+            vec!["2020-01-04"]
+        );
+        for item in batch.items.iter() {
+            item.validate()?;
+        }
+        for check in batch.checks.iter() {
+            check.validate()?;
+            assert!(check
+                .checked_at
+                .as_deref()
+                .is_some_and(|checked_at| checked_at.starts_with("2020-01-04T")));
+        }
+        assert_eq!(store.find_all_items().await?.len(), 2 + batch.items.len());
+        assert_eq!(store.find_all_checks().await?.len(), 2 + batch.checks.len());
+
+        let batch = seed(&store, &mut rng, 5, today.next_day().expect("next day")).await?;
+        assert_eq!(batch.check_lists.len(), 2);
+        // items are reused by name
+        let items = store.find_all_items().await?;
+        let names = items
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(names.len(), items.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,745 @@
+//! Offline-first sync.
+//!
+//! A client queues commands while offline and sends them with increasing
+//! per-client sequence numbers. The server applies each command once, then
+//! returns the changes made since the client's sync token.
+//!
+//! Conflict policy:
+//!
+//! - A command whose sequence is not greater than the last one stored for the
+//!   client is a duplicate and is not applied again.
+//! - `addItem`, `setItem` and `setChecked` are last-writer-wins in the order
+//!   the server receives them. `addItem` for an existing item updates it.
+//!   `setChecked` for a checked item replaces its quantity, price, shop, note
+//!   and `checkedAt`.
+//!   `setItem` (and `addItem` for an existing item) replaces the whole item:
+//!   an omitted `archivedAt`, `category`, `emoji`, `note`, `sortOrder` or
+//!   `unit` is cleared, so a client sends every field of the item. A client
+//!   that does not know a field clears it, e.g. restores an archived item.
+//! - `archiveItem` and `restoreItem` for an unknown item are rejected with
+//!   `NOT_FOUND`. `purgeItem` for an unknown item is applied as a no-op.
+//! - `addCheckList` for an existing id or date is rejected with
+//!   `ALREADY_EXISTS`. The client receives the server's check list through
+//!   the changes.
+//! - A command's sequence is claimed with a compare-and-set before the command
+//!   is applied, so concurrent syncs of the same commands apply it once.
+//! - A rejected command still consumes its sequence, so it is not retried.
+//!   Transient errors (e.g. a Firestore transaction aborted by contention)
+//!   give the sequence back and abort the sync.
+//! - Without a sync token, or if the token has expired, the response is a
+//!   full snapshot (`full: true`) of created changes: check lists, items, then
+//!   checks, each ordered by id. It is paged like the changes; the sync token
+//!   of a page with `has_more` continues the snapshot, and the token of the
+//!   last page continues with the changes made since the snapshot began.
+
+use crate::model;
+
+use super::{
+    data::{ChangeData, Command},
+    Error, Store,
+};
+
+/// The maximum number of changes returned by a sync.
+pub const CHANGES_LIMIT: usize = 1_000;
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRequest {
+    pub client_id: String,
+    pub commands: Vec<SequencedCommand>,
+    pub sync_token: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencedCommand {
+    pub command: Command,
+    pub sequence: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResponse {
+    pub changes: Vec<ChangeData>,
+    /// `changes` is a snapshot of the whole store
+    pub full: bool,
+    /// more changes are available with `sync_token`
+    pub has_more: bool,
+    pub results: Vec<CommandResult>,
+    pub sync_token: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub sequence: u64,
+    pub status: CommandStatus,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandStatus {
+    Applied,
+    Duplicate,
+    Rejected,
+}
+
+pub async fn sync(
+    store: &(dyn Store + Send + Sync),
+    request: SyncRequest,
+    limit: usize,
+) -> Result<SyncResponse, Error> {
+    if request.client_id.is_empty() {
+        return Err(Error::InvalidInput("client id is empty".to_owned()));
+    }
+    let sync_token = request
+        .sync_token
+        .as_deref()
+        .map(|sync_token| {
+            sync_token
+                .parse::<SyncToken>()
+                .map_err(|_| Error::InvalidInput(format!("sync token {}", sync_token)))
+        })
+        .transpose()?;
+
+    let mut last_sequence = store
+        .find_client_sequence(request.client_id.clone())
+        .await?;
+    let mut results = Vec::with_capacity(request.commands.len());
+    for SequencedCommand { command, sequence } in request.commands {
+        let previous_sequence = last_sequence;
+        if !claim_sequence(store, &request.client_id, &mut last_sequence, sequence).await? {
+            results.push(CommandResult {
+                code: None,
+                message: None,
+                sequence,
+                status: CommandStatus::Duplicate,
+            });
+            continue;
+        }
+        let result = match apply(store, command).await {
+            Ok(()) => CommandResult {
+                code: None,
+                message: None,
+                sequence,
+                status: CommandStatus::Applied,
+            },
+            Err(
+                e @ (Error::AlreadyExists(_)
+                | Error::Conflict(_)
+                | Error::InvalidInput(_)
+                | Error::NotFound(_)),
+            ) => CommandResult {
+                code: Some(e.code().to_owned()),
+                message: Some(e.to_string()),
+                sequence,
+                status: CommandStatus::Rejected,
+            },
+            Err(e) => {
+                // the retry applies the command again
+                if let Err(release_error) = store
+                    .compare_and_store_client_sequence(
+                        request.client_id.clone(),
+                        Some(sequence),
+                        previous_sequence,
+                    )
+                    .await
+                {
+                    tracing::warn!(error = %release_error, sequence, "cannot release the client sequence");
+                }
+                return Err(e);
+            }
+        };
+        results.push(result);
+    }
+
+    let (revision, cursor) = match sync_token {
+        Some(SyncToken::Changes(revision)) => {
+            match store.changes_since(revision, limit + 1).await {
+                Ok(mut changes) => {
+                    let has_more = changes.len() > limit;
+                    changes.truncate(limit);
+                    let sync_token = changes
+                        .last()
+                        .map(|change| change.revision)
+                        .unwrap_or(revision);
+                    return Ok(SyncResponse {
+                        changes: changes.into_iter().map(ChangeData::from).collect(),
+                        full: false,
+                        has_more,
+                        results,
+                        sync_token: SyncToken::Changes(sync_token).to_string(),
+                    });
+                }
+                Err(Error::Conflict(_)) => (store.find_current_revision().await?, None),
+                Err(e) => return Err(e),
+            }
+        }
+        Some(SyncToken::Snapshot(revision, cursor)) => (revision, Some(cursor)),
+        None => (store.find_current_revision().await?, None),
+    };
+    let (entities, next) = snapshot(store, cursor, limit).await?;
+    Ok(SyncResponse {
+        changes: entities
+            .into_iter()
+            .map(|entity| {
+                ChangeData::from(model::Change {
+                    entity,
+                    kind: model::ChangeKind::Created,
+                    revision,
+                })
+            })
+            .collect(),
+        full: true,
+        has_more: next.is_some(),
+        results,
+        sync_token: match next {
+            Some(cursor) => SyncToken::Snapshot(revision, cursor),
+            None => SyncToken::Changes(revision),
+        }
+        .to_string(),
+    })
+}
+
+/// Where the next sync continues: with the changes after a revision, or with
+/// a snapshot taken at a revision, after the entity at the cursor. Ids cannot
+/// contain `:`, e.g. `12` or `12:check:1:2`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum SyncToken {
+    Changes(u64),
+    Snapshot(u64, SnapshotCursor),
+}
+
+/// The last entity of a snapshot page.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum SnapshotCursor {
+    CheckList(String),
+    Item(String),
+    Check(String, String),
+}
+
+impl std::fmt::Display for SyncToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Changes(revision) => write!(f, "{}", revision),
+            Self::Snapshot(revision, SnapshotCursor::CheckList(id)) => {
+                write!(f, "{}:checkList:{}", revision, id)
+            }
+            Self::Snapshot(revision, SnapshotCursor::Item(id)) => {
+                write!(f, "{}:item:{}", revision, id)
+            }
+            Self::Snapshot(revision, SnapshotCursor::Check(check_list_id, item_id)) => {
+                write!(f, "{}:check:{}:{}", revision, check_list_id, item_id)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SyncToken {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let revision = parts
+            .next()
+            .and_then(|revision| revision.parse::<u64>().ok())
+            .ok_or(())?;
+        let token = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => Self::Changes(revision),
+            (Some("checkList"), Some(id), None) => {
+                Self::Snapshot(revision, SnapshotCursor::CheckList(id.to_owned()))
+            }
+            (Some("item"), Some(id), None) => {
+                Self::Snapshot(revision, SnapshotCursor::Item(id.to_owned()))
+            }
+            (Some("check"), Some(check_list_id), Some(item_id)) => Self::Snapshot(
+                revision,
+                SnapshotCursor::Check(check_list_id.to_owned(), item_id.to_owned()),
+            ),
+            _ => return Err(()),
+        };
+        match parts.next() {
+            Some(_) => Err(()),
+            None => Ok(token),
+        }
+    }
+}
+
+/// Advances the client's sequence to `sequence` and returns `true`, or returns
+/// `false` if a sync has already stored it or a later one. `last_sequence` is
+/// the stored sequence, which is reread when a concurrent sync advances it.
+async fn claim_sequence(
+    store: &(dyn Store + Send + Sync),
+    client_id: &str,
+    last_sequence: &mut Option<u64>,
+    sequence: u64,
+) -> Result<bool, Error> {
+    loop {
+        if last_sequence.is_some_and(|last_sequence| sequence <= last_sequence) {
+            return Ok(false);
+        }
+        match store
+            .compare_and_store_client_sequence(client_id.to_owned(), *last_sequence, Some(sequence))
+            .await
+        {
+            Ok(()) => {
+                *last_sequence = Some(sequence);
+                return Ok(true);
+            }
+            Err(Error::Conflict(_)) => {
+                *last_sequence = store.find_client_sequence(client_id.to_owned()).await?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn apply(store: &(dyn Store + Send + Sync), command: Command) -> Result<(), Error> {
+    match command {
+        Command::AddCheckList { check_list } => {
+            store
+                .store_check_list(model::CheckList::from(check_list))
+                .await
+        }
+        Command::AddItem { item } | Command::SetItem { item } => {
+            store.store_item(model::Item::from(item)).await
+        }
+        Command::ArchiveItem {
+            archived_at,
+            item_id,
+        } => store.archive_item(item_id, archived_at).await,
+        Command::PurgeItem { item_id } => store.purge_item(item_id).await,
+        Command::RestoreItem { item_id } => store.restore_item(item_id).await,
+        Command::SetChecked {
+            check_list_id,
+            checked: true,
+            checked_at,
+            item_id,
+            note,
+            price,
+            quantity,
+            shop,
+        } => {
+            store
+                .store_check(model::Check {
+                    check_list_id,
+                    item_id,
+                    checked_at,
+                    note,
+                    price: price.map(model::Price::from),
+                    quantity,
+                    shop,
+                    ..Default::default()
+                })
+                .await
+        }
+        Command::SetChecked {
+            check_list_id,
+            checked: false,
+            item_id,
+            ..
+        } => store.delete_check(check_list_id, item_id).await,
+    }
+}
+
+/// Returns up to `limit` entities of the current state after the cursor, and
+/// the cursor of the next page if there is one.
+///
+/// The revision is read before the first page. A write that lands while the
+/// snapshot is read is sent again as a change after the last page, which is
+/// harmless because the changes are applied last-writer-wins.
+async fn snapshot(
+    store: &(dyn Store + Send + Sync),
+    cursor: Option<SnapshotCursor>,
+    limit: usize,
+) -> Result<(Vec<model::Entity>, Option<SnapshotCursor>), Error> {
+    // one more entity than the page tells whether there is a next page
+    let mut entities = Vec::new();
+    if let None | Some(SnapshotCursor::CheckList(_)) = cursor {
+        let after = match &cursor {
+            Some(SnapshotCursor::CheckList(id)) => Some(id.clone()),
+            _ => None,
+        };
+        entities.extend(
+            store
+                .find_check_lists(after, limit + 1)
+                .await?
+                .into_iter()
+                .map(model::Entity::CheckList),
+        );
+    }
+    if entities.len() <= limit && !matches!(cursor, Some(SnapshotCursor::Check(..))) {
+        let after = match &cursor {
+            Some(SnapshotCursor::Item(id)) => Some(id.clone()),
+            _ => None,
+        };
+        entities.extend(
+            store
+                .find_items(after, limit + 1 - entities.len())
+                .await?
+                .into_iter()
+                .map(model::Entity::Item),
+        );
+    }
+    if entities.len() <= limit {
+        let after = match &cursor {
+            Some(SnapshotCursor::Check(check_list_id, item_id)) => {
+                Some((check_list_id.as_str(), item_id.as_str()))
+            }
+            _ => None,
+        };
+        let checks = store
+            .find_all_checks()
+            .await?
+            .into_iter()
+            .filter(|check| {
+                !after.is_some_and(|after| {
+                    (check.check_list_id.as_str(), check.item_id.as_str()) <= after
+                })
+            })
+            .take(limit + 1 - entities.len())
+            .map(model::Entity::Check)
+            .collect::<Vec<_>>();
+        entities.extend(checks);
+    }
+    if entities.len() <= limit {
+        return Ok((entities, None));
+    }
+    entities.truncate(limit);
+    let next = entities.last().map(|entity| match entity {
+        model::Entity::Check(check) => {
+            SnapshotCursor::Check(check.check_list_id.clone(), check.item_id.clone())
+        }
+        model::Entity::CheckList(check_list) => SnapshotCursor::CheckList(check_list.id.clone()),
+        model::Entity::Item(item) => SnapshotCursor::Item(item.id.clone()),
+    });
+    Ok((entities, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        infra::store::InMemoryStore,
+        use_case::data::{CheckListData, EntityData, ItemData},
+    };
+
+    use super::*;
+
+    fn add_item(sequence: u64, id: &str, name: &str) -> SequencedCommand {
+        SequencedCommand {
+            command: Command::AddItem {
+                item: ItemData {
+                    id: id.to_owned(),
+                    name: name.to_owned(),
+                    ..Default::default()
+                },
+            },
+            sequence,
+        }
+    }
+
+    fn statuses(response: &SyncResponse) -> Vec<CommandStatus> {
+        response
+            .results
+            .iter()
+            .map(|result| result.status)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_idempotent() -> anyhow::Result<()> {
+        let store = InMemoryStore::new();
+        let request = SyncRequest {
+            client_id: "a".to_owned(),
+            commands: vec![add_item(1, "1", "item1"), add_item(2, "1", "item1 renamed")],
+            sync_token: None,
+        };
+        let response = sync(&store, request.clone(), CHANGES_LIMIT).await?;
+        assert_eq!(
+            statuses(&response),
+            vec![CommandStatus::Applied, CommandStatus::Applied]
+        );
+        assert!(response.full);
+        assert_eq!(response.sync_token, "2");
+
+        // the response was lost and the client retries
+        let response = sync(&store, request, CHANGES_LIMIT).await?;
+        assert_eq!(
+            statuses(&response),
+            vec![CommandStatus::Duplicate, CommandStatus::Duplicate]
+        );
+        assert_eq!(store.find_current_revision().await?, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected() -> anyhow::Result<()> {
+        let store = InMemoryStore::example();
+        let request = SyncRequest {
+            client_id: "a".to_owned(),
+            commands: vec![
+                SequencedCommand {
+                    command: Command::AddCheckList {
+                        check_list: CheckListData {
+                            date: "2020-01-02".to_owned(),
+                            id: "3".to_owned(),
+                            version: 0,
+                        },
+                    },
+                    sequence: 1,
+                },
+                SequencedCommand {
+                    command: Command::SetChecked {
+                        check_list_id: "3".to_owned(),
+                        checked: true,
+                        checked_at: None,
+                        item_id: "1".to_owned(),
+                        note: None,
+                        price: None,
+                        quantity: None,
+                        shop: None,
+                    },
+                    sequence: 2,
+                },
+                add_item(3, "3", "item3"),
+            ],
+            sync_token: None,
+        };
+        let response = sync(&store, request, CHANGES_LIMIT).await?;
+        assert_eq!(
+            statuses(&response),
+            vec![
+                CommandStatus::Rejected,
+                CommandStatus::Rejected,
+                CommandStatus::Applied
+            ]
+        );
+        assert_eq!(response.results[0].code.as_deref(), Some("ALREADY_EXISTS"));
+        assert_eq!(response.results[1].code.as_deref(), Some("NOT_FOUND"));
+        assert_eq!(store.find_client_sequence("a".to_owned()).await?, Some(3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_changes_since_sync_token() -> anyhow::Result<()> {
+        let store = InMemoryStore::new();
+        let response = sync(
+            &store,
+            SyncRequest {
+                client_id: "a".to_owned(),
+                commands: vec![add_item(1, "1", "item1")],
+                sync_token: None,
+            },
+            CHANGES_LIMIT,
+        )
+        .await?;
+        let sync_token = response.sync_token;
+
+        // another client writes
+        sync(
+            &store,
+            SyncRequest {
+                client_id: "b".to_owned(),
+                commands: vec![add_item(1, "1", "item1 by b"), add_item(2, "2", "item2")],
+                sync_token: None,
+            },
+            CHANGES_LIMIT,
+        )
+        .await?;
+
+        let response = sync(
+            &store,
+            SyncRequest {
+                client_id: "a".to_owned(),
+                commands: vec![],
+                sync_token: Some(sync_token),
+            },
+            1,
+        )
+        .await?;
+        assert!(!response.full);
+        assert!(response.has_more);
+        assert_eq!(response.sync_token, "2");
+        assert_eq!(
+            response
+                .changes
+                .into_iter()
+                .map(|change| change.entity)
+                .collect::<Vec<_>>(),
+            vec![EntityData::Item(ItemData {
+                id: "1".to_owned(),
+                name: "item1 by b".to_owned(),
+                version: 2,
+                ..Default::default()
+            })]
+        );
+
+        let response = sync(
+            &store,
+            SyncRequest {
+                client_id: "a".to_owned(),
+                commands: vec![],
+                sync_token: Some(response.sync_token),
+            },
+            1,
+        )
+        .await?;
+        assert!(!response.has_more);
+        assert_eq!(response.sync_token, "3");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_item_replaces() -> anyhow::Result<()> {
+        let store = InMemoryStore::new();
+        let item = ItemData {
+            archived_at: Some("2020-01-02T03:04:05Z".to_owned()),
+            category: Some("dairy".to_owned()),
+            emoji: Some("🥛".to_owned()),
+            id: "1".to_owned(),
+            name: "milk".to_owned(),
+            note: Some("low fat".to_owned()),
+            sort_order: Some(1),
+            unit: Some("bottle".to_owned()),
+            version: 0,
+        };
+        let set_item = |sequence: u64, item: ItemData| SequencedCommand {
+            command: Command::SetItem { item },
+            sequence,
+        };
+        let response = sync(
+            &store,
+            SyncRequest {
+                client_id: "a".to_owned(),
+                commands: vec![
+                    set_item(1, item),
+                    set_item(
+                        2,
+                        ItemData {
+                            id: "1".to_owned(),
+                            name: "milk".to_owned(),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+                sync_token: None,
+            },
+            CHANGES_LIMIT,
+        )
+        .await?;
+        assert_eq!(
+            statuses(&response),
+            vec![CommandStatus::Applied, CommandStatus::Applied]
+        );
+        // the omitted fields are cleared
+        assert_eq!(
+            store.find_all_items().await?,
+            vec![model::Item {
+                id: "1".to_owned(),
+                name: "milk".to_owned(),
+                version: 2,
+                ..Default::default()
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_pages() -> anyhow::Result<()> {
+        let store = InMemoryStore::example();
+        let request = |sync_token: Option<String>| SyncRequest {
+            client_id: "a".to_owned(),
+            commands: vec![],
+            sync_token,
+        };
+        let whole = sync(&store, request(None), CHANGES_LIMIT).await?;
+        assert!(whole.full);
+        assert!(!whole.has_more);
+        let revision = store.find_current_revision().await?.to_string();
+        assert_eq!(whole.sync_token, revision);
+
+        let mut changes = vec![];
+        let mut sync_token = None;
+        loop {
+            let response = sync(&store, request(sync_token), 2).await?;
+            assert!(response.full);
+            assert!(response.changes.len() <= 2);
+            changes.extend(response.changes);
+            sync_token = Some(response.sync_token);
+            if !response.has_more {
+                break;
+            }
+        }
+        assert_eq!(changes, whole.changes);
+        // the last page continues with the changes
+        assert_eq!(sync_token, Some(revision));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_token() {
+        for sync_token in [
+            SyncToken::Changes(12),
+            SyncToken::Snapshot(12, SnapshotCursor::CheckList("1".to_owned())),
+            SyncToken::Snapshot(12, SnapshotCursor::Item("item-1".to_owned())),
+            SyncToken::Snapshot(12, SnapshotCursor::Check("1".to_owned(), "2".to_owned())),
+        ] {
+            assert_eq!(sync_token.to_string().parse(), Ok(sync_token));
+        }
+        assert_eq!(
+            "12:check:1:2".parse(),
+            Ok(SyncToken::Snapshot(
+                12,
+                SnapshotCursor::Check("1".to_owned(), "2".to_owned())
+            ))
+        );
+        for invalid in ["", "x", "12:item", "12:check:1", "12:item:1:2", "12:user:1"] {
+            assert_eq!(invalid.parse::<SyncToken>(), Err(()), "{}", invalid);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_retries() -> anyhow::Result<()> {
+        let store = std::sync::Arc::new(InMemoryStore::new());
+        let request = SyncRequest {
+            client_id: "a".to_owned(),
+            commands: vec![add_item(1, "1", "item1"), add_item(2, "2", "item2")],
+            sync_token: None,
+        };
+        let tasks = (0..8)
+            .map(|_| {
+                let (store, request) = (store.clone(), request.clone());
+                tokio::spawn(async move { sync(store.as_ref(), request, CHANGES_LIMIT).await })
+            })
+            .collect::<Vec<_>>();
+        let mut applied = 0;
+        for task in tasks {
+            applied += statuses(&task.await??)
+                .into_iter()
+                .filter(|status| *status == CommandStatus::Applied)
+                .count();
+        }
+        assert_eq!(applied, 2, "each command is applied once");
+        assert_eq!(store.find_current_revision().await?, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input() -> anyhow::Result<()> {
+        let store = InMemoryStore::new();
+        let request = SyncRequest {
+            client_id: "a".to_owned(),
+            commands: vec![],
+            sync_token: Some("x".to_owned()),
+        };
+        assert!(matches!(
+            sync(&store, request, CHANGES_LIMIT).await,
+            Err(Error::InvalidInput(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Users and their passwords.
+//!
+//! Passwords are stored as Argon2id hashes with a random salt, in the PHC
+//! string format (`$argon2id$v=19$m=...,t=...,p=...$<salt>$<hash>`).
+
+use argon2::{
+    password_hash::{
+        rand_core::OsRng, PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
+};
+
+use crate::model;
+
+use super::{Error, Store};
+
+const PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Fails with `Error::AlreadyExists` if the user exists.
+/// `params` is usually `Params::default()`, OWASP's recommendation for Argon2id.
+pub async fn add_user(
+    store: &(dyn Store + Send + Sync),
+    user_id: String,
+    password: &str,
+    params: Params,
+) -> Result<(), Error> {
+    if store.find_user(user_id.clone()).await?.is_some() {
+        return Err(Error::AlreadyExists(format!("user {}", user_id)));
+    }
+    store_user(store, user_id, password, params).await
+}
+
+/// Fails with `Error::NotFound` if the user does not exist.
+/// `params` is usually `Params::default()`, OWASP's recommendation for Argon2id.
+pub async fn reset_password(
+    store: &(dyn Store + Send + Sync),
+    user_id: String,
+    password: &str,
+    params: Params,
+) -> Result<(), Error> {
+    if store.find_user(user_id.clone()).await?.is_none() {
+        return Err(Error::NotFound(format!("user {}", user_id)));
+    }
+    store_user(store, user_id, password, params).await
+}
+
+async fn store_user(
+    store: &(dyn Store + Send + Sync),
+    user_id: String,
+    password: &str,
+    params: Params,
+) -> Result<(), Error> {
+    if password.chars().count() < PASSWORD_MIN_LENGTH {
+        return Err(Error::InvalidInput(format!(
+            "password must be at least {} characters",
+            PASSWORD_MIN_LENGTH
+        )));
+    }
+    let user = model::User {
+        id: user_id,
+        password_hash: hash_password(password, params)?,
+    };
+    user.validate()?;
+    store.store_user(user).await
+}
+
+pub fn hash_password(password: &str, params: Params) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| Error::Unknown(e.to_string()))
+}
+
+/// Returns false for a malformed hash.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infra::store::InMemoryStore;
+
+    use super::*;
+
+    fn params() -> Params {
+        Params::new(
+            Params::MIN_M_COST,
+            Params::MIN_T_COST,
+            Params::MIN_P_COST,
+            None,
+        )
+        .expect("params")
+    }
+
+    #[test]
+    fn test_verify_password() -> anyhow::Result<()> {
+        let password_hash = hash_password("password1", params())?;
+        assert!(password_hash.starts_with("$argon2id$v=19$m=8,t=1,p=1$"));
+        assert!(verify_password("password1", &password_hash));
+        assert!(!verify_password("password2", &password_hash));
+        assert!(!verify_password("password1", "password1"));
+        assert!(!verify_password(
+            "password1",
+            "pbkdf2-sha256$2$00112233445566778899aabbccddeeff$00"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_user_and_reset_password() -> anyhow::Result<()> {
+        let store = InMemoryStore::new();
+        add_user(&store, "user1".to_owned(), "password1", params()).await?;
+        assert!(matches!(
+            add_user(&store, "user1".to_owned(), "password1", params()).await,
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            add_user(&store, "user2".to_owned(), "short", params()).await,
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            add_user(&store, "user/2".to_owned(), "password1", params()).await,
+            Err(Error::InvalidInput(_))
+        ));
+        let user = store.find_user("user1".to_owned()).await?.expect("user1");
+        assert!(verify_password("password1", &user.password_hash));
+
+        reset_password(&store, "user1".to_owned(), "password2", params()).await?;
+        let user = store.find_user("user1".to_owned()).await?.expect("user1");
+        assert!(!verify_password("password1", &user.password_hash));
+        assert!(verify_password("password2", &user.password_hash));
+        assert!(matches!(
+            reset_password(&store, "user2".to_owned(), "password2", params()).await,
+            Err(Error::NotFound(_))
+        ));
+        Ok(())
+    }
+}